@@ -0,0 +1,4 @@
+//! Builds the C ABI of the `ffi` feature as `libinitramfs.so` / `libinitramfs.a`, as the main
+//! crate can't have these crate types without breaking `no_std` builds.
+
+pub use initramfs::ffi::*;
@@ -0,0 +1,90 @@
+#ifndef INITRAMFS_H
+#define INITRAMFS_H
+
+/* Generated by cbindgen, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct Initramfs Initramfs;
+
+/**
+ * Callback for [`initramfs_list`], called with the filename (not NUL-terminated) and size of
+ * every entry.
+ */
+typedef void (*InitramfsListCallback)(void *ctx,
+                                      const uint8_t *filename,
+                                      size_t filename_len,
+                                      uint32_t filesize);
+
+/**
+ * Parses `len` bytes at `data`, returning null if they aren't a valid initramfs or `data` is
+ * null.
+ *
+ * # Safety
+ *
+ * `data` must be null or valid for reads of `len` bytes.
+ */
+struct Initramfs *initramfs_parse(const uint8_t *data, size_t len);
+
+/**
+ * Frees an initramfs returned by [`initramfs_parse`]. Null is ignored.
+ *
+ * # Safety
+ *
+ * `initramfs` must be null or returned by [`initramfs_parse`] and not freed yet.
+ */
+void initramfs_free(struct Initramfs *initramfs);
+
+/**
+ * Calls `callback` for every entry of all parsed archives, excluding trailers, and returns the
+ * number of entries.
+ *
+ * # Safety
+ *
+ * `initramfs` must be a valid pointer returned by [`initramfs_parse`]. The filename passed to
+ * `callback` is only valid during the call.
+ */
+size_t initramfs_list(const struct Initramfs *initramfs, InitramfsListCallback callback, void *ctx);
+
+/**
+ * Looks up the entry the kernel would extract for `path` (see [`Initramfs::lookup`]), storing a
+ * pointer to its data in `data` and its length in `len`. Returns false if there is no such entry
+ * or `path` is null.
+ *
+ * # Safety
+ *
+ * `initramfs` must be a valid pointer returned by [`initramfs_parse`], `path` must be null or
+ * valid for reads of `path_len` bytes, and `data` and `len` must be valid for writes. The returned data is
+ * borrowed from `initramfs` and valid until it is freed.
+ */
+bool initramfs_get_file(const struct Initramfs *initramfs,
+                        const uint8_t *path,
+                        size_t path_len,
+                        const uint8_t **data,
+                        size_t *len);
+
+/**
+ * Serializes the initramfs into a newly allocated buffer, storing its length in `len`.
+ * The buffer must be released with [`initramfs_buffer_free`].
+ *
+ * # Safety
+ *
+ * `initramfs` must be a valid pointer returned by [`initramfs_parse`] and `len` must be valid
+ * for writes.
+ */
+uint8_t *initramfs_write(const struct Initramfs *initramfs, size_t *len);
+
+/**
+ * Frees a buffer returned by [`initramfs_write`]. Null is ignored.
+ *
+ * # Safety
+ *
+ * `data` must be null or returned by [`initramfs_write`] together with `len`, and not freed yet.
+ */
+void initramfs_buffer_free(uint8_t *data, size_t len);
+
+#endif  /* INITRAMFS_H */
@@ -0,0 +1,82 @@
+//! [`Arbitrary`] implementations generating structurally valid archives, which survive a
+//! [`Initramfs::write`] / [`Initramfs::parse`] round-trip unchanged.
+
+use alloc::vec::Vec;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::mode::{PERMISSION_BITS, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFREG, S_IFSOCK};
+use crate::{Archive, CpioHeader, CpioHeaderMagic, File, Initramfs};
+
+const FILE_TYPES: [u32; 7] = [S_IFREG, S_IFDIR, S_IFLNK, S_IFCHR, S_IFBLK, S_IFIFO, S_IFSOCK];
+
+impl<'a> Arbitrary<'a> for CpioHeaderMagic {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? { CpioHeaderMagic::WithChecksum } else { CpioHeaderMagic::WithoutChecksum })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CpioHeader {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let magic = u.arbitrary()?;
+        Ok(CpioHeader {
+            magic,
+            ino: u.arbitrary()?,
+            mode: *u.choose(&FILE_TYPES)? | u.int_in_range(0..=PERMISSION_BITS)?,
+            uid: u.arbitrary()?,
+            gid: u.arbitrary()?,
+            nlink: u.arbitrary()?,
+            mtime: u.arbitrary()?,
+            filesize: u.arbitrary()?,
+            maj: u.arbitrary()?,
+            min: u.arbitrary()?,
+            rmaj: u.arbitrary()?,
+            rmin: u.arbitrary()?,
+            namesize: u.arbitrary()?,
+            chksum: if magic == CpioHeaderMagic::WithChecksum { u.arbitrary()? } else { 0 },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for File {
+    /// Generates a non-trailer entry with a non-empty, NUL-free filename and consistent
+    /// `namesize`, `filesize` and `chksum`.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let header = u.arbitrary()?;
+        let mut filename: Vec<u8> = u.arbitrary::<Vec<u8>>()?.into_iter().filter(|&b| b != 0).collect();
+        if filename.is_empty() || filename == b"TRAILER!!!" {
+            filename.insert(0, b'f');
+        }
+        let mut file = File {
+            header,
+            filename: Vec::new(),
+            data: Vec::new(),
+            raw_header: None,
+            #[cfg(feature = "std")]
+            source: None,
+        };
+        file.set_filename(filename);
+        file.set_data(u.arbitrary()?);
+        Ok(file)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Archive {
+    /// Generates an archive of arbitrary entries terminated by a trailer.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut archive = Archive { files: u.arbitrary()?, padding: None };
+        archive.add_trailer();
+        Ok(archive)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Initramfs {
+    /// Generates an initramfs of parsed archives only.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut initramfs = Initramfs::new();
+        for archive in u.arbitrary_iter::<Archive>()? {
+            initramfs.add_archive(archive?);
+        }
+        Ok(initramfs)
+    }
+}
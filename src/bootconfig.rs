@@ -0,0 +1,64 @@
+// format: https://www.kernel.org/doc/html/latest/admin-guide/bootconfig.html
+
+const MAGIC: &[u8] = b"#BOOTCONFIG\n";
+
+/// Splits a boot configuration appended by `tools/bootconfig` off the end of an initrd,
+/// returning the initrd and the configuration (including its nul padding).
+///
+/// Like the kernel, up to 3 bytes of zero padding after the magic are accepted, and
+/// configurations with a wrong checksum are left in place. Returns `None` if there is none.
+pub fn split_bootconfig(image: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = (0..4)
+        .map(|padding| image.len().saturating_sub(padding))
+        .take_while(|&end| image[end..].iter().all(|&b| b == 0))
+        .find(|&end| image[..end].ends_with(MAGIC))?;
+    let footer = end.checked_sub(MAGIC.len() + 8)?;
+    let word = |offset: usize| u32::from_le_bytes(image[offset..offset + 4].try_into().unwrap());
+    let (size, checksum) = (word(footer), word(footer + 4));
+    let start = footer.checked_sub(usize::try_from(size).ok()?)?;
+    let config = &image[start..footer];
+    let sum = config.iter().fold(0u32, |sum, &b| sum.wrapping_add(b.into()));
+    (sum == checksum).then_some((&image[..start], config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// `image` with `config` appended like `tools/bootconfig -a`, followed by `padding` zeros.
+    fn append(image: &[u8], config: &[u8], padding: usize) -> Vec<u8> {
+        let checksum = config.iter().fold(0u32, |sum, &b| sum.wrapping_add(b.into()));
+        let mut out = image.to_vec();
+        out.extend_from_slice(config);
+        out.extend_from_slice(&(config.len() as u32).to_le_bytes());
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out.extend_from_slice(MAGIC);
+        out.resize(out.len() + padding, 0);
+        out
+    }
+
+    #[test]
+    fn split_bootconfig_with_padding() {
+        let config = b"kernel.console = ttyS0\n\0\0";
+        for padding in 0..4 {
+            let image = append(b"070701initrd", config, padding);
+            assert_eq!(split_bootconfig(&image), Some((&b"070701initrd"[..], &config[..])), "padding {padding}");
+        }
+        assert_eq!(split_bootconfig(&append(b"initrd", config, 4)), None);
+    }
+
+    #[test]
+    fn split_bootconfig_rejects_invalid() {
+        let config = b"init = /sbin/init\n";
+        let mut image = append(b"initrd", config, 0);
+        image[7] ^= 1;
+        assert_eq!(split_bootconfig(&image), None);
+        assert_eq!(split_bootconfig(b"initrd"), None);
+        assert_eq!(split_bootconfig(MAGIC), None);
+        // size larger than the image
+        let mut image = append(b"", config, 0);
+        image[config.len()] = 0xff;
+        assert_eq!(split_bootconfig(&image), None);
+    }
+}
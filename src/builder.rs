@@ -0,0 +1,211 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::fs::{file_from_host, file_from_metadata, host_xattrs, visit_dir};
+use crate::conflict::conflicts;
+use crate::source::{write_padding, ReaderFactory};
+use crate::{normalize_path, CpioHeaderMagic, DataSource, DirOptions, File, OnConflict, PathConflict};
+
+/// Builds an archive directly into a writer, streaming file data instead of keeping it in memory.
+///
+/// The written archive is the same as [`Archive::write`](crate::Archive::write) would produce,
+/// including the trailer.
+#[derive(Default)]
+pub struct ArchiveBuilder<'a> {
+    /// `None` for entries removed by [`OnConflict::ReplaceExisting`]
+    entries: Vec<Option<Entry<'a>>>,
+    on_conflict: OnConflict,
+    /// normalized path -> indices of the entries with it
+    paths: BTreeMap<Vec<u8>, Vec<usize>>,
+    /// first conflict with [`OnConflict::Error`], reported by `write`
+    conflict: Option<PathConflict>,
+}
+
+struct Entry<'a> {
+    /// header and filename; for streamed entries the data is empty but `filesize` set
+    file: File,
+    source: Source<'a>,
+}
+
+enum Source<'a> {
+    /// data in `Entry::file`
+    InMemory,
+    Reader(Box<dyn Read + 'a>),
+    Path(PathBuf),
+    Factory(ReaderFactory<'a>),
+    #[cfg(feature = "mmap")]
+    Mmap(memmap2::Mmap),
+}
+
+impl<'a> ArchiveBuilder<'a> {
+    pub fn new() -> ArchiveBuilder<'a> {
+        ArchiveBuilder::default()
+    }
+
+    /// Sets how entries with the path of an already added entry are handled, by default
+    /// [`OnConflict::KeepBoth`].
+    ///
+    /// With [`OnConflict::Error`], conflicting entries aren't added, and [`add_dir`](Self::add_dir)
+    /// or at the latest [`write`](Self::write) fail with [`io::ErrorKind::AlreadyExists`].
+    pub fn set_on_conflict(&mut self, on_conflict: OnConflict) {
+        self.on_conflict = on_conflict;
+    }
+
+    /// Adds a file, setting its inode number like [`Archive::add_file`](crate::Archive::add_file).
+    /// Lazy data (see [`File::set_source`]) is only read while writing.
+    pub fn add_file(&mut self, mut file: File) {
+        match file.source.take() {
+            Some(source) => self.add_entry(file, source.scoped()),
+            None => self.push(file, Source::InMemory),
+        }
+    }
+
+    /// Adds a regular file whose `len` bytes of data are read from `reader` while writing.
+    ///
+    /// Streamed entries always use the `070701` format, as the checksum would have to be known
+    /// before the data is read.
+    pub fn add_file_from_reader(&mut self, path: String, reader: impl Read + 'a, len: u32) {
+        let mut file = File::new(path, Vec::new());
+        file.header.filesize = len;
+        self.push(file, Source::Reader(Box::new(reader)));
+    }
+
+    /// Adds an entry whose data comes from `source`. Only one entry's data is read at a time
+    /// while writing, for [`Path`](DataSource::Path) and [`Reader`](DataSource::Reader) sources
+    /// nothing is read before.
+    ///
+    /// `file.header.filesize` has to be the length of the data, which is checked while writing;
+    /// `file.data` is ignored. For sources other than [`Bytes`](DataSource::Bytes), the entry
+    /// uses the `070701` format like [`add_file_from_reader`](Self::add_file_from_reader).
+    pub fn add_entry(&mut self, mut file: File, source: DataSource<'a>) {
+        file.raw_header = None;
+        let source = match source {
+            DataSource::Bytes(data) => {
+                file.set_data(data);
+                Source::InMemory
+            }
+            DataSource::Path(path) => Source::Path(path),
+            DataSource::Reader(factory) => Source::Factory(factory),
+        };
+        if !matches!(source, Source::InMemory) {
+            file.data = Vec::new();
+            file.header.magic = CpioHeaderMagic::WithoutChecksum;
+            file.header.chksum = 0;
+        }
+        self.push(file, source);
+    }
+
+    /// Adds an entry with the next inode number, resolving conflicts according to `on_conflict`.
+    fn push(&mut self, mut file: File, source: Source<'a>) {
+        file.header.ino = self.entries.len() as u32;
+        if !file.is_metadata() {
+            let indices = self.paths.entry(normalize_path(&file.filename).to_vec()).or_default();
+            let existing: Vec<_> = indices.iter().copied()
+                .filter(|&i| self.entries[i].as_ref().is_some_and(|entry| conflicts(&entry.file, &file)))
+                .collect();
+            match self.on_conflict {
+                OnConflict::Error if !existing.is_empty() => {
+                    self.conflict.get_or_insert_with(|| PathConflict { path: normalize_path(&file.filename).to_vec(), index: existing[0] });
+                    return;
+                }
+                OnConflict::ReplaceExisting => for i in existing {
+                    self.entries[i] = None;
+                    for entry in &mut self.entries[i + 1..] {
+                        match entry {
+                            Some(metadata) if metadata.file.is_metadata() => *entry = None,
+                            _ => break,
+                        }
+                    }
+                },
+                _ => (),
+            }
+            indices.push(self.entries.len());
+        }
+        self.entries.push(Some(Entry { file, source }));
+    }
+
+    fn conflict_error(&self) -> io::Result<()> {
+        match &self.conflict {
+            Some(conflict) => Err(io::Error::new(io::ErrorKind::AlreadyExists, conflict.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds the contents of a host directory like [`Archive::from_dir`](crate::Archive::from_dir).
+    ///
+    /// Regular files at or above [`DirOptions::mmap_threshold`] are memory-mapped (with the `mmap`
+    /// feature) or streamed from disk while writing, so they never have to fit into memory at once.
+    pub fn add_dir(&mut self, root: impl AsRef<Path>, options: &DirOptions) -> io::Result<()> {
+        visit_dir(root.as_ref(), &mut |filename, path, metadata| {
+            self.add_host_file(filename, path, metadata, options)?;
+            let xattrs = host_xattrs(path, options)?;
+            if !xattrs.is_empty() {
+                self.add_file(File::new_xattrs(&xattrs));
+            }
+            Ok(())
+        })?;
+        self.conflict_error()
+    }
+
+    /// Adds a single host entry, memory-mapping or streaming large regular files.
+    fn add_host_file(&mut self, filename: Vec<u8>, path: &Path, metadata: &std::fs::Metadata, options: &DirOptions) -> io::Result<()> {
+        let large = options.mmap_threshold.is_some_and(|threshold| metadata.len() >= threshold);
+        if !metadata.is_file() || !large {
+            self.add_file(file_from_host(filename, path, metadata)?);
+            return Ok(());
+        }
+        let file = file_from_metadata(filename, metadata)?;
+        // SAFETY: the mapping is only read while writing; the file must not be truncated
+        // concurrently, which is the same requirement as for reading it
+        #[cfg(feature = "mmap")]
+        let source = Source::Mmap(unsafe { memmap2::Mmap::map(&std::fs::File::open(path)?)? });
+        #[cfg(not(feature = "mmap"))]
+        let source = Source::Path(path.to_path_buf());
+        self.push(file, source);
+        Ok(())
+    }
+
+    /// Writes all entries followed by the trailer, returning the number of written bytes.
+    pub fn write(self, mut out: impl Write) -> io::Result<u64> {
+        self.conflict_error()?;
+        let mut written = 0;
+        let trailer = Entry { file: File::new("TRAILER!!!".to_string(), Vec::new()), source: Source::InMemory };
+        let mut buf = Vec::new();
+        for entry in self.entries.into_iter().flatten().chain(core::iter::once(trailer)) {
+            written += write_padding(&mut out, written, 4)?;
+            // header and filename are encoded starting at an aligned position, so their padding
+            // is the same as in the output
+            buf.clear();
+            entry.file.write(&mut buf);
+            out.write_all(&buf)?;
+            written += buf.len() as u64;
+            let len = u64::from(entry.file.header.filesize);
+            let reader: Box<dyn Read + 'a> = match entry.source {
+                Source::InMemory => continue,
+                Source::Reader(reader) => reader,
+                Source::Path(path) => Box::new(io::BufReader::new(std::fs::File::open(path)?)),
+                Source::Factory(factory) => factory()?,
+                #[cfg(feature = "mmap")]
+                Source::Mmap(mmap) => {
+                    if mmap.len() as u64 != len {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file changed size while building"));
+                    }
+                    out.write_all(&mmap)?;
+                    written += len;
+                    continue;
+                }
+            };
+            let copied = io::copy(&mut reader.take(len), &mut out)?;
+            if copied != len {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "reader returned less data than announced"));
+            }
+            written += len;
+        }
+        written += write_padding(&mut out, written, 4096)?;
+        Ok(written)
+    }
+}
@@ -0,0 +1,192 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+use crate::Archive;
+#[cfg(feature = "parse-cache")]
+use crate::{Error, Initramfs, ParseOptions};
+
+impl Archive {
+    /// SHA-256 of the archive as written by [`Archive::write`].
+    pub fn digest(&self) -> [u8; 32] {
+        let mut data = Vec::new();
+        self.write(&mut data);
+        Sha256::digest(&data).into()
+    }
+}
+
+/// Cache of compressed archives keyed by the digest of their uncompressed encoding, so that
+/// rebuilding an image only recompresses archives that changed.
+///
+/// Entries are kept in memory, and additionally stored as files in a directory if the cache
+/// was created with [`CompressionCache::open`], so they survive between runs (e.g. on CI).
+#[derive(Debug, Clone, Default)]
+pub struct CompressionCache {
+    dir: Option<PathBuf>,
+    /// (compressor, digest) -> compressed data
+    entries: HashMap<(String, [u8; 32]), Vec<u8>>,
+}
+
+impl CompressionCache {
+    /// Creates an in-memory cache.
+    pub fn new() -> CompressionCache {
+        CompressionCache::default()
+    }
+
+    /// Creates a cache backed by a directory, creating it if necessary.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<CompressionCache> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(CompressionCache { dir: Some(dir), entries: HashMap::new() })
+    }
+
+    /// Returns the compressed form of `archive`, calling `compress` with its encoding on a miss.
+    ///
+    /// `compressor` identifies the compression algorithm and settings (e.g. `"zstd-19"`), outputs
+    /// of different compressors are cached separately. As it is part of the cache file names, it
+    /// must not be empty or contain `/`, `\\` or `..`, otherwise an [`io::ErrorKind::InvalidInput`]
+    /// error is returned.
+    pub fn get_or_compress<F>(&mut self, archive: &Archive, compressor: &str, compress: F) -> io::Result<&[u8]>
+    where
+        F: FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+    {
+        if compressor.is_empty() || compressor.contains(['/', '\\']) || compressor.contains("..") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid compressor name {compressor:?}")));
+        }
+        let mut data = Vec::new();
+        archive.write(&mut data);
+        let digest: [u8; 32] = Sha256::digest(&data).into();
+        let key = (String::from(compressor), digest);
+        if !self.entries.contains_key(&key) {
+            let path = self.dir.as_ref().map(|dir| dir.join(format!("{}.{compressor}", hex::encode(digest))));
+            let compressed = match path.as_ref().map(std::fs::read) {
+                Some(Ok(compressed)) => compressed,
+                Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {
+                    log::debug!("compressing archive {} with {compressor}", hex::encode(digest));
+                    let compressed = compress(&data)?;
+                    if let Some(path) = &path {
+                        // written to a temporary file first, so concurrent readers never see partial entries
+                        let mut temporary = path.clone().into_os_string();
+                        temporary.push(format!(".{}.tmp", std::process::id()));
+                        std::fs::write(&temporary, &compressed).and_then(|()| std::fs::rename(&temporary, path))?;
+                    }
+                    compressed
+                }
+            };
+            self.entries.insert(key.clone(), compressed);
+        }
+        Ok(&self.entries[&key])
+    }
+
+    /// Removes all entries from memory, files in the cache directory are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Cache of parsed images in a directory, so that tools reopening the same large image skip
+/// parsing it again.
+///
+/// Entries are keyed by the SHA-256 of the image together with the parser and a format version,
+/// so modified images are reparsed automatically.
+#[cfg(feature = "parse-cache")]
+#[derive(Debug, Clone)]
+pub struct ParseCache {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "parse-cache")]
+impl ParseCache {
+    /// Bumped whenever the serialized representation changes.
+    const VERSION: u8 = 1;
+
+    /// Creates a cache backed by a directory, creating it if necessary.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<ParseCache> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(ParseCache { dir })
+    }
+
+    pub fn parse(&self, data: &[u8]) -> Result<Initramfs, Error> {
+        self.parse_with(data, &ParseOptions::default())
+    }
+
+    pub fn parse_with(&self, data: &[u8], options: &ParseOptions) -> Result<Initramfs, Error> {
+        self.get_or_parse(data, &format!("{options:?}"), |data| Initramfs::parse_with(data, options))
+    }
+
+    /// Returns the cached result of parsing `image`, calling `parse` with it on a miss.
+    ///
+    /// `image` can be the file as read from disk, so that `parse` can e.g. decompress it first,
+    /// which is skipped as well on a hit. `parser` identifies `parse` and its settings, results of
+    /// different parsers are cached separately. Failing to read or write the cache only logs a warning.
+    pub fn get_or_parse<F>(&self, image: &[u8], parser: &str, parse: F) -> Result<Initramfs, Error>
+    where
+        F: FnOnce(&[u8]) -> Result<Initramfs, Error>,
+    {
+        let mut hasher = Sha256::new();
+        hasher.update([ParseCache::VERSION]);
+        hasher.update(parser.len().to_le_bytes());
+        hasher.update(parser);
+        hasher.update(image);
+        let path = self.dir.join(format!("{}.parsed", hex::encode(hasher.finalize())));
+        match std::fs::read(&path) {
+            Ok(cached) => match postcard::from_bytes(&cached) {
+                Ok(initramfs) => return Ok(initramfs),
+                Err(e) => log::warn!("ignoring invalid cache entry {}: {e}", path.display()),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => log::warn!("can't read cache entry {}: {e}", path.display()),
+        }
+        let initramfs = parse(image)?;
+        let serialized = postcard::to_allocvec(&initramfs).expect("serializing to memory can't fail");
+        // written to a temporary file first, so concurrent readers never see partial entries
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        if let Err(e) = std::fs::write(&temporary, serialized).and_then(|()| std::fs::rename(&temporary, &path)) {
+            log::warn!("can't write cache entry {}: {e}", path.display());
+        }
+        Ok(initramfs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use crate::File;
+
+    #[test]
+    fn compression_cache_stores_entries_in_dir() {
+        let dir = std::env::temp_dir().join(format!("initramfs-compression-cache-{}", std::process::id()));
+        let mut archive = Archive::new();
+        archive.add_file(File::new("file".to_string(), b"data".to_vec()));
+        archive.add_trailer();
+        let reverse = |data: &[u8]| Ok(data.iter().rev().copied().collect());
+
+        let mut cache = CompressionCache::open(&dir).unwrap();
+        let compressed = cache.get_or_compress(&archive, "reverse", reverse).unwrap().to_vec();
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+        assert_eq!(files, [format!("{}.reverse", hex::encode(archive.digest()))]);
+
+        let mut cache = CompressionCache::open(&dir).unwrap();
+        let cached = cache.get_or_compress(&archive, "reverse", |_| panic!("compressed again")).unwrap();
+        assert_eq!(cached, compressed);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compression_cache_rejects_paths_as_compressor() {
+        let mut cache = CompressionCache::new();
+        for compressor in ["", "../zstd", "zstd/19", "..", "a\\b"] {
+            let err = cache.get_or_compress(&Archive::new(), compressor, |data| Ok(vec![0; data.len()])).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
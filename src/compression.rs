@@ -0,0 +1,213 @@
+use core::fmt::{Display, Formatter};
+
+#[cfg(feature = "compression")]
+use crate::{Archive, Error, Initramfs};
+
+/// Compression formats the kernel can unpack an initramfs segment from.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Codec {
+    /// plain newc cpio archive
+    Uncompressed,
+    Gzip,
+    Bzip2,
+    /// legacy `.lzma` format
+    Lzma,
+    Xz,
+    /// `lzop` format
+    Lzo,
+    /// legacy LZ4 format as produced by `lz4 -l`
+    Lz4,
+    Zstd,
+}
+
+impl Codec {
+    pub const ALL: [Codec; 8] = [
+        Codec::Uncompressed, Codec::Gzip, Codec::Bzip2, Codec::Lzma, Codec::Xz, Codec::Lzo, Codec::Lz4, Codec::Zstd,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Uncompressed => "uncompressed",
+            Codec::Gzip => "gzip",
+            Codec::Bzip2 => "bzip2",
+            Codec::Lzma => "lzma",
+            Codec::Xz => "xz",
+            Codec::Lzo => "lzo",
+            Codec::Lz4 => "lz4",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    /// Kernel config symbol enabling support for initramfs segments in this format,
+    /// `None` for uncompressed archives, which are always supported.
+    pub fn kernel_config(self) -> Option<&'static str> {
+        match self {
+            Codec::Uncompressed => None,
+            Codec::Gzip => Some("RD_GZIP"),
+            Codec::Bzip2 => Some("RD_BZIP2"),
+            Codec::Lzma => Some("RD_LZMA"),
+            Codec::Xz => Some("RD_XZ"),
+            Codec::Lzo => Some("RD_LZO"),
+            Codec::Lz4 => Some("RD_LZ4"),
+            Codec::Zstd => Some("RD_ZSTD"),
+        }
+    }
+}
+
+impl Display for Codec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Detects the format of an initramfs segment from its magic bytes, like the kernel does
+/// when unpacking it.
+///
+/// Returns `None` for unknown data. Leading zero padding has to be skipped by the caller.
+pub fn detect_compression(data: &[u8]) -> Option<Codec> {
+    const MAGICS: [(&[u8], Codec); 10] = [
+        (b"070701", Codec::Uncompressed),
+        (b"070702", Codec::Uncompressed),
+        (b"\x1f\x8b", Codec::Gzip),
+        // old gzip
+        (b"\x1f\x9e", Codec::Gzip),
+        (b"BZh", Codec::Bzip2),
+        (b"\x5d\x00\x00", Codec::Lzma),
+        (b"\xfd7zXZ\x00", Codec::Xz),
+        (b"\x89LZO\x00\r\n\x1a\n", Codec::Lzo),
+        (b"\x02\x21\x4c\x18", Codec::Lz4),
+        (b"\x28\xb5\x2f\xfd", Codec::Zstd),
+    ];
+    MAGICS.iter().find(|(magic, _)| data.starts_with(magic)).map(|&(_, codec)| codec)
+}
+
+/// Detects the format of a segment and decompresses it, returning uncompressed archives as-is.
+#[cfg(feature = "compression")]
+pub fn decompress(data: &[u8]) -> Result<(Codec, alloc::vec::Vec<u8>), Error> {
+    use std::io::Read;
+
+    let codec = detect_compression(data).ok_or(Error::UnknownCompression)?;
+    let mut decompressed = alloc::vec::Vec::new();
+    // single streams, as trailing zero padding isn't a valid next stream
+    let ok = match codec {
+        Codec::Uncompressed => {
+            decompressed.extend_from_slice(data);
+            true
+        }
+        Codec::Gzip => flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed).is_ok(),
+        Codec::Bzip2 => bzip2::read::BzDecoder::new(data).read_to_end(&mut decompressed).is_ok(),
+        Codec::Lzma => decompress_unpadded(data, &mut decompressed, |data, out| lzma_rs::lzma_decompress(&mut { data }, out).is_ok()),
+        Codec::Xz => decompress_unpadded(data, &mut decompressed, |data, out| lzma_rs::xz_decompress(&mut { data }, out).is_ok()),
+        Codec::Lz4 => decompress_lz4_legacy(data, &mut decompressed).is_some(),
+        Codec::Zstd => ruzstd::decoding::StreamingDecoder::new(data)
+            .is_ok_and(|mut decoder| decoder.read_to_end(&mut decompressed).is_ok()),
+        Codec::Lzo => return Err(Error::UnsupportedCompression(codec)),
+    };
+    if !ok {
+        return Err(Error::Decompression(codec));
+    }
+    Ok((codec, decompressed))
+}
+
+/// The lzma and xz decoders reject trailing data, but segments are commonly followed by zero
+/// padding. It's stripped, adding back zeros that belong to the stream until decoding succeeds.
+#[cfg(feature = "compression")]
+fn decompress_unpadded<F>(data: &[u8], decompressed: &mut alloc::vec::Vec<u8>, decompress: F) -> bool
+where
+    F: Fn(&[u8], &mut alloc::vec::Vec<u8>) -> bool,
+{
+    let end = data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    (end..=data.len().min(end + 8)).any(|end| {
+        decompressed.clear();
+        decompress(&data[..end], decompressed)
+    })
+}
+
+/// Decompresses the legacy LZ4 format: a magic followed by blocks prefixed with their little-endian
+/// size, each decompressing to at most 8 MiB. Concatenated streams repeat the magic.
+#[cfg(feature = "compression")]
+fn decompress_lz4_legacy(data: &[u8], decompressed: &mut alloc::vec::Vec<u8>) -> Option<()> {
+    const MAGIC: u32 = 0x184c2102;
+    let mut rest = &data[4..];
+    while let Some((size, tail)) = rest.split_first_chunk::<4>() {
+        let size = u32::from_le_bytes(*size);
+        rest = tail;
+        match size {
+            MAGIC => continue,
+            // zero padding after the last block
+            0 => break,
+            _ => (),
+        }
+        let block = rest.get(..size as usize)?;
+        rest = &rest[block.len()..];
+        decompressed.extend(lz4_flex::block::decompress(block, 8 << 20).ok()?);
+    }
+    Some(())
+}
+
+/// Compresses a segment in a format the kernel can unpack.
+///
+/// The pure-Rust encoders favor speed over ratio, so `lzma` and `zstd` compress noticeably worse
+/// than their reference tools, and `xz` data is only stored, not compressed.
+/// Compressing to [`Codec::Lzo`] isn't supported.
+#[cfg(feature = "compression")]
+pub fn compress(data: &[u8], codec: Codec) -> Result<alloc::vec::Vec<u8>, Error> {
+    use std::io::Write;
+
+    let io_error = |e: std::io::Error| Error::Io(e.kind());
+    let mut compressed = alloc::vec::Vec::new();
+    match codec {
+        Codec::Uncompressed => compressed.extend_from_slice(data),
+        Codec::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(&mut compressed, flate2::Compression::best());
+            encoder.write_all(data).and_then(|()| encoder.finish().map(drop)).map_err(io_error)?;
+        }
+        Codec::Bzip2 => {
+            let mut encoder = bzip2::write::BzEncoder::new(&mut compressed, bzip2::Compression::best());
+            encoder.write_all(data).and_then(|()| encoder.finish().map(drop)).map_err(io_error)?;
+        }
+        Codec::Lzma => lzma_rs::lzma_compress(&mut { data }, &mut compressed).map_err(io_error)?,
+        Codec::Xz => lzma_rs::xz_compress(&mut { data }, &mut compressed).map_err(io_error)?,
+        Codec::Lz4 => {
+            compressed.extend_from_slice(&0x184c2102u32.to_le_bytes());
+            for block in data.chunks(8 << 20) {
+                let block = lz4_flex::block::compress(block);
+                compressed.extend_from_slice(&(block.len() as u32).to_le_bytes());
+                compressed.extend_from_slice(&block);
+            }
+        }
+        Codec::Zstd => compressed = ruzstd::encoding::compress_to_vec(data, ruzstd::encoding::CompressionLevel::Fastest),
+        Codec::Lzo => return Err(Error::UnsupportedCompression(codec)),
+    }
+    Ok(compressed)
+}
+
+#[cfg(feature = "compression")]
+impl Initramfs {
+    /// Compresses the written image with every supported codec, returning the resulting sizes
+    /// sorted from smallest to largest. See [`compress`] regarding their accuracy.
+    ///
+    /// These are the sizes of the output of [`compress`], so `xz` reports the size of stored,
+    /// uncompressed data, not what `xz` itself would achieve.
+    pub fn estimate_compressed_sizes(&self) -> alloc::vec::Vec<(Codec, usize)> {
+        let data = self.to_bytes();
+        let mut sizes: alloc::vec::Vec<_> = Codec::ALL.into_iter()
+            .filter_map(|codec| Some((codec, compress(&data, codec).ok()?.len())))
+            .collect();
+        sizes.sort_by_key(|&(_, size)| size);
+        sizes
+    }
+}
+
+#[cfg(feature = "compression")]
+impl Archive {
+    /// Detects the format of a (e.g. raw) segment, decompresses and parses it.
+    ///
+    /// Only the first archive of the decompressed data is parsed.
+    pub fn parse_compressed(raw: &[u8]) -> Result<(Codec, Archive), Error> {
+        let raw = &raw[crate::parse_leading_zeroes(raw, 0)..];
+        let (codec, data) = decompress(raw)?;
+        let (archive, _) = Archive::parse(&data, 0)?;
+        Ok((codec, archive))
+    }
+}
@@ -0,0 +1,149 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::{normalize_path, Archive, File, FileType};
+
+/// What [`Archive::add_file_with`], [`Archive::append_with`] and [`ArchiveBuilder`](crate::ArchiveBuilder) do when an
+/// entry with the same path already exists.
+///
+/// Directories added over directories never conflict, as the kernel merges them.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum OnConflict {
+    /// fails with [`PathConflict`]
+    Error,
+    /// removes the existing entries (and their `METADATA!!!` entries)
+    ReplaceExisting,
+    /// adds the entry anyway, so it shadows the existing one when unpacking, like
+    /// [`Archive::add_file`]
+    #[default]
+    KeepBoth,
+}
+
+/// Error of [`OnConflict::Error`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PathConflict {
+    /// normalized path
+    pub path: Vec<u8>,
+    /// index of the existing entry
+    pub index: usize,
+}
+
+impl Display for PathConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} already exists as entry {}", String::from_utf8_lossy(&self.path), self.index)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PathConflict {}
+
+/// Whether adding `new` after `existing` shadows it.
+pub(crate) fn conflicts(existing: &File, new: &File) -> bool {
+    if existing.is_trailer() || existing.is_metadata() || new.is_metadata() {
+        return false;
+    }
+    let both_dirs = [existing, new].iter().all(|file| file.header.file_type() == FileType::Directory);
+    !both_dirs && normalize_path(&existing.filename) == normalize_path(&new.filename)
+}
+
+impl Archive {
+    /// Like [`add_file`](Self::add_file), but resolves entries with the same path according
+    /// to `on_conflict`.
+    ///
+    /// When replacing, the inode number is one more than the highest one left, so it can't
+    /// collide with a remaining entry.
+    ///
+    /// Like [`add_file`](Self::add_file), this panics if the archive ends with a trailer, use
+    /// [`append_with`](Self::append_with) for parsed archives.
+    pub fn add_file_with(&mut self, file: File, on_conflict: OnConflict) -> Result<(), PathConflict> {
+        if !self.resolve_conflicts(&file, on_conflict)? {
+            self.add_file(file);
+            return Ok(());
+        }
+        let ino = self.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+        self.add_file(file);
+        self.files.last_mut().unwrap().header.ino = ino;
+        Ok(())
+    }
+
+    /// Like [`append`](Self::append), but resolves entries with the same path according to
+    /// `on_conflict`.
+    pub fn append_with(&mut self, file: File, on_conflict: OnConflict) -> Result<(), PathConflict> {
+        self.resolve_conflicts(&file, on_conflict)?;
+        self.append(file);
+        Ok(())
+    }
+
+    /// Fails or removes the entries `file` conflicts with, returning whether any were removed.
+    fn resolve_conflicts(&mut self, file: &File, on_conflict: OnConflict) -> Result<bool, PathConflict> {
+        let existing: Vec<_> = (0..self.files.len()).filter(|&i| conflicts(&self.files[i], file)).collect();
+        match on_conflict {
+            _ if existing.is_empty() => Ok(false),
+            OnConflict::Error => Err(PathConflict { path: normalize_path(&file.filename).to_vec(), index: existing[0] }),
+            OnConflict::ReplaceExisting => {
+                let mut index = 0;
+                let mut keep = true;
+                self.files.retain(|file| {
+                    if !file.is_metadata() {
+                        keep = !existing.contains(&index);
+                    }
+                    index += 1;
+                    keep
+                });
+                Ok(true)
+            }
+            OnConflict::KeepBoth => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn archive() -> Archive {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("etc/".to_string(), Vec::new()));
+        archive.add_file(File::new("etc/passwd".to_string(), b"old".to_vec()));
+        archive.add_file(File::new_xattrs(&[(b"user.a".to_vec(), b"1".to_vec())]));
+        archive.add_file(File::new("etc/group".to_string(), Vec::new()));
+        archive.add_file(File::new_xattrs(&[(b"user.b".to_vec(), b"2".to_vec())]));
+        archive.add_trailer();
+        archive
+    }
+
+    fn names(archive: &Archive) -> Vec<&[u8]> {
+        archive.files.iter().map(|file| &file.filename[..]).collect()
+    }
+
+    #[test]
+    fn replace_existing_removes_metadata() {
+        let mut archive = archive();
+        archive.append_with(File::new("./etc/passwd".to_string(), b"new".to_vec()), OnConflict::ReplaceExisting).unwrap();
+        assert_eq!(names(&archive), [&b"etc/"[..], b"etc/group", b"METADATA!!!", b"./etc/passwd", b"TRAILER!!!"]);
+        assert_eq!(archive.xattrs(1), [(&b"user.b"[..], &b"2"[..])]);
+        assert!(archive.xattrs(3).is_empty());
+        let ino = archive.files[3].header.ino;
+        assert!(archive.files[..3].iter().all(|file| file.header.ino < ino));
+    }
+
+    #[test]
+    fn error_reports_existing_entry() {
+        let mut archive = archive();
+        let result = archive.append_with(File::new("etc/group".to_string(), Vec::new()), OnConflict::Error);
+        assert_eq!(result, Err(PathConflict { path: b"etc/group".to_vec(), index: 3 }));
+        assert_eq!(archive.files.len(), 6);
+    }
+
+    #[test]
+    fn directories_and_keep_both_add_entries() {
+        let mut archive = archive();
+        archive.append_with(File::new("etc/".to_string(), Vec::new()), OnConflict::Error).unwrap();
+        archive.append_with(File::new("etc/passwd".to_string(), Vec::new()), OnConflict::KeepBoth).unwrap();
+        assert_eq!(archive.files.len(), 8);
+        // a regular file over a directory conflicts
+        assert!(archive.append_with(File::new("etc".to_string(), Vec::new()), OnConflict::Error).is_err());
+    }
+}
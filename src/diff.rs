@@ -0,0 +1,129 @@
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use crate::{File, Initramfs};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl ChangeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Modified => "modified",
+        }
+    }
+}
+
+/// Difference of one path, returned by [`Initramfs::diff`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EntryChange<'a> {
+    /// normalized path
+    pub path: &'a [u8],
+    pub kind: ChangeKind,
+    /// entry in the old image, `None` if added
+    pub before: Option<&'a File>,
+    /// entry in the new image, `None` if removed
+    pub after: Option<&'a File>,
+}
+
+impl EntryChange<'_> {
+    /// Names of the differing fields of modified entries (`type`, `mode`, `uid`, `gid`, `mtime`,
+    /// `rdev` and `data`), otherwise empty.
+    pub fn changed_fields(&self) -> Vec<&'static str> {
+        let (Some(before), Some(after)) = (self.before, self.after) else { return Vec::new() };
+        changed_fields(before, after)
+    }
+}
+
+fn changed_fields(before: &File, after: &File) -> Vec<&'static str> {
+    let (old, new) = (&before.header, &after.header);
+    [
+        ("type", old.file_type() != new.file_type()),
+        ("mode", old.permissions() != new.permissions()),
+        ("uid", old.uid != new.uid),
+        ("gid", old.gid != new.gid),
+        ("mtime", old.mtime != new.mtime),
+        ("rdev", (old.rmaj, old.rmin) != (new.rmaj, new.rmin)),
+        ("data", before.data != after.data),
+    ].into_iter().filter(|&(_, changed)| changed).map(|(field, _)| field).collect()
+}
+
+impl Initramfs {
+    /// Compares the effective trees (see [`Initramfs::effective_view`]) of two images, returning
+    /// the changes from `self` to `other` sorted by path.
+    ///
+    /// Entries count as modified if one of the fields of [`EntryChange::changed_fields`]
+    /// differs. Inode and device numbers, link counts, checksums and the header format are
+    /// ignored, as they don't change the extracted file.
+    pub fn diff<'a>(&'a self, other: &'a Initramfs) -> Vec<EntryChange<'a>> {
+        let (old, new) = (self.effective_view(), other.effective_view());
+        let paths: BTreeSet<_> = old.iter().chain(new.iter()).map(|(path, _)| path).collect();
+        paths.into_iter().filter_map(|path| {
+            let (before, after) = (old.get(path), new.get(path));
+            let kind = match (before, after) {
+                (None, _) => ChangeKind::Added,
+                (_, None) => ChangeKind::Removed,
+                (Some(before), Some(after)) if changed_fields(before, after).is_empty() => return None,
+                (Some(_), Some(_)) => ChangeKind::Modified,
+            };
+            Some(EntryChange { path, kind, before, after })
+        }).collect()
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl File {
+    /// SHA-256 of the data.
+    pub fn sha256(&self) -> [u8; 32] {
+        use sha2::Digest;
+        sha2::Sha256::digest(&self.data).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::{Archive, CpioHeaderMagic};
+
+    fn image(files: impl IntoIterator<Item = File>) -> Initramfs {
+        let mut archive = Archive::new();
+        for file in files {
+            archive.add_file(file);
+        }
+        archive.add_trailer();
+        let mut initramfs = Initramfs::new();
+        initramfs.add_archive(archive);
+        initramfs
+    }
+
+    #[test]
+    fn diff_ignores_encoding() {
+        let old = image([
+            File::new(String::from("etc/"), Vec::new()),
+            File::new(String::from("etc/crypttab"), b"old".to_vec()),
+            File::new(String::from("etc/fstab"), b"fstab".to_vec()),
+        ]);
+        let mut new = image([
+            File::new(String::from("./etc/"), Vec::new()),
+            File::new(String::from("./etc/fstab"), b"fstab".to_vec()),
+            File::new(String::from("./etc/crypttab"), b"new".to_vec()),
+        ]);
+        for file in new.files_mut() {
+            file.header.magic = CpioHeaderMagic::WithChecksum;
+            file.header.chksum = file.checksum();
+            file.header.nlink = 1;
+        }
+        let changes = old.diff(&new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].path, changes[0].kind), (&b"etc/crypttab"[..], ChangeKind::Modified));
+        assert_eq!(changes[0].changed_fields(), ["data"]);
+    }
+}
@@ -0,0 +1,48 @@
+use alloc::string::String;
+use core::fmt::{self, Display, Formatter};
+
+use crate::{Archive, File, FileType};
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// `cpio -itv` style line of an entry, see [`File::listing`].
+///
+/// Owners are numeric, and dates always include the year like cpio shows them for files older than
+/// six months, so listings don't depend on the current time.
+#[derive(Debug, Clone, Copy)]
+pub struct Listing<'a>(&'a File);
+
+impl Display for Listing<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let header = &self.0.header;
+        write!(f, "{} {:>3} {:<8} {:<8} ", header.mode_string(), header.nlink, header.uid, header.gid)?;
+        match header.file_type() {
+            FileType::CharDevice | FileType::BlockDevice => write!(f, "{:>3}, {:>3} ", header.rmaj, header.rmin)?,
+            _ => write!(f, "{:>8} ", header.filesize)?,
+        }
+        let date = header.mtime_date_time();
+        write!(f, "{} {:>2}  {} {}", MONTHS[date.month as usize - 1], date.day, date.year, String::from_utf8_lossy(&self.0.filename))?;
+        if header.file_type() == FileType::Symlink {
+            write!(f, " -> {}", String::from_utf8_lossy(&self.0.data))?;
+        }
+        Ok(())
+    }
+}
+
+impl File {
+    pub fn listing(&self) -> Listing<'_> {
+        Listing(self)
+    }
+}
+
+impl Archive {
+    /// Lists all entries except the trailer like `cpio -itv`, one per line.
+    pub fn listing(&self) -> String {
+        use core::fmt::Write;
+        let mut listing = String::new();
+        for file in self.files.iter().filter(|file| !file.is_trailer()) {
+            writeln!(listing, "{}", file.listing()).unwrap();
+        }
+        listing
+    }
+}
@@ -0,0 +1,216 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::{glob_match, normalize_path, Archive, File, FileType, Initramfs, MaybeRawArchive, SymbolicMode};
+
+impl Archive {
+    /// Adds `dev/` with the `console`, `null` and `tty` device nodes the kernel and most init
+    /// programs expect, skipping entries that already exist. Entries are added like
+    /// [`Archive::append`], before the trailer.
+    pub fn add_standard_devices(&mut self) {
+        if self.lookup("dev").is_none() {
+            self.append(File::new("dev/".to_string(), Vec::new()));
+        }
+        let devices = [
+            ("dev/console", 0o600, 5, 1),
+            ("dev/null", 0o666, 1, 3),
+            ("dev/tty", 0o666, 5, 0),
+        ];
+        for (name, permissions, rmaj, rmin) in devices {
+            if self.lookup(name).is_none() {
+                self.append(File::new_char_device(name.to_string(), permissions, rmaj, rmin));
+            }
+        }
+    }
+
+    /// Sets the permission bits (`0o7777`) of all entries matching the glob pattern, keeping
+    /// their file type. Returns the number of matched entries.
+    ///
+    /// See [`glob_match`] for the pattern syntax.
+    pub fn chmod_glob(&mut self, pattern: &str, mode_mask: u32) -> usize {
+        let mut matched = 0;
+        for file in self.files.iter_mut().filter(|file| !file.is_trailer()) {
+            if glob_match(pattern.as_bytes(), &file.filename) {
+                file.header.set_permissions(mode_mask);
+                matched += 1;
+            }
+        }
+        matched
+    }
+
+    /// Like [`chmod_glob`](Self::chmod_glob), but applies a symbolic mode like `go-w` to the
+    /// current permissions of every matched entry.
+    pub fn chmod_symbolic_glob(&mut self, pattern: &str, mode: &SymbolicMode) -> usize {
+        let mut matched = 0;
+        for file in self.files.iter_mut().filter(|file| !file.is_trailer()) {
+            if glob_match(pattern.as_bytes(), &file.filename) {
+                file.header.mode = mode.apply(file.header.mode);
+                matched += 1;
+            }
+        }
+        matched
+    }
+
+    /// Sets owner and group of all entries matching the glob pattern. Returns the number of
+    /// matched entries.
+    ///
+    /// See [`glob_match`] for the pattern syntax.
+    pub fn chown_glob(&mut self, pattern: &str, uid: u32, gid: u32) -> usize {
+        let mut matched = 0;
+        for file in self.files.iter_mut().filter(|file| !file.is_trailer()) {
+            if glob_match(pattern.as_bytes(), &file.filename) {
+                file.header.uid = uid;
+                file.header.gid = gid;
+                matched += 1;
+            }
+        }
+        matched
+    }
+
+    /// Sets owner and group of every entry to root, like building as root would.
+    /// Returns the number of changed entries.
+    pub fn root_squash(&mut self) -> usize {
+        self.root_squash_except(&[])
+    }
+
+    /// Like [`root_squash`](Self::root_squash), but keeps the ownership of entries matching any
+    /// of the glob patterns in `keep`.
+    pub fn root_squash_except(&mut self, keep: &[&str]) -> usize {
+        let mut changed = 0;
+        for file in self.files.iter_mut().filter(|file| !file.is_trailer()) {
+            if (file.header.uid, file.header.gid) == (0, 0) || keep.iter().any(|pattern| glob_match(pattern.as_bytes(), &file.filename)) {
+                continue;
+            }
+            file.header.uid = 0;
+            file.header.gid = 0;
+            changed += 1;
+        }
+        changed
+    }
+
+    /// Renames the entry at `from` together with everything below it to `to`.
+    /// Returns the number of renamed entries.
+    pub fn rename(&mut self, from: &str, to: &str) -> usize {
+        let (from, to) = (normalize_path(from.as_bytes()), normalize_path(to.as_bytes()));
+        let mut renamed = 0;
+        for file in self.files.iter_mut().filter(|file| !file.is_trailer()) {
+            if let Some(new_name) = moved_path(&file.filename, from, to) {
+                file.set_filename(new_name);
+                renamed += 1;
+            }
+        }
+        renamed
+    }
+
+    /// Duplicates the entry at `from` together with everything below it to `to`.
+    ///
+    /// Copies are inserted before the trailer with fresh inode numbers, so copies of hard links
+    /// become independent files. `METADATA!!!` entries are copied with their entry. Returns the
+    /// number of copied entries, not counting `METADATA!!!` entries.
+    pub fn copy(&mut self, from: &str, to: &str) -> usize {
+        let (from, to) = (normalize_path(from.as_bytes()), normalize_path(to.as_bytes()));
+        let mut next_ino = self.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+        let mut copied = 0;
+        let mut copying = false;
+        let copies: Vec<File> = self.files.iter()
+            .filter(|file| !file.is_trailer())
+            .filter_map(|file| {
+                if file.is_metadata() {
+                    return copying.then(|| file.clone());
+                }
+                let new_name = moved_path(&file.filename, from, to);
+                copying = new_name.is_some();
+                let mut copy = file.clone();
+                copy.set_filename(new_name?);
+                copy.header.ino = next_ino;
+                next_ino += 1;
+                if copy.header.file_type() != FileType::Directory {
+                    copy.header.nlink = 1;
+                }
+                copied += 1;
+                Some(copy)
+            })
+            .collect();
+        let index = self.end_of_entries();
+        self.files.splice(index..index, copies);
+        copied
+    }
+
+    /// Sets the mtime of every entry, e.g. to `0` for reproducible output.
+    pub fn set_mtimes(&mut self, epoch: u32) {
+        for file in &mut self.files {
+            file.header.mtime = epoch;
+        }
+    }
+
+    /// Limits the mtime of every entry to `max_epoch`, like `SOURCE_DATE_EPOCH` clamping.
+    pub fn clamp_mtimes(&mut self, max_epoch: u32) {
+        for file in &mut self.files {
+            file.header.mtime = file.header.mtime.min(max_epoch);
+        }
+    }
+}
+
+impl Initramfs {
+    /// [`Archive::set_mtimes`] on all parsed archives.
+    pub fn set_mtimes(&mut self, epoch: u32) {
+        for archive in &mut self.archives {
+            if let MaybeRawArchive::Parsed(archive) = archive {
+                archive.set_mtimes(epoch);
+            }
+        }
+    }
+
+    /// [`Archive::clamp_mtimes`] on all parsed archives.
+    pub fn clamp_mtimes(&mut self, max_epoch: u32) {
+        for archive in &mut self.archives {
+            if let MaybeRawArchive::Parsed(archive) = archive {
+                archive.clamp_mtimes(max_epoch);
+            }
+        }
+    }
+}
+
+/// Returns the new name of `path` if it's `from` or below it.
+fn moved_path(path: &[u8], from: &[u8], to: &[u8]) -> Option<Vec<u8>> {
+    let path = normalize_path(path);
+    let rest = path.strip_prefix(from)?;
+    if !rest.is_empty() && !rest.starts_with(b"/") {
+        return None;
+    }
+    let mut new_name = to.to_vec();
+    new_name.extend_from_slice(rest);
+    Some(new_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_standard_devices_to_parsed_archive() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("dev/".to_string(), Vec::new()));
+        archive.add_file(File::new_char_device("dev/null".to_string(), 0o600, 1, 3));
+        archive.add_trailer();
+        archive.add_standard_devices();
+        let names: Vec<_> = archive.files.iter().map(|file| &file.filename[..]).collect();
+        assert_eq!(names, [&b"dev/"[..], b"dev/null", b"dev/console", b"dev/tty", b"TRAILER!!!"]);
+        assert_eq!(archive.lookup("dev/null").unwrap().header.permissions(), 0o600);
+    }
+
+    #[test]
+    fn copy_keeps_metadata_with_entry() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("etc/".to_string(), Vec::new()));
+        archive.add_file(File::new("etc/passwd".to_string(), b"root".to_vec()));
+        archive.add_file(File::new_xattrs(&[(b"user.a".to_vec(), b"1".to_vec())]));
+        archive.add_file(File::new("etcetera".to_string(), Vec::new()));
+        archive.add_file(File::new_xattrs(&[(b"user.b".to_vec(), b"2".to_vec())]));
+        archive.add_trailer();
+        assert_eq!(archive.copy("etc", "backup"), 2);
+        let names: Vec<_> = archive.files[5..].iter().map(|file| &file.filename[..]).collect();
+        assert_eq!(names, [&b"backup"[..], b"backup/passwd", b"METADATA!!!", b"TRAILER!!!"]);
+        assert_eq!(archive.files[7].data, archive.files[2].data);
+    }
+}
@@ -0,0 +1,112 @@
+//! Minimal ELF parsing for finding the dynamic dependencies of executables and libraries.
+
+use alloc::vec::Vec;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+/// Dynamic linking information of an ELF file.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct Dynamic<'a> {
+    /// program interpreter (`PT_INTERP`), i.e. the dynamic loader
+    pub interpreter: Option<&'a [u8]>,
+    /// `DT_NEEDED` entries
+    pub needed: Vec<&'a [u8]>,
+    /// directories of `DT_RPATH` and `DT_RUNPATH`, unexpanded
+    pub search_paths: Vec<&'a [u8]>,
+}
+
+pub(crate) fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(b"\x7fELF")
+}
+
+/// Parses the dynamic linking information, returns `None` for malformed files.
+///
+/// Statically linked executables and object files without program headers have no dependencies.
+pub(crate) fn parse_dynamic(data: &[u8]) -> Option<Dynamic<'_>> {
+    if !is_elf(data) {
+        return None;
+    }
+    let is_64 = match data.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let is_le = match data.get(5)? {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+    let bytes_at = |offset: u64, len: u64| -> Option<&[u8]> {
+        let start = usize::try_from(offset).ok()?;
+        data.get(start..start.checked_add(usize::try_from(len).ok()?)?)
+    };
+    let uint_at = |offset: u64, size: u64| -> Option<u64> {
+        let bytes = bytes_at(offset, size)?;
+        Some(bytes.iter().enumerate().fold(0, |value, (i, &b)| match is_le {
+            true => value | (b as u64) << (8 * i),
+            false => value << 8 | b as u64,
+        }))
+    };
+    let word = if is_64 { 8 } else { 4 };
+    let (phoff, phentsize, phnum) = match is_64 {
+        true => (uint_at(0x20, 8)?, uint_at(0x36, 2)?, uint_at(0x38, 2)?),
+        false => (uint_at(0x1c, 4)?, uint_at(0x2a, 2)?, uint_at(0x2c, 2)?),
+    };
+
+    // offsets within the tables can't overflow after checking that the tables are within the file
+    bytes_at(phoff, phnum * phentsize)?;
+    // (p_type, p_offset, p_vaddr, p_filesz)
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let header = phoff + i * phentsize;
+        let segment = match is_64 {
+            true => (uint_at(header, 4)? as u32, uint_at(header + 8, 8)?, uint_at(header + 16, 8)?, uint_at(header + 32, 8)?),
+            false => (uint_at(header, 4)? as u32, uint_at(header + 4, 4)?, uint_at(header + 8, 4)?, uint_at(header + 16, 4)?),
+        };
+        segments.push(segment);
+    }
+
+    let mut dynamic = Dynamic::default();
+    if let Some(&(_, offset, _, size)) = segments.iter().find(|segment| segment.0 == PT_INTERP) {
+        let interpreter = bytes_at(offset, size)?;
+        dynamic.interpreter = interpreter.split(|&b| b == 0).next();
+    }
+    let Some(&(_, offset, _, size)) = segments.iter().find(|segment| segment.0 == PT_DYNAMIC) else {
+        return Some(dynamic);
+    };
+    bytes_at(offset, size)?;
+    let mut entries = Vec::new();
+    for i in 0..size / (2 * word) {
+        let entry = offset + i * 2 * word;
+        let tag = uint_at(entry, word)?;
+        if tag == DT_NULL {
+            break;
+        }
+        entries.push((tag, uint_at(entry + word, word)?));
+    }
+    // DT_STRTAB is a virtual address, which has to be mapped to a file offset via the loaded segments
+    let strtab = entries.iter().find(|(tag, _)| *tag == DT_STRTAB)?.1;
+    let strtab = segments.iter()
+        .filter(|segment| segment.0 == PT_LOAD)
+        .find(|&&(_, _, vaddr, filesz)| (vaddr..vaddr.saturating_add(filesz)).contains(&strtab))
+        .and_then(|&(_, offset, vaddr, _)| (strtab - vaddr).checked_add(offset))?;
+    let string_at = |offset: u64| -> Option<&[u8]> {
+        let start = usize::try_from(strtab.checked_add(offset)?).ok()?;
+        data.get(start..)?.split(|&b| b == 0).next()
+    };
+    for &(tag, value) in &entries {
+        match tag {
+            DT_NEEDED => dynamic.needed.push(string_at(value)?),
+            DT_RPATH | DT_RUNPATH => dynamic.search_paths.extend(string_at(value)?.split(|&b| b == b':').filter(|dir| !dir.is_empty())),
+            _ => (),
+        }
+    }
+    Some(dynamic)
+}
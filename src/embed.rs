@@ -0,0 +1,54 @@
+//! Helpers for build scripts embedding an initramfs into the compiled binary.
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     initramfs::embed::embed_dir("rootfs", "initramfs.cpio").unwrap();
+//! }
+//!
+//! // src/main.rs
+//! static INITRAMFS: &[u8] = initramfs::include_initramfs!("initramfs.cpio");
+//! ```
+
+use alloc::vec::Vec;
+use std::io;
+use std::println;
+use std::path::{Path, PathBuf};
+
+use crate::{Archive, Initramfs};
+
+/// Validates an existing initramfs image and copies it to `$OUT_DIR/<name>`.
+pub fn embed_image(image: impl AsRef<Path>, name: &str) -> io::Result<PathBuf> {
+    let image = image.as_ref();
+    println!("cargo:rerun-if-changed={}", image.display());
+    let content = std::fs::read(image)?;
+    Initramfs::parse(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write_out(name, &content)
+}
+
+/// Creates an archive from a directory (see [`Archive::from_dir`]) and writes it to `$OUT_DIR/<name>`.
+pub fn embed_dir(dir: impl AsRef<Path>, name: &str) -> io::Result<PathBuf> {
+    let dir = dir.as_ref();
+    println!("cargo:rerun-if-changed={}", dir.display());
+    let mut archive = Archive::from_dir(dir)?;
+    archive.add_trailer();
+    let mut content = Vec::new();
+    archive.write(&mut content);
+    write_out(name, &content)
+}
+
+fn write_out(name: &str, content: &[u8]) -> io::Result<PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR not set, not running in a build script"))?;
+    let path = Path::new(&out_dir).join(name);
+    std::fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Includes an image written by [`embed_image`] or [`embed_dir`] from the build script as `&'static [u8; N]`.
+#[macro_export]
+macro_rules! include_initramfs {
+    ($name:literal) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/", $name))
+    };
+}
@@ -0,0 +1,237 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::walk::path_components;
+use crate::{File, FileType, Initramfs};
+
+/// Options for [`Initramfs::extract`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExtractOptions {
+    /// Maps characters and names that are invalid on common filesystems (e.g. `:` or `CON` on
+    /// Windows) and emulates symlinks to regular files with copies. Defaults to true on Windows.
+    pub portable: bool,
+    /// Restores POSIX ACLs of directories and regular files from their `METADATA!!!` entries,
+    /// see [`DirOptions::acls`](crate::DirOptions::acls).
+    #[cfg(feature = "xattrs")]
+    pub acls: bool,
+}
+
+// only derivable on non-Windows targets
+#[allow(clippy::derivable_impls)]
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions {
+            portable: cfg!(windows),
+            #[cfg(feature = "xattrs")]
+            acls: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SkipReason {
+    /// symlink that can't be created or emulated
+    Symlink,
+    /// device node, fifo or socket, which can't be created without privileges or platform support
+    Special,
+    /// path containing `..`
+    UnsafePath,
+    /// entry with unknown file type
+    Unknown,
+    /// creating the entry failed
+    Io(io::ErrorKind),
+}
+
+/// Result of [`Initramfs::extract`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ExtractReport {
+    /// number of created entries, including emulated symlinks
+    pub extracted: usize,
+    /// (archive filename, path relative to the target directory) of entries whose name got mapped
+    pub renamed: Vec<(Vec<u8>, PathBuf)>,
+    /// filenames of symlinks extracted as copies of their target
+    pub emulated_symlinks: Vec<Vec<u8>>,
+    pub skipped: Vec<(Vec<u8>, SkipReason)>,
+    /// filenames of extracted entries whose ACLs couldn't be set, e.g. because the target
+    /// filesystem doesn't support them
+    #[cfg(feature = "xattrs")]
+    pub failed_acls: Vec<(Vec<u8>, io::ErrorKind)>,
+}
+
+impl Initramfs {
+    /// Extracts the effective tree (see [`Initramfs::effective_view`]) into `dir`.
+    ///
+    /// Entries which can't be created are skipped and listed in the report instead of aborting
+    /// the extraction. On unix, permissions are applied and symlinks are created after all other
+    /// entries, so they can't redirect later entries outside of `dir`.
+    pub fn extract(&self, dir: impl AsRef<Path>, options: &ExtractOptions) -> io::Result<ExtractReport> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let view = self.effective_view();
+        // data of hard links is only stored with one of their entries
+        let mut link_data: BTreeMap<(u32, u32, u32), &[u8]> = BTreeMap::new();
+        for (_, file) in view.iter() {
+            if file.header.file_type() == FileType::Regular && file.header.nlink > 1 && !file.data.is_empty() {
+                link_data.insert((file.header.maj, file.header.min, file.header.ino), &file.data);
+            }
+        }
+
+        #[cfg(feature = "xattrs")]
+        let xattrs = if options.acls { self.effective_xattrs() } else { BTreeMap::new() };
+        #[cfg(feature = "xattrs")]
+        let mut acl_targets = Vec::new();
+
+        let mut report = ExtractReport::default();
+        let mut symlinks = Vec::new();
+        let mut dirs = Vec::new();
+        for (path, file) in view.iter() {
+            let Some(relative) = target_path(path, options.portable) else {
+                report.skipped.push((path.to_vec(), SkipReason::UnsafePath));
+                continue;
+            };
+            let renamed = relative.components().map(|component| component.as_os_str().as_encoded_bytes()).ne(path_components(path));
+            if options.portable && renamed {
+                report.renamed.push((path.to_vec(), relative.clone()));
+            }
+            let target = dir.join(&relative);
+            let result = match file.header.file_type() {
+                FileType::Directory => {
+                    dirs.push((path, target.clone(), file));
+                    std::fs::create_dir_all(&target)
+                }
+                FileType::Regular => {
+                    let data = match link_data.get(&(file.header.maj, file.header.min, file.header.ino)) {
+                        Some(data) if file.header.nlink > 1 => data,
+                        _ => &file.data[..],
+                    };
+                    write_file(&target, data, file)
+                }
+                FileType::Symlink if cfg!(unix) && !options.portable => {
+                    symlinks.push((path, target, file));
+                    continue;
+                }
+                FileType::Symlink => match view.follow(path) {
+                    Some((_, resolved)) if resolved.header.file_type() == FileType::Regular => {
+                        report.emulated_symlinks.push(path.to_vec());
+                        write_file(&target, &resolved.data, resolved)
+                    }
+                    _ => {
+                        report.skipped.push((path.to_vec(), SkipReason::Symlink));
+                        continue;
+                    }
+                },
+                FileType::CharDevice | FileType::BlockDevice | FileType::Fifo | FileType::Socket => {
+                    report.skipped.push((path.to_vec(), SkipReason::Special));
+                    continue;
+                }
+                FileType::Unknown => {
+                    report.skipped.push((path.to_vec(), SkipReason::Unknown));
+                    continue;
+                }
+            };
+            match result {
+                Ok(()) => report.extracted += 1,
+                Err(e) => {
+                    report.skipped.push((path.to_vec(), SkipReason::Io(e.kind())));
+                    continue;
+                }
+            }
+            #[cfg(feature = "xattrs")]
+            if matches!(file.header.file_type(), FileType::Directory | FileType::Regular) {
+                if let Some(xattrs) = xattrs.get(path) {
+                    acl_targets.push((path, target, xattrs));
+                }
+            }
+        }
+        #[cfg(unix)]
+        for (path, target, file) in symlinks {
+            let link = <std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(&file.data);
+            match std::os::unix::fs::symlink(link, &target) {
+                Ok(()) => report.extracted += 1,
+                Err(e) => report.skipped.push((path.to_vec(), SkipReason::Io(e.kind()))),
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = symlinks;
+        // deepest first, so read-only directories don't prevent changing their children
+        for (path, target, file) in dirs.iter().rev() {
+            if let Err(e) = set_permissions(target, file) {
+                report.skipped.push((path.to_vec(), SkipReason::Io(e.kind())));
+            }
+        }
+        // after the permissions, which would otherwise change the ACL mask
+        #[cfg(feature = "xattrs")]
+        for (path, target, xattrs) in acl_targets {
+            let acls = xattrs.iter()
+                .filter_map(|&(name, value)| crate::xattrs::ACL_XATTRS.into_iter().find(|acl| acl.as_bytes() == name).map(|acl| (acl, value)));
+            for (name, value) in acls {
+                if let Err(e) = ::xattr::set(&target, name, value) {
+                    report.failed_acls.push((path.to_vec(), e.kind()));
+                    break;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn write_file(target: &Path, data: &[u8], file: &File) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(target, data)?;
+    set_permissions(target, file)
+}
+
+fn set_permissions(target: &Path, file: &File) -> io::Result<()> {
+    #[cfg(unix)] {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(target, std::fs::Permissions::from_mode(file.header.permissions()))
+    }
+    #[cfg(not(unix))] {
+        let _ = (target, file);
+        Ok(())
+    }
+}
+
+/// Maps a normalized archive path to a path relative to the extraction directory, or `None` if
+/// it would escape it.
+fn target_path(path: &[u8], portable: bool) -> Option<PathBuf> {
+    let mut target = PathBuf::new();
+    for component in path_components(path) {
+        if component == b".." {
+            return None;
+        }
+        match portable {
+            true => target.push(portable_name(component)),
+            #[cfg(unix)]
+            false => target.push(<std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(component)),
+            #[cfg(not(unix))]
+            false => target.push(&*String::from_utf8_lossy(component)),
+        }
+    }
+    Some(target)
+}
+
+/// Replaces characters invalid on Windows and common filesystems with `_`, and suffixes reserved
+/// device names like `CON` or `lpt1.txt` with `_`.
+fn portable_name(component: &[u8]) -> String {
+    let mut name: String = String::from_utf8_lossy(component).chars()
+        .map(|c| if c.is_control() || "<>:\"\\|?*".contains(c) { '_' } else { c })
+        .collect();
+    // trailing dots and spaces are silently dropped by Windows
+    while name.ends_with(['.', ' ']) {
+        name.pop();
+        name.push('_');
+    }
+    let stem = name.split('.').next().unwrap().to_ascii_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || (stem.len() == 4 && (stem.starts_with("COM") || stem.starts_with("LPT")) && stem.as_bytes()[3].is_ascii_digit());
+    if reserved {
+        name.insert(stem.len(), '_');
+    }
+    name
+}
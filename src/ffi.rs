@@ -0,0 +1,118 @@
+//! C ABI for parsing, listing and writing initramfs images.
+//!
+//! An [`Initramfs`] is passed to C as an opaque pointer created by [`initramfs_parse`] and released
+//! with [`initramfs_free`]. The header `include/initramfs.h` is generated with `cbindgen`, the
+//! shared and static library are built by the `capi` crate (`cargo build -p initramfs-capi`).
+
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::{ptr, slice};
+
+use crate::Initramfs;
+
+/// Callback for [`initramfs_list`], called with the filename (not NUL-terminated) and size of
+/// every entry.
+pub type InitramfsListCallback = extern "C" fn(ctx: *mut c_void, filename: *const u8, filename_len: usize, filesize: u32);
+
+/// Parses `len` bytes at `data`, returning null if they aren't a valid initramfs or `data` is
+/// null.
+///
+/// # Safety
+///
+/// `data` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn initramfs_parse(data: *const u8, len: usize) -> *mut Initramfs {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+    let data = slice::from_raw_parts(data, len);
+    match Initramfs::parse(data) {
+        Ok(initramfs) => Box::into_raw(Box::new(initramfs)),
+        Err(e) => {
+            log::debug!("initramfs_parse failed: {e}");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees an initramfs returned by [`initramfs_parse`]. Null is ignored.
+///
+/// # Safety
+///
+/// `initramfs` must be null or returned by [`initramfs_parse`] and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn initramfs_free(initramfs: *mut Initramfs) {
+    if !initramfs.is_null() {
+        drop(Box::from_raw(initramfs));
+    }
+}
+
+/// Calls `callback` for every entry of all parsed archives, excluding trailers, and returns the
+/// number of entries.
+///
+/// # Safety
+///
+/// `initramfs` must be a valid pointer returned by [`initramfs_parse`]. The filename passed to
+/// `callback` is only valid during the call.
+#[no_mangle]
+pub unsafe extern "C" fn initramfs_list(initramfs: *const Initramfs, callback: InitramfsListCallback, ctx: *mut c_void) -> usize {
+    let mut count = 0;
+    for file in (*initramfs).files_without_trailers() {
+        callback(ctx, file.filename.as_ptr(), file.filename.len(), file.header.filesize);
+        count += 1;
+    }
+    count
+}
+
+/// Looks up the entry the kernel would extract for `path` (see [`Initramfs::lookup`]), storing a
+/// pointer to its data in `data` and its length in `len`. Returns false if there is no such entry
+/// or `path` is null.
+///
+/// # Safety
+///
+/// `initramfs` must be a valid pointer returned by [`initramfs_parse`], `path` must be null or
+/// valid for reads of `path_len` bytes, and `data` and `len` must be valid for writes. The returned data is
+/// borrowed from `initramfs` and valid until it is freed.
+#[no_mangle]
+pub unsafe extern "C" fn initramfs_get_file(
+    initramfs: *const Initramfs, path: *const u8, path_len: usize, data: *mut *const u8, len: *mut usize,
+) -> bool {
+    if path.is_null() {
+        return false;
+    }
+    let Ok(path) = core::str::from_utf8(slice::from_raw_parts(path, path_len)) else { return false };
+    match (*initramfs).lookup(path) {
+        Some((_, file)) => {
+            *data = file.data.as_ptr();
+            *len = file.data.len();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Serializes the initramfs into a newly allocated buffer, storing its length in `len`.
+/// The buffer must be released with [`initramfs_buffer_free`].
+///
+/// # Safety
+///
+/// `initramfs` must be a valid pointer returned by [`initramfs_parse`] and `len` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn initramfs_write(initramfs: *const Initramfs, len: *mut usize) -> *mut u8 {
+    let data = (*initramfs).to_bytes();
+    *len = data.len();
+    Box::into_raw(data.into_boxed_slice()) as *mut u8
+}
+
+/// Frees a buffer returned by [`initramfs_write`]. Null is ignored.
+///
+/// # Safety
+///
+/// `data` must be null or returned by [`initramfs_write`] together with `len`, and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn initramfs_buffer_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
@@ -0,0 +1,99 @@
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::{glob_match, normalize_path, Archive};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FilterRule {
+    Include(Vec<u8>),
+    Exclude(Vec<u8>),
+}
+
+/// Ordered include/exclude glob rules, see [`Archive::apply_filters`].
+///
+/// The first rule matching a path decides; paths matching no rule are included.
+/// Excluding a directory excludes everything below it.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct FilterSet {
+    pub rules: Vec<FilterRule>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FilterError {
+    /// (line number) line doesn't start with `+ ` or `- `
+    InvalidRule(usize),
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::InvalidRule(line) => write!(f, "invalid filter rule in line {line}, expected `+ <glob>` or `- <glob>`"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FilterError {}
+
+impl FilterSet {
+    pub fn new() -> FilterSet {
+        FilterSet { rules: Vec::new() }
+    }
+
+    /// Parses a filter config with one rule per line, `+ <glob>` to include and `- <glob>` to
+    /// exclude, like rsync filter files. Empty lines and lines starting with `#` are ignored.
+    pub fn parse(config: &str) -> Result<FilterSet, FilterError> {
+        let mut filters = FilterSet::new();
+        for (i, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once(' ') {
+                Some(("+", pattern)) => filters.include(pattern.trim_start()),
+                Some(("-", pattern)) => filters.exclude(pattern.trim_start()),
+                _ => return Err(FilterError::InvalidRule(i + 1)),
+            }
+        }
+        Ok(filters)
+    }
+
+    pub fn include(&mut self, pattern: &str) {
+        self.rules.push(FilterRule::Include(pattern.as_bytes().to_vec()));
+    }
+
+    pub fn exclude(&mut self, pattern: &str) {
+        self.rules.push(FilterRule::Exclude(pattern.as_bytes().to_vec()));
+    }
+
+    /// Checks whether `path` and all its parent directories are included.
+    pub fn is_included(&self, path: &[u8]) -> bool {
+        let path = normalize_path(path);
+        let parents = path.iter().enumerate()
+            .filter(|&(_, &b)| b == b'/')
+            .map(|(i, _)| &path[..i]);
+        !parents.chain(core::iter::once(path)).any(|path| {
+            let rule = self.rules.iter().find(|rule| match rule {
+                FilterRule::Include(pattern) | FilterRule::Exclude(pattern) => glob_match(pattern, path),
+            });
+            matches!(rule, Some(FilterRule::Exclude(_)))
+        })
+    }
+}
+
+impl Archive {
+    /// Removes all entries excluded by `filters`, keeping the trailer.
+    /// `METADATA!!!` entries are kept or removed together with the entry they belong to.
+    /// Returns the number of removed entries.
+    pub fn apply_filters(&mut self, filters: &FilterSet) -> usize {
+        let len = self.files.len();
+        let mut keep = true;
+        self.files.retain(|file| {
+            if !file.is_metadata() {
+                keep = file.is_trailer() || filters.is_included(&file.filename);
+            }
+            keep
+        });
+        len - self.files.len()
+    }
+}
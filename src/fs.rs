@@ -0,0 +1,349 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+
+use crate::{normalize_path, Archive, DataSource, File, FileType};
+
+/// Options for building archives from host directories.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DirOptions {
+    /// With the `mmap` feature, regular files of at least this size are memory-mapped instead of
+    /// read into memory when using [`ArchiveBuilder::add_dir`](crate::ArchiveBuilder::add_dir).
+    /// Without the feature, they are streamed from the file instead. [`Archive::from_dir_with`]
+    /// only reads them when writing (see [`File::set_source`]).
+    pub mmap_threshold: Option<u64>,
+    /// Number of threads reading file contents in [`Archive::from_dir_with`], values below 2
+    /// read on the calling thread.
+    pub threads: usize,
+    /// Stores the extended attributes of host files (e.g. `security.selinux`) in `METADATA!!!`
+    /// entries, see [`File::new_xattrs`].
+    #[cfg(feature = "xattrs")]
+    pub xattrs: bool,
+    /// Stores only the POSIX ACLs of host files (`system.posix_acl_access` and
+    /// `system.posix_acl_default`), implied by [`xattrs`](Self::xattrs).
+    #[cfg(feature = "xattrs")]
+    pub acls: bool,
+}
+
+impl Archive {
+    /// Creates an archive from the contents of a host directory (not including the directory itself).
+    ///
+    /// Entries are added in sorted order with every directory directly followed by its contents.
+    /// On unix, modes, ownership, device numbers and symlinks are taken over from the host;
+    /// hard links are stored as separate files.
+    /// No trailer is added, call [`Archive::add_trailer`] once the archive is complete.
+    pub fn from_dir(root: impl AsRef<Path>) -> io::Result<Archive> {
+        Archive::from_dir_with(root, &DirOptions::default())
+    }
+
+    /// Like [`from_dir`](Self::from_dir), reading files on [`DirOptions::threads`] threads.
+    /// The resulting archive doesn't depend on the number of threads.
+    pub fn from_dir_with(root: impl AsRef<Path>, options: &DirOptions) -> io::Result<Archive> {
+        Archive::from_dir_reusing(root, options, &Archive::new(), |_| true)
+    }
+
+    /// Like [`from_dir_with`](Self::from_dir_with), but takes the data of regular files from
+    /// `previous`, an earlier result for the same directory, instead of reading them again,
+    /// unless `changed` returns true for their host path or their size differs.
+    ///
+    /// Rebuilding after a few changes (e.g. in a watch mode) thus only reads the changed files.
+    pub fn from_dir_reusing(
+        root: impl AsRef<Path>, options: &DirOptions, previous: &Archive, changed: impl Fn(&Path) -> bool + Sync,
+    ) -> io::Result<Archive> {
+        let previous: BTreeMap<&[u8], &File> = previous.files.iter()
+            .filter(|file| !file.is_metadata() && file.header.file_type() == FileType::Regular)
+            .map(|file| (&file.filename[..], file))
+            .collect();
+        let mut entries = Vec::new();
+        visit_dir(root.as_ref(), &mut |filename, path, metadata| {
+            entries.push((filename, path.to_path_buf(), metadata.clone()));
+            Ok(())
+        })?;
+        let files = parallel_map(entries, options.threads, |(filename, path, metadata)| -> io::Result<_> {
+            let old = previous.get(&filename[..]).filter(|old| {
+                metadata.is_file() && u64::from(old.header.filesize) == metadata.len() && !changed(&path)
+            });
+            let large = options.mmap_threshold.is_some_and(|threshold| metadata.len() >= threshold);
+            let file = if let Some(old) = old {
+                let mut file = file_from_metadata(filename, &metadata)?;
+                file.data = old.data.clone();
+                file.source = old.source.clone();
+                file
+            } else if metadata.is_file() && large {
+                let mut file = file_from_metadata(filename, &metadata)?;
+                file.set_source(DataSource::Path(path.clone()), file.header.filesize);
+                file
+            } else {
+                file_from_host(filename, &path, &metadata)?
+            };
+            Ok((file, host_xattrs(&path, options)?))
+        });
+        let mut archive = Archive::new();
+        for file in files {
+            let (file, xattrs) = file?;
+            archive.add_file(file);
+            if !xattrs.is_empty() {
+                archive.add_file(File::new_xattrs(&xattrs));
+            }
+        }
+        Ok(archive)
+    }
+}
+
+impl Archive {
+    /// Copies the contents of a host directory (like [`from_dir`](Self::from_dir)) on top of the
+    /// archive, returning the number of added or replaced entries.
+    ///
+    /// Entries at colliding paths are replaced in place together with their `METADATA!!!`
+    /// entries, except for existing directories and symlinks where the host has a directory, so
+    /// e.g. `lib -> usr/lib` keeps working. A directory replaced by another type of file is
+    /// removed with all entries below it. Parent directories are part of the host tree and thus
+    /// created if missing.
+    pub fn overlay_dir(&mut self, root: impl AsRef<Path>) -> io::Result<usize> {
+        let overlay = Archive::from_dir(root)?;
+        let mut count = 0;
+        let mut next_ino = self.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+        let mut files = overlay.files.into_iter().peekable();
+        while let Some(mut file) = files.next() {
+            let mut group = Vec::new();
+            while let Some(metadata) = files.next_if(File::is_metadata) {
+                group.push(metadata);
+            }
+            let path = normalize_path(&file.filename).to_vec();
+            let is_dir = file.header.file_type() == FileType::Directory;
+            file.header.ino = next_ino;
+            group.insert(0, file);
+            let existing = self.files.iter()
+                .rposition(|other| !other.is_trailer() && !other.is_metadata() && normalize_path(&other.filename) == path);
+            match existing {
+                Some(i) if is_dir && matches!(self.files[i].header.file_type(), FileType::Directory | FileType::Symlink) => continue,
+                Some(i) => {
+                    let was_dir = self.files[i].header.file_type() == FileType::Directory;
+                    let end = i + 1 + self.files[i + 1..].iter().take_while(|other| other.is_metadata()).count();
+                    self.files.splice(i..end, group);
+                    if was_dir && !is_dir {
+                        self.remove_below(&path);
+                    }
+                }
+                None => {
+                    let index = self.end_of_entries();
+                    self.files.splice(index..index, group);
+                }
+            }
+            next_ino += 1;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Removes all entries below the normalized `dir` with their `METADATA!!!` entries.
+    fn remove_below(&mut self, dir: &[u8]) {
+        let mut keep = true;
+        self.files.retain(|file| {
+            if !file.is_metadata() {
+                let path = normalize_path(&file.filename);
+                keep = file.is_trailer() || !(path.starts_with(dir) && path[dir.len()..].starts_with(b"/"));
+            }
+            keep
+        });
+    }
+}
+
+/// Applies `f` to all items on up to `threads` threads, returning the results in the order of `items`.
+pub(crate) fn parallel_map<T, R, F>(items: Vec<T>, threads: usize, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let threads = threads.min(items.len());
+    if threads < 2 {
+        return items.into_iter().map(f).collect();
+    }
+    let queue = std::sync::Mutex::new(items.into_iter().enumerate());
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(|| {
+            let mut results = Vec::new();
+            loop {
+                // not in the loop condition, which would keep the lock while calling `f`
+                let next = queue.lock().unwrap().next();
+                let Some((i, item)) = next else { break };
+                results.push((i, f(item)));
+            }
+            results
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+    results.sort_unstable_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Calls `visit` with the archive filename, host path and metadata of every entry below `root`,
+/// sorted with directories directly followed by their contents.
+pub(crate) fn visit_dir<F>(root: &Path, visit: &mut F) -> io::Result<()>
+where
+    F: FnMut(Vec<u8>, &Path, &Metadata) -> io::Result<()>,
+{
+    visit_dir_recursive(root, &mut Vec::new(), visit)
+}
+
+fn visit_dir_recursive<F>(dir: &Path, prefix: &mut Vec<u8>, visit: &mut F) -> io::Result<()>
+where
+    F: FnMut(Vec<u8>, &Path, &Metadata) -> io::Result<()>,
+{
+    let mut entries = std::fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let prefix_len = prefix.len();
+        if !prefix.is_empty() {
+            prefix.push(b'/');
+        }
+        prefix.extend_from_slice(&os_str_bytes(&entry.file_name()));
+        let metadata = std::fs::symlink_metadata(&path)?;
+        visit(prefix.clone(), &path, &metadata)?;
+        if metadata.is_dir() {
+            visit_dir_recursive(&path, prefix, visit)?;
+        }
+        prefix.truncate(prefix_len);
+    }
+    Ok(())
+}
+
+/// Creates an entry named `filename` from a host file, reading its data.
+pub(crate) fn file_from_host(filename: Vec<u8>, path: &Path, metadata: &Metadata) -> io::Result<File> {
+    let data = if metadata.file_type().is_symlink() {
+        os_str_bytes(std::fs::read_link(path)?.as_os_str())
+    } else if metadata.is_file() {
+        std::fs::read(path)?
+    } else {
+        Vec::new()
+    };
+    let mut file = file_from_metadata(filename, metadata)?;
+    file.set_data(data);
+    Ok(file)
+}
+
+/// Extended attributes (name, value) of a host file sorted by name as enabled in `options`.
+/// Symlinks aren't followed.
+pub(crate) fn host_xattrs(path: &Path, options: &DirOptions) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    #[cfg(feature = "xattrs")]
+    if options.xattrs || options.acls {
+        let names = match ::xattr::list(path) {
+            Ok(names) => names,
+            // filesystem or platform without xattr support
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut xattrs = Vec::new();
+        for name in names {
+            if !options.xattrs && !crate::xattrs::ACL_XATTRS.iter().any(|acl| name == *acl) {
+                continue;
+            }
+            // `None` if removed in the meantime
+            if let Some(value) = ::xattr::get(path, &name)? {
+                xattrs.push((os_str_bytes(&name), value));
+            }
+        }
+        xattrs.sort();
+        return Ok(xattrs);
+    }
+    let _ = (path, options);
+    Ok(Vec::new())
+}
+
+/// Creates an entry named `filename` from host metadata without data.
+///
+/// `filesize` is set to the size of regular files, fails if they are too large for the format.
+pub(crate) fn file_from_metadata(filename: Vec<u8>, metadata: &Metadata) -> io::Result<File> {
+    let mut file = File::new(String::new(), Vec::new());
+    file.set_filename(filename);
+    if metadata.is_file() {
+        file.header.filesize = u32::try_from(metadata.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file larger than 4 GiB"))?;
+    }
+    file.header.nlink = if metadata.is_dir() { 2 } else { 1 };
+    file.header.set_mtime_from_metadata(metadata)?;
+    #[cfg(unix)] {
+        use std::os::unix::fs::MetadataExt;
+        file.header.mode = metadata.mode();
+        file.header.uid = metadata.uid();
+        file.header.gid = metadata.gid();
+        let rdev = metadata.rdev();
+        file.header.rmaj = (((rdev >> 32) & 0xffff_f000) | ((rdev >> 8) & 0xfff)) as u32;
+        file.header.rmin = (((rdev >> 12) & 0xffff_ff00) | (rdev & 0xff)) as u32;
+    }
+    #[cfg(not(unix))] {
+        file.header.mode = if metadata.is_dir() {
+            crate::mode::DEFAULT_DIR_MODE
+        } else if metadata.file_type().is_symlink() {
+            crate::mode::DEFAULT_SYMLINK_MODE
+        } else if metadata.permissions().readonly() {
+            crate::mode::S_IFREG | 0o444
+        } else {
+            crate::mode::DEFAULT_FILE_MODE
+        };
+    }
+    Ok(file)
+}
+
+pub(crate) fn os_str_bytes(s: &std::ffi::OsStr) -> Vec<u8> {
+    #[cfg(unix)] {
+        use std::os::unix::ffi::OsStrExt;
+        s.as_bytes().to_vec()
+    }
+    #[cfg(not(unix))] {
+        s.to_string_lossy().into_owned().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_dir_reusing_reads_only_changed_files() {
+        let dir = std::env::temp_dir().join(alloc::format!("initramfs-{}-reuse", std::process::id()));
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        std::fs::write(dir.join("init"), b"old").unwrap();
+        std::fs::write(dir.join("bin/sh"), b"sh").unwrap();
+        let mut previous = Archive::from_dir(&dir).unwrap();
+        // only visible if the data is reused instead of read again
+        previous.lookup_mut("bin/sh").unwrap().data = b"xx".to_vec();
+        std::fs::write(dir.join("init"), b"new").unwrap();
+        std::fs::write(dir.join("added"), b"added").unwrap();
+
+        let archive = Archive::from_dir_reusing(&dir, &DirOptions::default(), &previous, |path| path.ends_with("init")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(archive.lookup("init").unwrap().data, b"new");
+        assert_eq!(archive.lookup("bin/sh").unwrap().data, b"xx");
+        assert_eq!(archive.lookup("added").unwrap().data, b"added");
+    }
+
+    #[test]
+    fn overlay_dir_replaces_entries_with_their_metadata() {
+        let dir = std::env::temp_dir().join(alloc::format!("initramfs-{}-overlay", std::process::id()));
+        std::fs::create_dir_all(dir.join("etc")).unwrap();
+        std::fs::write(dir.join("etc/hostname"), b"new").unwrap();
+        std::fs::write(dir.join("lib"), b"file").unwrap();
+        let mut archive = Archive::new();
+        archive.add_file(File::new("etc/".into(), Vec::new()));
+        archive.add_file(File::new("etc/hostname".into(), b"old".to_vec()));
+        archive.add_file(File::new_xattrs(&[(b"user.old".to_vec(), b"1".to_vec())]));
+        archive.add_file(File::new("lib/".into(), Vec::new()));
+        archive.add_file(File::new("lib/libc.so".into(), b"libc".to_vec()));
+        archive.add_file(File::new_xattrs(&[(b"user.libc".to_vec(), b"1".to_vec())]));
+        archive.add_file(File::new("libexec".into(), Vec::new()));
+        archive.add_trailer();
+
+        assert_eq!(archive.overlay_dir(&dir).unwrap(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let names: Vec<_> = archive.files.iter().map(|file| &file.filename[..]).collect();
+        assert_eq!(names, [&b"etc/"[..], b"etc/hostname", b"lib", b"libexec", b"TRAILER!!!"]);
+        assert_eq!(archive.lookup("etc/hostname").unwrap().data, b"new");
+        assert_eq!(archive.lookup("lib").unwrap().header.file_type(), FileType::Regular);
+    }
+}
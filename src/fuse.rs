@@ -0,0 +1,183 @@
+//! Read-only FUSE filesystem serving the effective contents of an [`Initramfs`].
+
+use alloc::collections::BTreeMap;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use fuser::{
+    Errno, FileAttr, FileHandle, Filesystem, Generation, INodeNo, LockOwner, MountOption, OpenFlags,
+    ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+};
+
+use crate::{CpioHeader, FileType, Initramfs};
+
+const TTL: Duration = Duration::from_secs(60);
+
+struct Node {
+    parent: u64,
+    /// header and data of the entry, `None` for directories only implied by their contents
+    entry: Option<(CpioHeader, Vec<u8>)>,
+    children: BTreeMap<Vec<u8>, u64>,
+}
+
+/// FUSE filesystem exposing an initramfs read-only, with later archives overriding earlier ones.
+pub struct InitramfsFs {
+    /// indexed by inode number - 1
+    nodes: Vec<Node>,
+}
+
+impl InitramfsFs {
+    pub fn new(initramfs: &Initramfs) -> InitramfsFs {
+        let mut fs = InitramfsFs {
+            nodes: alloc::vec![Node { parent: 1, entry: None, children: BTreeMap::new() }],
+        };
+        for (path, file) in initramfs.effective_view().iter() {
+            let mut ino = 1;
+            for component in path.split(|&b| b == b'/') {
+                ino = match fs.nodes[ino as usize - 1].children.get(component) {
+                    Some(&child) => child,
+                    None => {
+                        fs.nodes.push(Node { parent: ino, entry: None, children: BTreeMap::new() });
+                        let child = fs.nodes.len() as u64;
+                        fs.nodes[ino as usize - 1].children.insert(component.to_vec(), child);
+                        child
+                    }
+                };
+            }
+            fs.nodes[ino as usize - 1].entry = Some((file.header.clone(), file.data.clone()));
+        }
+        fs
+    }
+
+    fn node(&self, ino: INodeNo) -> Result<&Node, Errno> {
+        self.nodes.get((u64::from(ino) as usize).wrapping_sub(1)).ok_or(Errno::ENOENT)
+    }
+
+    fn attr(&self, ino: u64) -> FileAttr {
+        let node = &self.nodes[ino as usize - 1];
+        let (kind, header, size) = match &node.entry {
+            Some((header, data)) => (fuse_file_type(header.file_type()), Some(header), data.len() as u64),
+            None => (fuser::FileType::Directory, None, 0),
+        };
+        let mtime = header.map(CpioHeader::mtime_system_time).unwrap_or(std::time::UNIX_EPOCH);
+        FileAttr {
+            ino: INodeNo(ino),
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind,
+            perm: header.map_or(0o755, |header| header.permissions() as u16),
+            nlink: if kind == fuser::FileType::Directory { 2 } else { 1 },
+            uid: header.map_or(0, |header| header.uid),
+            gid: header.map_or(0, |header| header.gid),
+            rdev: header.map_or(0, |header| {
+                (header.rmin & 0xff) | (header.rmaj << 8) | ((header.rmin & !0xff) << 12)
+            }),
+            flags: 0,
+            blksize: 512,
+        }
+    }
+}
+
+impl Filesystem for InitramfsFs {
+    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
+        let name = crate::fs::os_str_bytes(name);
+        match self.node(parent).map(|node| node.children.get(&name)) {
+            Ok(Some(&ino)) => reply.entry(&TTL, &self.attr(ino), Generation(0)),
+            Ok(None) => reply.error(Errno::ENOENT),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn getattr(&self, _req: &Request, ino: INodeNo, _fh: Option<FileHandle>, reply: ReplyAttr) {
+        match self.node(ino) {
+            Ok(_) => reply.attr(&TTL, &self.attr(ino.into())),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readlink(&self, _req: &Request, ino: INodeNo, reply: ReplyData) {
+        match self.node(ino).map(|node| &node.entry) {
+            Ok(Some((header, data))) if header.file_type() == FileType::Symlink => reply.data(data),
+            Ok(_) => reply.error(Errno::EINVAL),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _fh: FileHandle,
+        offset: u64,
+        size: u32,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        reply: ReplyData,
+    ) {
+        match self.node(ino).map(|node| &node.entry) {
+            Ok(Some((header, data))) if header.file_type() != FileType::Directory => {
+                let start = (offset as usize).min(data.len());
+                let end = start.saturating_add(size as usize).min(data.len());
+                reply.data(&data[start..end]);
+            }
+            Ok(_) => reply.error(Errno::EISDIR),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(&self, _req: &Request, ino: INodeNo, _fh: FileHandle, offset: u64, mut reply: ReplyDirectory) {
+        let node = match self.node(ino) {
+            Ok(node) => node,
+            Err(e) => return reply.error(e),
+        };
+        if self.attr(ino.into()).kind != fuser::FileType::Directory {
+            return reply.error(Errno::ENOTDIR);
+        }
+        let entries = [(u64::from(ino), &b"."[..]), (node.parent, &b".."[..])].into_iter()
+            .chain(node.children.iter().map(|(name, &child)| (child, name.as_slice())));
+        for (i, (child, name)) in entries.enumerate().skip(offset as usize) {
+            let kind = self.attr(child).kind;
+            if reply.add(INodeNo(child), (i + 1) as u64, kind, bytes_os_str(name)) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Mounts the initramfs read-only at `mountpoint`, blocking until it is unmounted.
+pub fn mount(initramfs: &Initramfs, mountpoint: impl AsRef<Path>) -> io::Result<()> {
+    let mut config = fuser::Config::default();
+    config.mount_options.extend([MountOption::RO, MountOption::FSName("initramfs".to_string())]);
+    fuser::mount(InitramfsFs::new(initramfs), mountpoint, &config)
+}
+
+fn fuse_file_type(file_type: FileType) -> fuser::FileType {
+    match file_type {
+        FileType::Directory => fuser::FileType::Directory,
+        FileType::Symlink => fuser::FileType::Symlink,
+        FileType::CharDevice => fuser::FileType::CharDevice,
+        FileType::BlockDevice => fuser::FileType::BlockDevice,
+        FileType::Fifo => fuser::FileType::NamedPipe,
+        FileType::Socket => fuser::FileType::Socket,
+        FileType::Regular | FileType::Unknown => fuser::FileType::RegularFile,
+    }
+}
+
+fn bytes_os_str(bytes: &[u8]) -> std::borrow::Cow<'_, OsStr> {
+    #[cfg(unix)] {
+        use std::os::unix::ffi::OsStrExt;
+        std::borrow::Cow::Borrowed(OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))] {
+        std::borrow::Cow::Owned(String::from_utf8_lossy(bytes).into_owned().into())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::normalize_path;
+
+/// Matches a path against a shell-style glob pattern.
+///
+/// Both pattern and path are normalized first, so `/usr/bin/*`, `./usr/bin/*` and `usr/bin/*`
+/// are equivalent. Supported syntax:
+///
+/// * `*` matches any sequence of characters except `/`
+/// * `**` matches any sequence of characters including `/`, `**/` also matches no directory at all
+/// * `?` matches any single character except `/`
+/// * `[abc]`, `[a-z]`, `[!a-z]` match (or with `!` / `^` don't match) a single character
+/// * `\` escapes the following character
+pub fn glob_match(pattern: &[u8], path: &[u8]) -> bool {
+    matches(normalize_path(pattern), normalize_path(path))
+}
+
+fn matches(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            matches(rest, path) || path.iter().enumerate()
+                .any(|(i, &b)| b == b'/' && matches(rest, &path[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+        [b'*', rest @ ..] => {
+            let component_len = path.iter().position(|&b| b == b'/').unwrap_or(path.len());
+            (0..=component_len).any(|i| matches(rest, &path[i..]))
+        }
+        [b'?', rest @ ..] => match path {
+            [b, path @ ..] if *b != b'/' => matches(rest, path),
+            _ => false,
+        },
+        [b'[', class @ ..] => match (path, parse_class(class)) {
+            ([b, path @ ..], Some((matched, rest))) if *b != b'/' => matched(*b) && matches(rest, path),
+            // unterminated class, treat `[` literally
+            ([b'[', path @ ..], None) => matches(class, path),
+            _ => false,
+        },
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => match path {
+            [b, path @ ..] if b == c => matches(rest, path),
+            _ => false,
+        },
+    }
+}
+
+/// Parses a character class after the opening `[`, returning a matcher and the rest of the pattern.
+fn parse_class(class: &[u8]) -> Option<(impl Fn(u8) -> bool + '_, &[u8])> {
+    let (negated, class) = match class {
+        [b'!' | b'^', class @ ..] => (true, class),
+        class => (false, class),
+    };
+    // a `]` directly after the opening bracket is part of the class
+    let end = 1 + class.get(1..)?.iter().position(|&b| b == b']')?;
+    let (set, rest) = (&class[..end], &class[end + 1..]);
+    let matcher = move |b: u8| {
+        let mut i = 0;
+        let mut found = false;
+        while i < set.len() {
+            if i + 2 < set.len() && set[i + 1] == b'-' {
+                found |= (set[i]..=set[i + 2]).contains(&b);
+                i += 3;
+            } else {
+                found |= set[i] == b;
+                i += 1;
+            }
+        }
+        found != negated
+    };
+    Some((matcher, rest))
+}
@@ -0,0 +1,56 @@
+use alloc::vec::Vec;
+
+use crate::{normalize_path, Archive, File};
+
+/// Sorted index over the paths of an archive for repeated lookups.
+///
+/// Building the index allocates once, lookups are `O(log n)` and don't allocate.
+/// If a path occurs multiple times, the last entry wins like on extraction.
+#[derive(Debug, Clone)]
+pub struct PathIndex<'a> {
+    archive: &'a Archive,
+    /// indices into `archive.files`, sorted by normalized path
+    sorted: Vec<usize>,
+}
+
+impl<'a> PathIndex<'a> {
+    pub fn build(archive: &'a Archive) -> PathIndex<'a> {
+        let path = |i: usize| normalize_path(&archive.files[i].filename);
+        let mut sorted: Vec<_> = (0..archive.files.len())
+            .filter(|&i| !archive.files[i].is_trailer())
+            .collect();
+        // stable, so duplicates stay in archive order
+        sorted.sort_by(|&a, &b| path(a).cmp(path(b)));
+        // keep the last of each run of duplicates
+        let mut deduped: Vec<usize> = Vec::with_capacity(sorted.len());
+        for i in sorted {
+            match deduped.last_mut() {
+                Some(last) if path(*last) == path(i) => *last = i,
+                _ => deduped.push(i),
+            }
+        }
+        PathIndex { archive, sorted: deduped }
+    }
+
+    /// Returns the entry of a path, ignoring leading `/` and `./`.
+    pub fn find_file(&self, path: &str) -> Option<&'a File> {
+        let path = normalize_path(path.as_bytes());
+        let files = &self.archive.files;
+        self.sorted.binary_search_by(|&i| normalize_path(&files[i].filename).cmp(path))
+            .ok()
+            .map(|i| &files[self.sorted[i]])
+    }
+
+    /// Returns the data of a path, see [`find_file`](Self::find_file).
+    pub fn find(&self, path: &str) -> Option<&'a [u8]> {
+        self.find_file(path).map(|file| file.data.as_slice())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+}
@@ -0,0 +1,91 @@
+use alloc::vec::Vec;
+
+use crate::{glob_match, EffectiveView, FileType, Initramfs};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum InitSystem {
+    Systemd,
+    /// `init` is busybox or a script run by it
+    Busybox,
+    /// `init` is a script run by another interpreter
+    Script,
+    /// `init` is some other executable
+    Other,
+    /// there is no `init`
+    Missing,
+}
+
+/// Tool that generated the image, detected by its hook directory layout.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Generator {
+    Dracut,
+    InitramfsTools,
+    Mkinitcpio,
+}
+
+/// Result of [`Initramfs::detect_init`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InitReport {
+    pub init_system: InitSystem,
+    /// path the kernel executes (`init`, with symlinks followed)
+    pub entry_point: Option<Vec<u8>>,
+    /// interpreter from the shebang line if the entry point is a script
+    pub interpreter: Option<Vec<u8>>,
+    pub generator: Option<Generator>,
+    /// hook scripts and units run during boot, sorted by path
+    pub hooks: Vec<Vec<u8>>,
+}
+
+const SYSTEMD_MARKERS: [&str; 3] = ["usr/lib/systemd/systemd", "lib/systemd/systemd", "etc/initrd-release"];
+
+const HOOKS: [(Option<Generator>, &str); 5] = [
+    (Some(Generator::Dracut), "usr/lib/dracut/hooks/**"),
+    (Some(Generator::InitramfsTools), "scripts/*/*"),
+    (Some(Generator::Mkinitcpio), "hooks/*"),
+    (None, "usr/lib/systemd/system/initrd*.target.wants/*"),
+    (None, "etc/systemd/system/initrd*.target.wants/*"),
+];
+
+impl Initramfs {
+    /// Identifies the init system from the entry point the kernel runs (`/init`) and well-known
+    /// paths, and lists hooks of the common initramfs generators.
+    pub fn detect_init(&self) -> InitReport {
+        let view = self.effective_view();
+        let entry = view.follow(b"init").filter(|(_, file)| file.header.file_type() == FileType::Regular);
+        let interpreter = entry.as_ref().and_then(|(_, file)| shebang_interpreter(&file.data)).map(<[u8]>::to_vec);
+        let is_busybox = |path: &[u8]| path.ends_with(b"busybox") || view.follow(path).is_some_and(|(path, _)| path.ends_with(b"busybox"));
+        let init_system = match (&entry, &interpreter) {
+            _ if SYSTEMD_MARKERS.iter().any(|path| view.get(path.as_bytes()).is_some()) => InitSystem::Systemd,
+            (Some((path, _)), _) if path.ends_with(b"systemd") => InitSystem::Systemd,
+            (Some((path, _)), None) if is_busybox(path) => InitSystem::Busybox,
+            (Some(_), Some(interpreter)) if is_busybox(interpreter) => InitSystem::Busybox,
+            (Some(_), Some(_)) => InitSystem::Script,
+            (Some(_), None) => InitSystem::Other,
+            (None, _) => InitSystem::Missing,
+        };
+        let (generator, hooks) = hooks(&view);
+        InitReport { init_system, entry_point: entry.map(|(path, _)| path), interpreter, generator, hooks }
+    }
+}
+
+fn hooks(view: &EffectiveView<'_>) -> (Option<Generator>, Vec<Vec<u8>>) {
+    let mut generator = None;
+    let mut hooks = Vec::new();
+    for (path, file) in view.iter() {
+        if file.header.file_type() == FileType::Directory {
+            continue;
+        }
+        if let Some(&(hook_generator, _)) = HOOKS.iter().find(|(_, pattern)| glob_match(pattern.as_bytes(), path)) {
+            generator = generator.or(hook_generator);
+            hooks.push(path.to_vec());
+        }
+    }
+    (generator, hooks)
+}
+
+/// Returns the interpreter path of a `#!` line, without arguments.
+pub(crate) fn shebang_interpreter(data: &[u8]) -> Option<&[u8]> {
+    let line = data.strip_prefix(b"#!")?;
+    let line = &line[..line.iter().position(|&b| b == b'\n').unwrap_or(line.len())];
+    line.split(|&b| b == b' ' || b == b'\t').find(|word| !word.is_empty())
+}
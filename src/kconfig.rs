@@ -0,0 +1,140 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::{detect_compression, parse_leading_zeroes, Codec, FileType, Initramfs, MaybeRawArchive};
+
+/// Parsed kernel `.config`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct KernelConfig {
+    /// symbol without the `CONFIG_` prefix -> value, e.g. `BLK_DEV_INITRD` -> `y`
+    pub symbols: BTreeMap<String, String>,
+}
+
+impl KernelConfig {
+    /// Parses `CONFIG_<symbol>=<value>` lines, ignoring comments like `# CONFIG_X is not set`.
+    pub fn parse(config: &str) -> KernelConfig {
+        let symbols = config.lines()
+            .filter_map(|line| line.trim().strip_prefix("CONFIG_")?.split_once('='))
+            .map(|(symbol, value)| (symbol.to_string(), value.trim_matches('"').to_string()))
+            .collect();
+        KernelConfig { symbols }
+    }
+
+    /// Returns the value of a symbol, with or without `CONFIG_` prefix.
+    pub fn get(&self, symbol: &str) -> Option<&str> {
+        self.symbols.get(symbol.strip_prefix("CONFIG_").unwrap_or(symbol)).map(String::as_str)
+    }
+
+    pub fn is_builtin(&self, symbol: &str) -> bool {
+        self.get(symbol) == Some("y")
+    }
+
+    /// Whether a symbol is built in or built as module.
+    pub fn is_enabled(&self, symbol: &str) -> bool {
+        matches!(self.get(symbol), Some("y" | "m"))
+    }
+
+    /// Whether the driver of a module is built into the kernel, for the modules known by
+    /// [`Initramfs::root_requirements`](crate::Initramfs::root_requirements).
+    pub fn is_module_builtin(&self, module: &str) -> bool {
+        module_symbol(module).is_some_and(|symbol| self.is_builtin(symbol))
+    }
+}
+
+/// Feature of an image the kernel can't consume, see [`Initramfs::kernel_config_issues`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ConfigIssue {
+    /// `CONFIG_BLK_DEV_INITRD` isn't set, so the kernel ignores the initramfs
+    InitrdDisabled,
+    /// (archive index, codec) segment compressed with a codec whose `CONFIG_RD_*` isn't set
+    UnsupportedCodec(usize, Codec),
+    /// (archive index) raw segment the kernel can't identify
+    UnknownFormat(usize),
+    /// early microcode is bundled, but `CONFIG_MICROCODE` isn't set
+    MicrocodeDisabled,
+    /// directories have mtimes, but `CONFIG_INITRAMFS_PRESERVE_MTIME` isn't set, so they are
+    /// set to the time of extraction
+    MtimesNotPreserved,
+}
+
+impl ConfigIssue {
+    /// Whether the kernel fails to unpack the image, as opposed to losing some information.
+    pub fn is_fatal(self) -> bool {
+        !matches!(self, ConfigIssue::MicrocodeDisabled | ConfigIssue::MtimesNotPreserved)
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigIssue::InitrdDisabled => write!(f, "CONFIG_BLK_DEV_INITRD isn't set, the initramfs is ignored"),
+            ConfigIssue::UnsupportedCodec(archive, codec) => {
+                write!(f, "archive {archive} is compressed with {codec}, but CONFIG_{} isn't set", codec.kernel_config().unwrap_or_default())
+            }
+            ConfigIssue::UnknownFormat(archive) => write!(f, "archive {archive} is in an unknown format"),
+            ConfigIssue::MicrocodeDisabled => write!(f, "early microcode is bundled, but CONFIG_MICROCODE isn't set"),
+            ConfigIssue::MtimesNotPreserved => write!(f, "directory mtimes aren't preserved without CONFIG_INITRAMFS_PRESERVE_MTIME"),
+        }
+    }
+}
+
+impl Initramfs {
+    /// Checks that a kernel built with `config` can unpack the image: initramfs support, the
+    /// decompressors of all compressed segments and the features the image relies on.
+    pub fn kernel_config_issues(&self, config: &KernelConfig) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        if !config.is_builtin("BLK_DEV_INITRD") {
+            issues.push(ConfigIssue::InitrdDisabled);
+        }
+        for (i, archive) in self.archives.iter().enumerate() {
+            let MaybeRawArchive::Raw(raw) = archive else { continue };
+            let data = &raw[parse_leading_zeroes(raw, 0)..];
+            if data.is_empty() {
+                continue;
+            }
+            match detect_compression(data) {
+                Some(codec) if codec.kernel_config().is_some_and(|symbol| !config.is_builtin(symbol)) => {
+                    issues.push(ConfigIssue::UnsupportedCodec(i, codec));
+                }
+                Some(_) => (),
+                None => issues.push(ConfigIssue::UnknownFormat(i)),
+            }
+        }
+        let view = self.effective_view();
+        if view.iter().any(|(path, _)| path.starts_with(b"kernel/x86/microcode/")) && !config.is_builtin("MICROCODE") {
+            issues.push(ConfigIssue::MicrocodeDisabled);
+        }
+        let dir_mtimes = view.iter().any(|(_, file)| file.header.file_type() == FileType::Directory && file.header.mtime != 0);
+        if dir_mtimes && !config.is_builtin("INITRAMFS_PRESERVE_MTIME") {
+            issues.push(ConfigIssue::MtimesNotPreserved);
+        }
+        issues
+    }
+}
+
+/// Config symbol of the driver of a module.
+fn module_symbol(module: &str) -> Option<&'static str> {
+    Some(match module {
+        "sd_mod" => "BLK_DEV_SD",
+        "nvme" => "BLK_DEV_NVME",
+        "virtio_blk" => "VIRTIO_BLK",
+        "xen_blkfront" => "XEN_BLKDEV_FRONTEND",
+        "mmc_block" => "MMC_BLOCK",
+        "md_mod" => "BLK_DEV_MD",
+        "dm_mod" => "BLK_DEV_DM",
+        "dm_crypt" => "DM_CRYPT",
+        "nfs" => "NFS_FS",
+        "ext2" => "EXT2_FS",
+        "ext3" => "EXT3_FS",
+        "ext4" => "EXT4_FS",
+        "xfs" => "XFS_FS",
+        "btrfs" => "BTRFS_FS",
+        "f2fs" => "F2FS_FS",
+        "vfat" => "VFAT_FS",
+        "squashfs" => "SQUASHFS",
+        "erofs" => "EROFS_FS",
+        _ => return None,
+    })
+}
@@ -0,0 +1,75 @@
+use core::fmt::{Display, Formatter};
+
+use crate::{detect_compression, Archive, Codec, Initramfs, MaybeRawArchive};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LayoutError {
+    /// raw segment that is neither an uncompressed cpio archive nor in a known compression format
+    UnknownFormat,
+    /// (offset) uncompressed segment would start unaligned, because the recorded
+    /// [`Archive::padding`] of the previous archive doesn't end on a 4-byte boundary
+    Unaligned(usize),
+}
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LayoutError::UnknownFormat => write!(f, "segment is neither a cpio archive nor compressed in a known format"),
+            LayoutError::Unaligned(offset) => write!(f, "uncompressed segment would start at unaligned offset {offset:#x}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LayoutError {}
+
+impl Initramfs {
+    /// Adds a segment following the rules of the kernel and early microcode loaders: uncompressed
+    /// archives have to precede all compressed ones and start 4-byte-aligned.
+    ///
+    /// Uncompressed segments are inserted before the first compressed one, so their entries no
+    /// longer override those of the compressed segments.
+    pub fn push_segment(&mut self, segment: MaybeRawArchive) -> Result<(), LayoutError> {
+        let codec = match &segment {
+            MaybeRawArchive::Parsed(_) => Codec::Uncompressed,
+            MaybeRawArchive::Raw(raw) => {
+                let start = crate::parse_leading_zeroes(raw, 0);
+                // a segment of only zeros is padding, which can go anywhere
+                match raw.get(start..).filter(|rest| !rest.is_empty()) {
+                    Some(rest) => detect_compression(rest).ok_or(LayoutError::UnknownFormat)?,
+                    None => Codec::Uncompressed,
+                }
+            }
+        };
+        if codec != Codec::Uncompressed {
+            self.archives.push(segment);
+            return Ok(());
+        }
+        let index = self.archives.iter()
+            .position(|archive| matches!(archive, MaybeRawArchive::Raw(raw)
+                if detect_compression(&raw[crate::parse_leading_zeroes(raw, 0)..]).is_some_and(|codec| codec != Codec::Uncompressed)))
+            .unwrap_or(self.archives.len());
+        let offset = self.archives[..index].iter().fold(0, written_end);
+        if !offset.is_multiple_of(4) {
+            return Err(LayoutError::Unaligned(offset));
+        }
+        self.archives.insert(index, segment);
+        Ok(())
+    }
+}
+
+/// Offset after writing `archive` at `offset` with [`Initramfs::write`].
+fn written_end(offset: usize, archive: &MaybeRawArchive) -> usize {
+    match archive {
+        MaybeRawArchive::Raw(raw) => (offset + raw.len()).next_multiple_of(4),
+        MaybeRawArchive::Parsed(Archive { files, padding }) => {
+            let end = files.iter().fold(offset, |offset, file| {
+                (offset.next_multiple_of(4) + 110 + file.filename.len() + 1).next_multiple_of(4) + file.data.len()
+            });
+            match padding {
+                Some(padding) => end + padding,
+                None => end.next_multiple_of(4096),
+            }
+        }
+    }
+}
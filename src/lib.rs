@@ -3,12 +3,140 @@
 // specification: https://www.kernel.org/doc/Documentation/driver-api/early-userspace/buffer-format.rst
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod bootconfig;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "cache")]
+mod cache;
+mod compression;
+mod conflict;
+mod diff;
+mod display;
+mod edit;
+mod elf;
+#[cfg(feature = "std")]
+mod extract;
+#[cfg(feature = "std")]
+pub mod embed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod filter;
+#[cfg(feature = "std")]
+mod fs;
+#[cfg(feature = "fuse")]
+pub mod fuse;
+mod glob;
+mod index;
+mod init;
+mod kconfig;
+mod layout;
+mod lint;
+#[cfg(feature = "sha2")]
+mod merkle;
+mod meta;
+pub mod mode;
+mod modules;
+mod name;
+#[cfg(feature = "oci")]
+mod oci;
+#[cfg(all(feature = "packages", target_os = "linux"))]
+pub mod packages;
+mod patch;
+#[cfg(feature = "std")]
+mod reader;
+mod regex;
+mod roundtrip;
+mod scan;
+mod secrets;
+mod selinux;
+mod shrink;
+mod sort;
+#[cfg(feature = "std")]
+mod source;
+mod split;
+mod squashfs;
+mod stats;
+mod template;
+mod time;
+#[cfg(feature = "uki")]
+pub mod uki;
+mod validate;
+#[cfg(feature = "std")]
+mod verify;
+mod vfs;
+mod walk;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod whiteout;
+mod xattrs;
+
+pub use bootconfig::split_bootconfig;
+#[cfg(feature = "std")]
+pub use builder::ArchiveBuilder;
+#[cfg(feature = "cache")]
+pub use cache::CompressionCache;
+#[cfg(feature = "parse-cache")]
+pub use cache::ParseCache;
+#[cfg(feature = "compression")]
+pub use compression::{compress, decompress};
+pub use compression::{detect_compression, Codec};
+pub use conflict::{OnConflict, PathConflict};
+pub use diff::{ChangeKind, EntryChange};
+pub use display::Listing;
+#[cfg(feature = "std")]
+pub use extract::{ExtractOptions, ExtractReport, SkipReason};
+pub use filter::{FilterError, FilterRule, FilterSet};
+#[cfg(feature = "std")]
+pub use fs::DirOptions;
+pub use glob::glob_match;
+pub use index::PathIndex;
+pub use init::{Generator, InitReport, InitSystem};
+pub use kconfig::{ConfigIssue, KernelConfig};
+pub use layout::LayoutError;
+pub use lint::{Finding, LintConfig, LintRule, Severity};
+#[cfg(feature = "sha2")]
+pub use merkle::{entry_hash, HashTree, InclusionProof};
+pub use meta::EntryMeta;
+pub use mode::{parse_mode_string, parse_symbolic_mode, SymbolicMode};
+pub use modules::{ModuleInventory, ModuleRequirement};
+pub use name::NonUtf8Name;
+#[cfg(feature = "oci")]
+pub use oci::{OciError, OciOptions};
+pub use patch::Patcher;
+#[cfg(feature = "std")]
+pub use reader::EntryReader;
+pub use roundtrip::{Divergence, RoundtripReport};
+pub use scan::{find_embedded_initramfs, EmbeddedInitramfs};
+pub use secrets::{Redaction, SecretDetector, SecretMatch};
+pub use selinux::{FileContexts, FileContextsError};
+pub use shrink::{ShrinkReport, ShrinkRule};
+pub use sort::SortOrder;
+#[cfg(feature = "std")]
+pub use source::DataSource;
+pub use squashfs::SquashfsOptions;
+pub use stats::{ArchiveStats, DirUsage, DuplicateGroup, SegmentReport};
+pub use template::RESCUE_APPLETS;
+pub use time::DateTime;
+pub use validate::{FilenameIssue, MissingInterpreter, MissingLibrary};
+#[cfg(feature = "std")]
+pub use verify::{verify_stream, StreamSummary};
+pub use vfs::{EffectiveView, FsError, ReadOnlyFs, MAX_SYMLINKS};
+pub use walk::{Walk, WalkEntry};
+pub use whiteout::Whiteout;
+pub use xattrs::Xattr;
 
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::fmt::{Display, Formatter};
+use core::ops::Range;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     InvalidCpioHeaderMagic([u8; 6]),
     /// (header property name, property bytes)
@@ -21,6 +149,31 @@ pub enum Error {
     /// (expected, actual)
     InvalidFilenameLength(u32, u32),
     UnexpectedEof,
+    /// (index, size) an offset computed from header values doesn't fit into `usize`,
+    /// e.g. a huge `filesize` on a 32-bit target
+    Overflow(usize, u32),
+    /// (seconds since the epoch) timestamp not representable as u32 mtime
+    MtimeOutOfRange(i64),
+    /// data starts with neither a cpio magic nor the magic of a known compression format
+    UnknownCompression,
+    /// no decompressor is available for the format
+    UnsupportedCompression(Codec),
+    /// the compressed data is corrupt
+    Decompression(Codec),
+    /// reading or writing failed, e.g. in [`verify_stream`]
+    #[cfg(feature = "std")]
+    Io(std::io::ErrorKind),
+    /// (size, max_size) the written image exceeds [`WriteOptions::max_size`]
+    TooLarge(usize, usize),
+    /// (max_segments) the image has more archives than [`ParseOptions::max_segments`]
+    TooManySegments(usize),
+    /// (max_entries) the image has more entries than [`ParseOptions::max_entries`]
+    TooManyEntries(usize),
+    /// (namesize) the filename including its NUL terminator is longer than `PATH_MAX` (4096),
+    /// which the kernel rejects
+    FilenameTooLong(u32),
+    /// (block_size) [`SquashfsOptions::block_size`] isn't a power of two between 4 KiB and 1 MiB
+    InvalidBlockSize(u32),
 }
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
@@ -32,11 +185,100 @@ impl Display for Error {
             Error::InvalidChecksum(expected, actual) => write!(f, "invalid checksum: expected {expected}, got {actual}"),
             Error::InvalidFilenameLength(expected, actual) => write!(f, "invalid filename length: expected {expected}, got {actual}"),
             Error::UnexpectedEof => write!(f, "unexpected EOF"),
+            Error::Overflow(index, size) => write!(f, "size {size} at index {index} overflows the address space"),
+            Error::MtimeOutOfRange(secs) => write!(f, "timestamp {secs} out of range for mtime"),
+            Error::UnknownCompression => write!(f, "unknown compression format"),
+            Error::UnsupportedCompression(codec) => write!(f, "decompressing {codec} isn't supported"),
+            Error::Decompression(codec) => write!(f, "invalid {codec} data"),
+            #[cfg(feature = "std")]
+            Error::Io(kind) => write!(f, "I/O error: {kind}"),
+            Error::TooLarge(size, max_size) => write!(f, "image size {size} exceeds the maximum of {max_size} bytes"),
+            Error::TooManySegments(max) => write!(f, "image has more than {max} archives"),
+            Error::TooManyEntries(max) => write!(f, "image has more than {max} entries"),
+            Error::FilenameTooLong(namesize) => write!(f, "filename of {namesize} bytes exceeds PATH_MAX"),
+            Error::InvalidBlockSize(block_size) => write!(f, "block size {block_size} isn't a power of two between 4096 and 1048576"),
         }
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Deviation from the format accepted with [`ParseOptions::lenient`] or
+/// [`ParseOptions::warn_checksums`], see [`Initramfs::parse_with_warnings`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParseWarning {
+    /// (index of the header, namesize, filename length including the NUL terminator)
+    /// `namesize` includes padding NULs after the filename
+    FilenamePadding(usize, u32, u32),
+    /// (index of the header, expected, actual) checksum mismatch accepted with
+    /// [`ParseOptions::warn_checksums`]
+    InvalidChecksum(usize, u32, u32),
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseWarning::FilenamePadding(index, namesize, len) => {
+                write!(f, "entry at index {index} has namesize {namesize}, but the filename is only {len} bytes long")
+            }
+            ParseWarning::InvalidChecksum(index, expected, actual) => {
+                write!(f, "entry at index {index} has invalid checksum: expected {expected}, got {actual}")
+            }
+        }
+    }
+}
+
+/// Coarse classification of an [`Error`], see [`Error::kind`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// the input is damaged or not a newc cpio archive
+    Corrupt,
+    /// the input is valid but uses a format this crate doesn't handle, e.g. old binary or odc cpio
+    Unsupported,
+    /// a value exceeds what the format or the platform can represent
+    Limit,
+    /// reading or writing failed
+    Io,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            // old character (odc) cpio archives
+            Error::InvalidCpioHeaderMagic([b'0', b'7', b'0', b'7', b'0', b'7']) => ErrorKind::Unsupported,
+            Error::InvalidCpioHeaderMagic(_)
+            | Error::InvalidHex(..)
+            | Error::InvalidAlign(..)
+            | Error::InvalidChecksumNotZero(_)
+            | Error::InvalidChecksum(..)
+            | Error::InvalidFilenameLength(..)
+            | Error::UnexpectedEof
+            | Error::Decompression(_) => ErrorKind::Corrupt,
+            Error::UnknownCompression | Error::UnsupportedCompression(_) => ErrorKind::Unsupported,
+            Error::Overflow(..)
+            | Error::MtimeOutOfRange(_)
+            | Error::TooLarge(..)
+            | Error::TooManySegments(_)
+            | Error::TooManyEntries(_)
+            | Error::FilenameTooLong(_)
+            | Error::InvalidBlockSize(_) => ErrorKind::Limit,
+            #[cfg(feature = "std")]
+            Error::Io(_) => ErrorKind::Io,
+        }
+    }
+
+    /// Whether the structure of the archive is still intact, so that the error can be reported
+    /// as a warning and the contents used anyway, e.g. a wrong checksum or non-zero padding.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, Error::InvalidAlign(..) | Error::InvalidChecksumNotZero(_) | Error::InvalidChecksum(..))
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Initramfs {
     pub archives: Vec<MaybeRawArchive>,
 }
@@ -54,24 +296,173 @@ impl Initramfs {
         self.archives.push(MaybeRawArchive::Raw(archive));
     }
 
-    pub fn parse(initramfs: &Vec<u8>) -> Result<Initramfs, Error> {
+    /// Parses an initramfs from any byte buffer, e.g. a `Vec<u8>`, a memory map or a `&'static [u8]`.
+    pub fn parse(initramfs: impl AsRef<[u8]>) -> Result<Initramfs, Error> {
+        Initramfs::parse_with(initramfs, &ParseOptions::default())
+    }
+
+    pub fn parse_with(initramfs: impl AsRef<[u8]>, options: &ParseOptions) -> Result<Initramfs, Error> {
+        Initramfs::parse_with_warnings(initramfs, options).map(|(initramfs, _)| initramfs)
+    }
+
+    /// Like [`parse_with`](Self::parse_with), but also returns the deviations from the format
+    /// that were accepted with [`ParseOptions::lenient`] or [`ParseOptions::warn_checksums`].
+    pub fn parse_with_warnings(initramfs: impl AsRef<[u8]>, options: &ParseOptions) -> Result<(Initramfs, Vec<ParseWarning>), Error> {
+        let initramfs = initramfs.as_ref();
+        let mut warnings = Vec::new();
         log::trace!("Initramfs::parse");
         let mut archives = Vec::new();
-        let mut index = 0;
+        let mut index = parse_leading_zeroes(initramfs, 0);
+        if options.preserve_layout && index > 0 {
+            archives.push(MaybeRawArchive::Raw(initramfs[..index].to_vec()));
+        }
+        let (mut segments, mut entries) = (0, 0);
         while index < initramfs.len() {
-            index = parse_leading_zeroes(initramfs, index);
-            let (archive, idx) = Archive::parse(initramfs, index)?;
-            index = idx;
+            if let Some(max) = options.max_segments.filter(|&max| segments >= max) {
+                return Err(Error::TooManySegments(max));
+            }
+            segments += 1;
+            let (mut archive, idx) = Archive::parse_counted(initramfs, index, options, &mut warnings, &mut entries)?;
+            index = parse_leading_zeroes(initramfs, idx);
+            if options.preserve_layout {
+                archive.padding = Some(index - idx);
+            }
             archives.push(MaybeRawArchive::Parsed(archive));
         }
-        Ok(Initramfs { archives })
+        Ok((Initramfs { archives }, warnings))
+    }
+
+    /// Parses an initramfs located at `range` within a larger buffer (e.g. a disk or firmware image)
+    /// without copying it.
+    ///
+    /// Alignment is relative to the start of the region, like when the region is loaded on its own.
+    pub fn parse_region(data: &[u8], range: Range<usize>) -> Result<Initramfs, Error> {
+        Initramfs::parse(data.get(range).ok_or(Error::UnexpectedEof)?)
+    }
+
+    /// Iterates over the entries of all parsed archives, including their trailers.
+    pub fn files(&self) -> impl Iterator<Item = &File> {
+        self.archives.iter().filter_map(|archive| match archive {
+            MaybeRawArchive::Parsed(archive) => Some(&archive.files),
+            MaybeRawArchive::Raw(_) => None,
+        }).flatten()
+    }
+
+    pub fn files_mut(&mut self) -> impl Iterator<Item = &mut File> {
+        self.archives.iter_mut().filter_map(|archive| match archive {
+            MaybeRawArchive::Parsed(archive) => Some(&mut archive.files),
+            MaybeRawArchive::Raw(_) => None,
+        }).flatten()
+    }
+
+    /// Like [`files`](Self::files), but skips trailers.
+    pub fn files_without_trailers(&self) -> impl Iterator<Item = &File> {
+        self.files().filter(|file| !file.is_trailer())
+    }
+
+    pub fn files_without_trailers_mut(&mut self) -> impl Iterator<Item = &mut File> {
+        self.files_mut().filter(|file| !file.is_trailer())
+    }
+
+    /// Finds the entry the kernel would extract for a path.
+    ///
+    /// Later entries override earlier ones, so archives and their entries are searched in
+    /// reverse order. Returns the index of the providing archive together with the entry.
+    pub fn lookup(&self, path: &str) -> Option<(usize, &File)> {
+        self.archives.iter().enumerate().rev().find_map(|(i, archive)| match archive {
+            MaybeRawArchive::Parsed(archive) => archive.lookup(path).map(|file| (i, file)),
+            MaybeRawArchive::Raw(_) => None,
+        })
     }
 
+    /// Finds all entries with the given path in extraction order, so the last one is the
+    /// effective entry returned by [`lookup`](Self::lookup) and the others are shadowed by it.
+    pub fn lookup_all(&self, path: &str) -> Vec<(EntryLocation, &File)> {
+        let path = normalize_path(path.as_bytes());
+        let mut found = Vec::new();
+        for (a, archive) in self.archives.iter().enumerate() {
+            let MaybeRawArchive::Parsed(archive) = archive else { continue };
+            found.extend(archive.files.iter().enumerate()
+                .filter(|(_, file)| !file.is_trailer() && normalize_path(&file.filename) == path)
+                .map(|(i, file)| ((a, i), file)));
+        }
+        found
+    }
+
+    /// Switches every parsed entry to the given format, recomputing or zeroing checksums.
+    ///
+    /// Raw archives are left untouched.
+    pub fn convert_checksums(&mut self, to: CpioHeaderMagic) {
+        for archive in &mut self.archives {
+            if let MaybeRawArchive::Parsed(archive) = archive {
+                archive.convert_checksums(to);
+            }
+        }
+    }
+
+    /// Panics if reading lazy data (see [`File::set_source`]) fails, see
+    /// [`try_write_with`](Self::try_write_with).
     pub fn write(&self, data: &mut Vec<u8>) {
+        self.write_with(data, &WriteOptions::default());
+    }
+
+    /// Writes the initramfs into a new buffer, see [`write`](Self::write).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.write(&mut data);
+        data
+    }
+
+    /// Like [`write`](Self::write) with options. Exceeding [`WriteOptions::max_size`] only logs a
+    /// warning, see [`try_write_with`](Self::try_write_with) to fail instead.
+    pub fn write_with(&self, data: &mut Vec<u8>, options: &WriteOptions) {
+        let start = data.len();
+        self.write_archives(data, options.split_size, Archive::write);
+        let size = data.len() - start;
+        if let Some(max_size) = options.max_size.filter(|&max_size| size > max_size) {
+            log::warn!("written initramfs has {size} bytes, exceeding the maximum of {max_size}");
+        }
+    }
+
+    /// Like [`write_with`](Self::write_with), but fails with [`Error::TooLarge`] if the written
+    /// image exceeds [`WriteOptions::max_size`], and like [`File::try_write`] if lazy data can't
+    /// be read. `data` is left unchanged in that case.
+    pub fn try_write_with(&self, data: &mut Vec<u8>, options: &WriteOptions) -> Result<(), Error> {
+        let start = data.len();
+        let mut result = Ok(());
+        self.write_archives(data, options.split_size, |archive, data| {
+            if result.is_ok() {
+                result = archive.try_write(data);
+            }
+        });
+        if let Err(e) = result {
+            data.truncate(start);
+            return Err(e);
+        }
+        let size = data.len() - start;
+        match options.max_size {
+            Some(max_size) if size > max_size => {
+                data.truncate(start);
+                Err(Error::TooLarge(size, max_size))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn write_archives(&self, data: &mut Vec<u8>, split_size: Option<usize>, mut write: impl FnMut(&Archive, &mut Vec<u8>)) {
         for archive in &self.archives {
-            match archive {
-                MaybeRawArchive::Parsed(archive) => archive.write(data),
-                MaybeRawArchive::Raw(raw) => data.extend_from_slice(raw),
+            match (archive, split_size) {
+                (MaybeRawArchive::Parsed(archive), None) => write(archive, data),
+                (MaybeRawArchive::Parsed(archive), Some(split_size)) => {
+                    for archive in archive.split(split_size) {
+                        write(&archive, data);
+                    }
+                }
+                (MaybeRawArchive::Raw(raw), _) => data.extend_from_slice(raw),
+            }
+            if let MaybeRawArchive::Parsed(Archive { padding: Some(_), .. }) = archive {
+                // recorded padding already reproduces the original alignment
+                continue;
             }
             // The spec doesn't state it, but uncompressed archives must be 4-byte-aligned.
             // Compressed archives can directly follow each other unaligned.
@@ -81,62 +472,220 @@ impl Initramfs {
     }
 }
 
+/// Options for [`Initramfs::parse_with`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ParseOptions {
+    /// Records the original header encoding ([`File::raw_header`]), the padding after each archive
+    /// ([`Archive::padding`]) and leading zeros (as a raw archive), so that writing reproduces
+    /// the input byte-for-byte as long as it isn't modified.
+    pub preserve_layout: bool,
+    /// Accepts quirks of some generators instead of failing, reported as [`ParseWarning`]s:
+    /// a `namesize` rounded up or including extra NULs after the filename.
+    /// Such entries are normalized and thus re-encoded on writing.
+    pub lenient: bool,
+    /// Reports checksum mismatches of `070702` entries as [`ParseWarning::InvalidChecksum`]
+    /// instead of failing, like GNU cpio. The stored checksum is kept, so writing the entry
+    /// with [`CpioHeaderMagic::WithChecksum`] repairs it.
+    pub warn_checksums: bool,
+    /// Fails with [`Error::TooManySegments`] instead of parsing more archives, e.g. to bound the
+    /// memory used for untrusted images.
+    pub max_segments: Option<usize>,
+    /// Fails with [`Error::TooManyEntries`] instead of parsing more entries (not counting
+    /// trailers) across all archives, as even empty entries take memory.
+    pub max_entries: Option<usize>,
+}
+
+/// Options for [`Initramfs::write_with`] and [`Initramfs::try_write_with`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct WriteOptions {
+    /// Splits parsed archives into multiple archives of at most this size, see [`Archive::split`].
+    pub split_size: Option<usize>,
+    /// Size budget in bytes, e.g. of a boot partition. With the `compression` feature,
+    /// `Initramfs::estimate_compressed_sizes` helps picking a codec that fits.
+    pub max_size: Option<usize>,
+}
+
+impl WriteOptions {
+    /// Sets the size budget in bytes, e.g.
+    /// `initramfs.try_write_with(&mut data, &WriteOptions::default().max_size(32 << 20))`.
+    pub fn max_size(mut self, bytes: usize) -> WriteOptions {
+        self.max_size = Some(bytes);
+        self
+    }
+}
+
+/// (archive index, file index) of an entry within an [`Initramfs`]
+pub type EntryLocation = (usize, usize);
+
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MaybeRawArchive {
     Parsed(Archive),
-    Raw(Vec<u8>),
+    Raw(#[cfg_attr(feature = "serde", serde(with = "serde_bytes"))] Vec<u8>),
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Archive {
     pub files: Vec<File>,
+    /// Number of zero bytes following the archive in the original input, written instead of
+    /// padding to 4096 bytes. Only recorded with [`ParseOptions::preserve_layout`].
+    pub padding: Option<usize>,
 }
 
 impl Archive {
     pub fn new() -> Archive {
-        Archive { files: Vec::new() }
+        Archive { files: Vec::new(), padding: None }
     }
 
     pub fn add_file(&mut self, mut file: File) {
         match self.files.last() {
-            Some(file) if file.filename == b"TRAILER!!!" => panic!("Archive::add_file called after trailer"),
+            Some(file) if file.is_trailer() => panic!("Archive::add_file called after trailer"),
             _ => (),
         }
         file.header.ino = self.files.len() as u32;
         self.files.push(file);
     }
 
+    /// Adds a file like `cpio -A`: before the trailer if there is one, otherwise at the end.
+    ///
+    /// The inode number is set to one more than the highest inode number in the archive.
+    pub fn append(&mut self, mut file: File) {
+        file.header.ino = self.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+        let index = self.end_of_entries();
+        self.files.insert(index, file);
+    }
+
+    /// Index of the trailer, or the number of files if there is no trailer.
+    pub(crate) fn end_of_entries(&self) -> usize {
+        match self.files.last() {
+            Some(file) if file.is_trailer() => self.files.len() - 1,
+            _ => self.files.len(),
+        }
+    }
+
     pub fn add_trailer(&mut self) {
         self.files.push(File::new("TRAILER!!!".to_string(), Vec::new()));
     }
 
-    pub fn parse(data: &Vec<u8>, mut index: usize) -> Result<(Archive, usize), Error> {
+    pub fn parse(data: &[u8], index: usize) -> Result<(Archive, usize), Error> {
+        Archive::parse_with(data, index, &ParseOptions::default())
+    }
+
+    pub fn parse_with(data: &[u8], index: usize, options: &ParseOptions) -> Result<(Archive, usize), Error> {
+        Archive::parse_with_warnings(data, index, options, &mut Vec::new())
+    }
+
+    /// Like [`parse_with`](Self::parse_with), appending accepted deviations to `warnings`.
+    pub fn parse_with_warnings(
+        data: &[u8], index: usize, options: &ParseOptions, warnings: &mut Vec<ParseWarning>,
+    ) -> Result<(Archive, usize), Error> {
+        Archive::parse_counted(data, index, options, warnings, &mut 0)
+    }
+
+    /// Like [`parse_with_warnings`](Self::parse_with_warnings), but counts the entries against
+    /// [`ParseOptions::max_entries`] starting from `entries`, so the limit applies across archives.
+    fn parse_counted(
+        data: &[u8], mut index: usize, options: &ParseOptions, warnings: &mut Vec<ParseWarning>, entries: &mut usize,
+    ) -> Result<(Archive, usize), Error> {
         log::trace!("Archive::parse {index}");
         let mut files = Vec::new();
         while index < data.len() {
-            let (file, idx) = File::parse(data, index)?;
+            let (mut file, idx) = File::parse_with(data, index, options, warnings)?;
+            if !file.is_trailer() {
+                *entries += 1;
+                if let Some(max) = options.max_entries.filter(|&max| *entries > max) {
+                    return Err(Error::TooManyEntries(max));
+                }
+            }
+            if options.preserve_layout {
+                let header = 4 * index.div_ceil(4);
+                file.raw_header = Some(RawCpioHeader::new(data[header..header + 110].try_into().unwrap()));
+            }
             index = idx;
             files.push(file);
-            if files.last().unwrap().filename == b"TRAILER!!!" {
+            if files.last().unwrap().is_trailer() {
                 break;
             }
         }
-        Ok((Archive { files }, index))
+        Ok((Archive { files, padding: None }, index))
+    }
+
+    /// Like [`parse`](Self::parse), but doesn't read past `end`.
+    ///
+    /// Entries extending beyond `end` result in [`Error::UnexpectedEof`].
+    pub fn parse_at(data: &[u8], index: usize, end: usize) -> Result<(Archive, usize), Error> {
+        Archive::parse(data.get(..end).ok_or(Error::UnexpectedEof)?, index)
+    }
+
+    /// Finds the last entry with the given path, ignoring leading `/` and `./`.
+    pub fn lookup(&self, path: &str) -> Option<&File> {
+        let path = normalize_path(path.as_bytes());
+        self.files.iter().rev()
+            .filter(|file| !file.is_trailer())
+            .find(|file| normalize_path(&file.filename) == path)
+    }
+
+    /// Like [`lookup`](Self::lookup), returning a mutable reference.
+    pub fn lookup_mut(&mut self, path: &str) -> Option<&mut File> {
+        let path = normalize_path(path.as_bytes());
+        self.files.iter_mut().rev()
+            .filter(|file| !file.is_trailer())
+            .find(|file| normalize_path(&file.filename) == path)
+    }
+
+    /// Checks the checksum of every entry, returning one status per entry in `files`.
+    pub fn verify_checksums(&self) -> Vec<ChecksumStatus> {
+        self.files.iter().map(File::verify_checksum).collect()
+    }
+
+    pub fn convert_checksums(&mut self, to: CpioHeaderMagic) {
+        for file in &mut self.files {
+            file.convert_checksum(to);
+        }
     }
 
+    /// Panics if reading lazy data (see [`File::set_source`]) fails, use
+    /// [`try_write`](Self::try_write) or [`write_to`](Self::write_to) to handle that.
     pub fn write(&self, data: &mut Vec<u8>) {
         for file in &self.files {
             file.write(data);
         }
-        write_align_to(data, 4096);
+        self.write_padding(data);
+    }
+
+    /// Like [`write`](Self::write), but fails like [`File::try_write`].
+    pub fn try_write(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+        for file in &self.files {
+            file.try_write(data)?;
+        }
+        self.write_padding(data);
+        Ok(())
+    }
+
+    fn write_padding(&self, data: &mut Vec<u8>) {
+        match self.padding {
+            Some(padding) => data.resize(data.len() + padding, 0),
+            None => write_align_to(data, 4096),
+        }
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct File {
     pub header: CpioHeader,
+    #[cfg_attr(feature = "serde", serde(with = "serde_bytes"))]
     pub filename: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_bytes"))]
     pub data: Vec<u8>,
+    /// Original header encoding, recorded with [`ParseOptions::preserve_layout`].
+    /// Written instead of `header` as long as it still decodes to the same values.
+    pub raw_header: Option<RawCpioHeader>,
+    /// Data read only when writing instead of `data`, see [`File::set_source`]. Not serialized.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub source: Option<DataSource<'static>>,
 }
 
 impl File {
@@ -147,7 +696,7 @@ impl File {
                 magic: CpioHeaderMagic::WithoutChecksum,
                 ino: 0,
                 // directory or regular file
-                mode: if filename.ends_with('/') { 0o40755 } else { 0o100644 },
+                mode: if filename.ends_with('/') { mode::DEFAULT_DIR_MODE } else { mode::DEFAULT_FILE_MODE },
                 uid: 0,
                 gid: 0,
                 nlink: 0,
@@ -162,54 +711,177 @@ impl File {
             },
             filename: filename.into_bytes(),
             data,
+            raw_header: None,
+            #[cfg(feature = "std")]
+            source: None,
         }
     }
 
-    pub fn parse(data: &Vec<u8>, mut index: usize) -> Result<(File, usize), Error> {
+    /// Creates a character device node with the given permission bits and device numbers.
+    pub fn new_char_device(filename: String, permissions: u32, rmaj: u32, rmin: u32) -> File {
+        let mut file = File::new(filename, Vec::new());
+        file.header.mode = mode::S_IFCHR | (permissions & mode::PERMISSION_BITS);
+        file.header.rmaj = rmaj;
+        file.header.rmin = rmin;
+        file
+    }
+
+    pub fn new_symlink(filename: String, target: Vec<u8>) -> File {
+        let mut file = File::new(filename, target);
+        file.header.mode = mode::DEFAULT_SYMLINK_MODE;
+        file
+    }
+
+    pub fn parse(data: &[u8], index: usize) -> Result<(File, usize), Error> {
+        File::parse_with(data, index, &ParseOptions::default(), &mut Vec::new())
+    }
+
+    fn parse_with(data: &[u8], mut index: usize, options: &ParseOptions, warnings: &mut Vec<ParseWarning>) -> Result<(File, usize), Error> {
         log::trace!("File::parse {index}");
         index = parse_align_to_4(data, index)?;
-        let array = data.get(index..index+110).ok_or(Error::UnexpectedEof)?
+        let start = index;
+        let array = data.get(index..).and_then(|rest| rest.get(..110)).ok_or(Error::UnexpectedEof)?
             .try_into().unwrap();
         index += 110;
         let cpio_header = RawCpioHeader::new(array);
-        let header = CpioHeader::parse(&cpio_header)?;
+        let mut header = CpioHeader::parse(&cpio_header)?;
         log::trace!("{header:#?}");
-        let filename: Vec<_> = data.get(index..).ok_or(Error::UnexpectedEof)?
-            .iter().copied()
-            .take_while(|&b| b != 0)
-            .collect();
-        index += filename.len();
-        if filename.len() as u32 + 1 != header.namesize {
-            return Err(Error::InvalidFilenameLength(filename.len() as u32 + 1, header.namesize));
-        }
-        assert_eq!(0, *data.get(index).ok_or(Error::UnexpectedEof)?);
-        index += 1;
+        let rest = data.get(index..).ok_or(Error::UnexpectedEof)?;
+        let filename_len = rest.iter().position(|&b| b == 0).ok_or(Error::UnexpectedEof)?;
+        if filename_len.checked_add(1) != Some(header.namesize as usize) {
+            let padded = options.lenient
+                && header.namesize as usize > filename_len + 1
+                && rest.get(filename_len..header.namesize as usize).is_some_and(|padding| padding.iter().all(|&b| b == 0));
+            if !padded {
+                return Err(Error::InvalidFilenameLength((filename_len as u32).wrapping_add(1), header.namesize));
+            }
+            let warning = ParseWarning::FilenamePadding(start, header.namesize, filename_len as u32 + 1);
+            log::warn!("{warning}");
+            warnings.push(warning);
+        }
+        let filename = rest[..filename_len].to_vec();
+        // the filename and its NUL terminator (and padding) are within `data`, so this can't overflow
+        index += header.namesize as usize;
+        header.namesize = filename_len as u32 + 1;
         index = parse_align_to_4(data, index)?;
-        let mut checksum: u32 = 0;
-        let data: Vec<_> = data.get(index..index + header.filesize as usize)
+        let end = index.checked_add(header.filesize as usize).ok_or(Error::Overflow(index, header.filesize))?;
+        let data = data.get(index..end)
             .ok_or(Error::UnexpectedEof)?
-            .iter().copied()
-            .inspect(|&b| checksum += b as u32)
-            .collect();
-        index += data.len();
-        // verify checksum
-        match header.magic {
-            CpioHeaderMagic::WithoutChecksum => if header.chksum != 0 {
-                return Err(Error::InvalidChecksumNotZero(header.chksum));
-            },
-            CpioHeaderMagic::WithChecksum => if header.chksum != checksum {
-                return Err(Error::InvalidChecksum(header.chksum, checksum));
+            .to_vec();
+        index = end;
+        let file = File {
+            header,
+            filename,
+            data,
+            raw_header: None,
+            #[cfg(feature = "std")]
+            source: None,
+        };
+        match file.verify_checksum() {
+            ChecksumStatus::Ok => (),
+            ChecksumStatus::NotZero(actual) => return Err(Error::InvalidChecksumNotZero(actual)),
+            ChecksumStatus::Mismatch(expected, actual) if options.warn_checksums => {
+                let warning = ParseWarning::InvalidChecksum(start, expected, actual);
+                log::warn!("{warning}");
+                warnings.push(warning);
             }
+            ChecksumStatus::Mismatch(expected, actual) => return Err(Error::InvalidChecksum(expected, actual)),
         }
 
-        log::debug!("parsed file {:?} size {}", String::from_utf8_lossy(&filename), header.filesize);
-        Ok((File { header, filename, data }, index))
+        log::debug!("parsed file {:?} size {}", String::from_utf8_lossy(&file.filename), file.header.filesize);
+        Ok((file, index))
+    }
+
+    /// Renames the entry, updating `namesize`.
+    pub fn set_filename(&mut self, filename: Vec<u8>) {
+        self.header.namesize = filename.len() as u32 + 1;
+        self.filename = filename;
+    }
+
+    pub fn is_trailer(&self) -> bool {
+        self.filename == b"TRAILER!!!"
+    }
+
+    /// Number of bytes this entry occupies when written, including alignment padding.
+    pub fn encoded_len(&self) -> usize {
+        #[cfg(feature = "std")]
+        let data_len = if self.source.is_some() { self.header.filesize as usize } else { self.data.len() };
+        #[cfg(not(feature = "std"))]
+        let data_len = self.data.len();
+        4 * (110 + self.filename.len() + 1).div_ceil(4) + 4 * data_len.div_ceil(4)
     }
 
+    /// Sum of all data bytes (modulo 2^32), as stored in `chksum` by the `070702` format.
+    pub fn checksum(&self) -> u32 {
+        checksum(&self.data)
+    }
+
+    /// Checks `chksum` against the data according to the entry's format.
+    pub fn verify_checksum(&self) -> ChecksumStatus {
+        match self.header.magic {
+            CpioHeaderMagic::WithoutChecksum if self.header.chksum != 0 => ChecksumStatus::NotZero(self.header.chksum),
+            CpioHeaderMagic::WithChecksum if self.header.chksum != self.checksum() => {
+                ChecksumStatus::Mismatch(self.header.chksum, self.checksum())
+            }
+            _ => ChecksumStatus::Ok,
+        }
+    }
+
+    /// Replaces the file data, updating `filesize` and (for `070702` entries) `chksum`.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.header.filesize = data.len() as u32;
+        self.data = data;
+        if self.header.magic == CpioHeaderMagic::WithChecksum {
+            self.header.chksum = self.checksum();
+        }
+    }
+
+    /// Switches the entry between `070701` and `070702`, setting `chksum` as required by the format.
+    pub fn convert_checksum(&mut self, to: CpioHeaderMagic) {
+        self.header.chksum = match to {
+            CpioHeaderMagic::WithoutChecksum => 0,
+            CpioHeaderMagic::WithChecksum => self.checksum(),
+        };
+        self.header.magic = to;
+    }
+
+    /// Panics if reading lazy data (see [`File::set_source`]) fails, use
+    /// [`try_write`](Self::try_write) to handle that.
     pub fn write(&self, data: &mut Vec<u8>) {
+        if let Err(e) = self.try_write(data) {
+            panic!("failed to read data of {}: {e}", String::from_utf8_lossy(&self.filename));
+        }
+    }
+
+    /// Like [`write`](Self::write), but fails with [`Error::Io`] if lazy data can't be read or
+    /// is shorter than `filesize`.
+    pub fn try_write(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        if let Some(file) = self.resolved()? {
+            file.write_in_memory(data);
+            return Ok(());
+        }
+        self.write_in_memory(data);
+        Ok(())
+    }
+
+    /// Writes the entry with the in-memory [`data`](Self::data), ignoring lazy data.
+    pub(crate) fn write_in_memory(&self, data: &mut Vec<u8>) {
         write_align_to_4(data);
-        let cpio_header = self.header.to_cpio_header();
-        cpio_header.write(data);
+        match &self.raw_header {
+            // keep the original encoding (e.g. hex case) of unmodified headers
+            Some(raw) if CpioHeader::parse(raw).as_ref() == Ok(&self.header) && self.verify_checksum() == ChecksumStatus::Ok => {
+                raw.write(data);
+            }
+            _ => {
+                // always emit a valid checksum, even if the data was modified without updating the header
+                let chksum = match self.header.magic {
+                    CpioHeaderMagic::WithChecksum => self.checksum(),
+                    CpioHeaderMagic::WithoutChecksum => self.header.chksum,
+                };
+                self.header.encode_into(chksum, data);
+            }
+        }
         data.extend_from_slice(&self.filename);
         data.push(0);
         write_align_to_4(data);
@@ -217,13 +889,24 @@ impl File {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChecksumStatus {
+    Ok,
+    /// `070701` entry with a non-zero checksum
+    NotZero(u32),
+    /// `070702` entry with a wrong checksum (expected, actual)
+    Mismatch(u32, u32),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CpioHeaderMagic {
     WithoutChecksum,
     WithChecksum,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CpioHeader {
     pub magic: CpioHeaderMagic,
     pub ino: u32,
@@ -242,7 +925,24 @@ pub struct CpioHeader {
     pub chksum: u32,
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum FileType {
+    Regular,
+    Directory,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+    Socket,
+    /// file type bits not set or not known, e.g. for the trailer
+    Unknown,
+}
+
 impl CpioHeader {
+    pub fn file_type(&self) -> FileType {
+        FileType::from_mode(self.mode)
+    }
+
     pub fn parse(header: &RawCpioHeader) -> Result<CpioHeader, Error> {
         log::trace!("CpioHeader::parse");
         Ok(CpioHeader {
@@ -267,6 +967,24 @@ impl CpioHeader {
         })
     }
 
+    /// Appends the encoded header with the given checksum, filling a single reserved region in place.
+    fn encode_into(&self, chksum: u32, data: &mut Vec<u8>) {
+        let start = data.len();
+        data.resize(start + 110, 0);
+        let out = &mut data[start..];
+        out[..6].copy_from_slice(match self.magic {
+            CpioHeaderMagic::WithoutChecksum => b"070701",
+            CpioHeaderMagic::WithChecksum => b"070702",
+        });
+        let fields = [
+            self.ino, self.mode, self.uid, self.gid, self.nlink, self.mtime, self.filesize,
+            self.maj, self.min, self.rmaj, self.rmin, self.namesize, chksum,
+        ];
+        for (field, out) in fields.into_iter().zip(out[6..].chunks_exact_mut(8)) {
+            hex::encode_to_slice(field.to_be_bytes(), out).unwrap();
+        }
+    }
+
     pub fn to_cpio_header(&self) -> RawCpioHeader {
         RawCpioHeader {
             magic: match self.magic {
@@ -291,6 +1009,7 @@ impl CpioHeader {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RawCpioHeader {
     pub magic: [u8; 6],
     pub ino: [u8; 8],
@@ -347,15 +1066,32 @@ impl RawCpioHeader {
     }
 }
 
-fn parse_leading_zeroes(data: &Vec<u8>, mut index: usize) -> usize {
+/// Strips leading `/` and `./` as well as trailing `/`, so that differently written paths compare equal.
+pub(crate) fn normalize_path(mut path: &[u8]) -> &[u8] {
+    loop {
+        if let Some(rest) = path.strip_prefix(b"/") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix(b"./") {
+            path = rest;
+        } else {
+            break;
+        }
+    }
+    while let Some(rest) = path.strip_suffix(b"/") {
+        path = rest;
+    }
+    if path == b"." { b"" } else { path }
+}
+
+pub(crate) fn parse_leading_zeroes(data: &[u8], mut index: usize) -> usize {
     while let Some(0) = data.get(index) {
         index += 1;
     }
     index
 }
 
-fn parse_align_to_4(data: &Vec<u8>, index: usize) -> Result<usize, Error> {
-    let new_index = 4 * ((index + 3) / 4);
+fn parse_align_to_4(data: &[u8], index: usize) -> Result<usize, Error> {
+    let new_index = index.checked_next_multiple_of(4).ok_or(Error::Overflow(index, 4))?;
     for (i, align) in data.get(index..new_index).into_iter().flatten().enumerate() {
         if *align != 0 {
             return Err(Error::InvalidAlign(index + i, *align));
@@ -369,18 +1105,22 @@ fn write_align_to_4(data: &mut Vec<u8>) {
 }
 
 fn write_align_to(data: &mut Vec<u8>, align_to: usize) {
-    let new_len = align_to * ((data.len() + (align_to-1)) / align_to);
+    let new_len = align_to * data.len().div_ceil(align_to);
     assert!(new_len >= data.len());
     data.resize(new_len, 0);
 }
 
+pub(crate) fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |sum, &b| sum.wrapping_add(b as u32))
+}
+
 fn parse_hex_be_u32(property: &'static str, data: [u8; 8]) -> Result<u32, Error> {
     let parse_hex_nibble = |byte: u8| {
-        if b'0' <= byte && byte <= b'9' {
+        if byte.is_ascii_digit() {
             Ok((byte - b'0') as u32)
-        } else if b'a' <= byte && byte <= b'f' {
+        } else if (b'a'..=b'f').contains(&byte) {
             Ok((byte - b'a' + 10) as u32)
-        } else if b'A' <= byte && byte <= b'F' {
+        } else if (b'A'..=b'F').contains(&byte) {
             Ok((byte - b'A' + 10) as u32)
         } else {
             Err(Error::InvalidHex(property, data))
@@ -405,3 +1145,73 @@ fn to_hex_be_u32(data: u32) -> [u8; 8] {
     hex::encode_to_slice(data.to_be_bytes(), &mut array).unwrap();
     array
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encoding of an `init` entry with `namesize` and `filesize` replaced.
+    fn entry(namesize: u32, filesize: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        File::new("init".to_string(), b"data".to_vec()).write(&mut data);
+        data[54..62].copy_from_slice(alloc::format!("{filesize:08X}").as_bytes());
+        data[94..102].copy_from_slice(alloc::format!("{namesize:08X}").as_bytes());
+        data
+    }
+
+    #[test]
+    fn parse_rejects_huge_sizes() {
+        assert_eq!(File::parse(&entry(5, 4), 0).unwrap().0.data, b"data");
+        assert_eq!(File::parse(&entry(u32::MAX, 4), 0).unwrap_err(), Error::InvalidFilenameLength(5, u32::MAX));
+        assert_eq!(File::parse(&entry(5, u32::MAX), 0).unwrap_err(), Error::UnexpectedEof);
+        let mut unterminated = entry(5, 4);
+        unterminated.truncate(110 + 4);
+        assert_eq!(File::parse(&unterminated, 0).unwrap_err(), Error::UnexpectedEof);
+        assert_eq!(parse_align_to_4(&[], usize::MAX - 1), Err(Error::Overflow(usize::MAX - 1, 4)));
+    }
+
+    /// Image of `segments` archives with two entries each.
+    fn image(segments: usize) -> Vec<u8> {
+        let mut initramfs = Initramfs::new();
+        for i in 0..segments {
+            let mut archive = Archive::new();
+            archive.add_file(File::new(alloc::format!("{i}/"), Vec::new()));
+            archive.add_file(File::new(alloc::format!("{i}/file"), b"data".to_vec()));
+            archive.add_trailer();
+            initramfs.archives.push(MaybeRawArchive::Parsed(archive));
+        }
+        let mut data = Vec::new();
+        initramfs.write(&mut data);
+        data
+    }
+
+    #[test]
+    fn parse_limits() {
+        let image = image(3);
+        let limits = |max_segments, max_entries| ParseOptions { max_segments, max_entries, ..ParseOptions::default() };
+        assert_eq!(Initramfs::parse_with(&image, &limits(Some(3), Some(6))).unwrap().archives.len(), 3);
+        assert_eq!(Initramfs::parse_with(&image, &limits(Some(2), None)).unwrap_err(), Error::TooManySegments(2));
+        // trailers don't count
+        assert_eq!(Initramfs::parse_with(&image, &limits(None, Some(5))).unwrap_err(), Error::TooManyEntries(5));
+        assert_eq!(Initramfs::parse_with(&image, &limits(Some(0), None)).unwrap_err(), Error::TooManySegments(0));
+        assert_eq!(Initramfs::parse_with(&image, &limits(None, Some(0))).unwrap_err().kind(), ErrorKind::Limit);
+    }
+
+    #[test]
+    fn try_write_with_max_size() {
+        let initramfs = Initramfs::parse(image(2)).unwrap();
+        let size = image(2).len();
+        // archives are aligned within `data`
+        let prefix = alloc::vec![1; 4096];
+        let mut data = prefix.clone();
+        let result = initramfs.try_write_with(&mut data, &WriteOptions::default().max_size(size - 1));
+        assert_eq!(result, Err(Error::TooLarge(size, size - 1)));
+        assert_eq!(data, prefix);
+        initramfs.try_write_with(&mut data, &WriteOptions::default().max_size(size)).unwrap();
+        assert_eq!(data[4096..], image(2));
+        // only warns
+        let mut data = Vec::new();
+        initramfs.write_with(&mut data, &WriteOptions::default().max_size(1));
+        assert_eq!(data, image(2));
+    }
+}
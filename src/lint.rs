@@ -0,0 +1,304 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::vfs::EffectiveView;
+use crate::walk::path_components;
+use crate::{normalize_path, Archive, FileType, FsError, Initramfs, MaybeRawArchive, MAX_SYMLINKS};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Error];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Severity> {
+        Severity::ALL.into_iter().find(|severity| severity.name() == name)
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Structural check of [`Archive::lint`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum LintRule {
+    /// entry whose parent directory isn't created before it, so the kernel fails to extract it
+    MissingParent,
+    /// non-directory path occurring multiple times, see [`Archive::duplicate_paths`]
+    DuplicatePath,
+    /// `nlink` of a hard link doesn't match the number of entries sharing its inode, e.g. because
+    /// its peers are missing
+    BadNlink,
+    /// hard links sharing an inode differ in mode, ownership or mtime, of which the kernel only
+    /// applies the first
+    InconsistentHardlink,
+    /// symlink whose target doesn't exist in the archive (or [`LintConfig::host_paths`]) or loops
+    DanglingSymlink,
+    /// unknown file type, world-writable entries, setuid / setgid bits
+    SuspiciousMode,
+    /// filename that breaks shell-based tooling, see [`File::filename_issues`](crate::File::filename_issues)
+    BadFilename,
+    /// archive doesn't end with a `TRAILER!!!` entry
+    MissingTrailer,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 8] = [
+        LintRule::MissingParent,
+        LintRule::DuplicatePath,
+        LintRule::BadNlink,
+        LintRule::InconsistentHardlink,
+        LintRule::DanglingSymlink,
+        LintRule::SuspiciousMode,
+        LintRule::BadFilename,
+        LintRule::MissingTrailer,
+    ];
+
+    /// Stable identifier, e.g. for configuration files.
+    pub fn code(self) -> &'static str {
+        match self {
+            LintRule::MissingParent => "missing-parent",
+            LintRule::DuplicatePath => "duplicate-path",
+            LintRule::BadNlink => "bad-nlink",
+            LintRule::InconsistentHardlink => "inconsistent-hardlink",
+            LintRule::DanglingSymlink => "dangling-symlink",
+            LintRule::SuspiciousMode => "suspicious-mode",
+            LintRule::BadFilename => "bad-filename",
+            LintRule::MissingTrailer => "missing-trailer",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<LintRule> {
+        LintRule::ALL.into_iter().find(|rule| rule.code() == code)
+    }
+
+    pub fn default_severity(self) -> Severity {
+        match self {
+            LintRule::MissingParent | LintRule::MissingTrailer => Severity::Error,
+            LintRule::DuplicatePath
+            | LintRule::BadNlink
+            | LintRule::InconsistentHardlink
+            | LintRule::DanglingSymlink
+            | LintRule::SuspiciousMode => Severity::Warning,
+            LintRule::BadFilename => Severity::Info,
+        }
+    }
+}
+
+impl Display for LintRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Configuration of [`Archive::lint`]. By default, all rules are enabled with their
+/// [`LintRule::default_severity`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct LintConfig {
+    /// rule -> severity to report it with, `None` to disable the rule
+    pub overrides: BTreeMap<LintRule, Option<Severity>>,
+    /// Absolute paths provided by the host at runtime, e.g. `/proc` and `/sys`. Symlinks into
+    /// them aren't reported as dangling.
+    pub host_paths: Vec<String>,
+}
+
+impl LintConfig {
+    pub fn severity(&self, rule: LintRule) -> Option<Severity> {
+        self.overrides.get(&rule).copied().unwrap_or(Some(rule.default_severity()))
+    }
+
+    /// Whether the normalized `path` is inside one of the [`host_paths`](Self::host_paths).
+    fn is_host_path(&self, path: &[u8]) -> bool {
+        self.host_paths.iter().any(|host_path| {
+            let host_path = normalize_path(host_path.as_bytes());
+            host_path.is_empty() || path.strip_prefix(host_path).is_some_and(|rest| rest.is_empty() || rest.starts_with(b"/"))
+        })
+    }
+}
+
+/// Normalized path a symlink at `path` points to, resolving `..` lexically.
+fn symlink_target(path: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut components = if target.starts_with(b"/") {
+        Vec::new()
+    } else {
+        let mut components = path_components(path);
+        components.pop();
+        components
+    };
+    for component in path_components(target) {
+        if component == b".." {
+            components.pop();
+        } else {
+            components.push(component);
+        }
+    }
+    components.join(&b'/')
+}
+
+/// Result of [`Archive::lint`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Finding {
+    pub rule: LintRule,
+    pub severity: Severity,
+    /// index of the offending entry in [`Archive::files`], `None` for the archive as a whole
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+    }
+}
+
+impl Archive {
+    /// Runs all enabled structural checks, returning the findings sorted by entry.
+    pub fn lint(&self, config: &LintConfig) -> Vec<Finding> {
+        self.lint_with(config, &BTreeSet::new(), &EffectiveView::new(&self.files))
+    }
+
+    /// `directories` are the directories created by previous archives, symlinks are resolved
+    /// within `view`.
+    fn lint_with(&self, config: &LintConfig, directories: &BTreeSet<&[u8]>, view: &EffectiveView<'_>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut report = |rule: LintRule, index: Option<usize>, message: String| {
+            if let Some(severity) = config.severity(rule) {
+                findings.push(Finding { rule, severity, index, message });
+            }
+        };
+
+        let mut created = directories.clone();
+        for (i, file) in self.files.iter().enumerate().filter(|(_, file)| !file.is_trailer()) {
+            let path = normalize_path(&file.filename);
+            let name = String::from_utf8_lossy(path);
+            if let Some(parent) = path.iter().rposition(|&b| b == b'/').map(|end| &path[..end]) {
+                if !created.contains(parent) {
+                    report(LintRule::MissingParent, Some(i), format!("{name}: parent directory {} isn't created before", String::from_utf8_lossy(parent)));
+                }
+            }
+            // entries can also be created through symlinks to directories
+            if matches!(file.header.file_type(), FileType::Directory | FileType::Symlink) {
+                created.insert(path);
+            }
+
+            let mode = file.header.mode;
+            match file.header.file_type() {
+                FileType::Unknown => report(LintRule::SuspiciousMode, Some(i), format!("{name}: unknown file type in mode {mode:o}")),
+                FileType::Symlink => (),
+                FileType::Directory if file.header.is_world_writable() && !file.header.is_sticky() => {
+                    report(LintRule::SuspiciousMode, Some(i), format!("{name}: world-writable directory without sticky bit"));
+                }
+                FileType::Directory => (),
+                _ if file.header.is_world_writable() => report(LintRule::SuspiciousMode, Some(i), format!("{name}: world-writable")),
+                _ => (),
+            }
+            if file.header.file_type() != FileType::Directory && (file.header.is_setuid() || file.header.is_setgid()) {
+                report(LintRule::SuspiciousMode, Some(i), format!("{name}: setuid or setgid bit set"));
+            }
+
+            if file.header.file_type() == FileType::Symlink {
+                let target = String::from_utf8_lossy(&file.data);
+                match view.resolve(path, MAX_SYMLINKS) {
+                    Ok(_) => (),
+                    Err(FsError::NotFound) if config.is_host_path(&symlink_target(path, &file.data)) => (),
+                    Err(FsError::TooManyLinks) => report(LintRule::DanglingSymlink, Some(i), format!("{name}: target {target} loops")),
+                    Err(FsError::NotADirectory) => {
+                        report(LintRule::DanglingSymlink, Some(i), format!("{name}: target {target} goes through a non-directory"));
+                    }
+                    Err(_) => report(LintRule::DanglingSymlink, Some(i), format!("{name}: target {target} doesn't exist")),
+                }
+            }
+
+            for issue in file.filename_issues() {
+                report(LintRule::BadFilename, Some(i), format!("{name}: {issue}"));
+            }
+        }
+
+        for (path, indices) in self.duplicate_paths() {
+            let name = String::from_utf8_lossy(path);
+            report(LintRule::DuplicatePath, Some(indices[1]), format!("{name}: occurs {} times", indices.len()));
+        }
+
+        let mut inodes: BTreeMap<_, Vec<usize>> = BTreeMap::new();
+        for (i, file) in self.files.iter().enumerate() {
+            if file.header.file_type() != FileType::Directory && file.header.nlink > 1 {
+                inodes.entry((file.header.maj, file.header.min, file.header.ino)).or_default().push(i);
+            }
+        }
+        for indices in inodes.values() {
+            let file = &self.files[indices[0]];
+            let name = String::from_utf8_lossy(normalize_path(&file.filename));
+            if indices.len() == 1 {
+                report(LintRule::BadNlink, Some(indices[0]), format!(
+                    "{name}: nlink is {}, but no other entry has inode {}", file.header.nlink, file.header.ino,
+                ));
+            } else if file.header.nlink as usize != indices.len() {
+                report(LintRule::BadNlink, Some(indices[0]), format!(
+                    "{name}: nlink is {}, but the number of entries with inode {} is {}", file.header.nlink, file.header.ino, indices.len(),
+                ));
+            }
+            for &i in &indices[1..] {
+                let (first, other) = (&file.header, &self.files[i].header);
+                let differing: Vec<_> = [
+                    ("mode", first.mode != other.mode),
+                    ("uid", first.uid != other.uid),
+                    ("gid", first.gid != other.gid),
+                    ("mtime", first.mtime != other.mtime),
+                    ("nlink", first.nlink != other.nlink),
+                ].into_iter().filter(|&(_, differs)| differs).map(|(field, _)| field).collect();
+                if !differing.is_empty() {
+                    report(LintRule::InconsistentHardlink, Some(i), format!(
+                        "{}: differs in {} from {name}, which shares inode {}",
+                        String::from_utf8_lossy(normalize_path(&self.files[i].filename)), differing.join(", "), file.header.ino,
+                    ));
+                }
+            }
+        }
+
+        if !self.files.last().is_some_and(|file| file.is_trailer()) {
+            report(LintRule::MissingTrailer, None, "archive doesn't end with a trailer".into());
+        }
+
+        findings.sort_by_key(|finding| finding.index.unwrap_or(usize::MAX));
+        findings
+    }
+}
+
+impl Initramfs {
+    /// Lints every parsed archive (see [`Archive::lint`]), returning the index of the archive
+    /// with each finding.
+    ///
+    /// Parent directories may come from previous archives and symlinks are resolved in the
+    /// effective tree.
+    pub fn lint(&self, config: &LintConfig) -> Vec<(usize, Finding)> {
+        let view = self.effective_view();
+        let mut directories = BTreeSet::new();
+        let mut findings = Vec::new();
+        for (i, archive) in self.archives.iter().enumerate() {
+            let MaybeRawArchive::Parsed(archive) = archive else { continue };
+            findings.extend(archive.lint_with(config, &directories, &view).into_iter().map(|finding| (i, finding)));
+            directories.extend(archive.files.iter()
+                .filter(|file| matches!(file.header.file_type(), FileType::Directory | FileType::Symlink))
+                .map(|file| normalize_path(&file.filename)));
+        }
+        findings
+    }
+}
@@ -1,24 +1,1613 @@
-use initramfs::{Initramfs, MaybeRawArchive};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use initramfs::{
+    glob_match, Archive, ChangeKind, ChecksumStatus, Codec, CpioHeaderMagic, DirOptions, ExtractOptions, File, FileContexts,
+    FileType, FilterSet, Initramfs, KernelConfig, LintConfig, LintRule, MaybeRawArchive, ParseOptions, Redaction,
+    SecretDetector, Severity, ShrinkRule, SortOrder, SquashfsOptions,
+};
+
+const USAGE: &str = "Usage:
+    [--crc | --no-crc] [--preserve-layout] [--lenient] [--warn-checksums] <initramfs-file> [<output-file>]
+    [--crc | --no-crc] [--preserve-layout] [--lenient] [--warn-checksums] <initramfs-file>... -o <output-file>
+    stats <initramfs-file>...
+    duplicates <initramfs-file>... [-h]
+    info <initramfs-file>...|<uki-file>
+    which <initramfs-file>... <path>
+    init <initramfs-file>...
+    verify-boot <initramfs-file>... [--kernel-config <.config>] [--root <root=>] [--rootfstype <type>]
+    verify <initramfs-file>...
+    verify-extract <initramfs-file>... <directory>
+    profile <initramfs-file>...
+    secrets <initramfs-file>... [--detector private-key|password|password-hash|cloud-credentials]... [--redact remove|replace -o <output-file>]
+    diff <old-initramfs-file> <new-initramfs-file> [--json]
+    lint <initramfs-file>... [--deny info|warning|error] [--rule <code>=info|warning|error|off]... [--host-path <path>]...
+    tree <initramfs-file>... [<path>] [-h]
+    du <initramfs-file>... [<path>] [-d <depth>] [-h] [--sort name|size]
+    ls <initramfs-file>... [<glob>] [-h] [--color always|never|auto] [--cpio]
+    touch <initramfs-file>... <glob> [--mtime @<epoch>] [-o <output-file>]
+    chmod <initramfs-file>... <glob> <octal-mode>|<rwxr-xr-x>|<symbolic-mode> [-o <output-file>]
+    chown <initramfs-file>... <glob> <uid>:<gid> [-o <output-file>]
+    extract <initramfs-file>... <directory> [--portable] [--acls]
+    mount <initramfs-file>... <directory>    (requires the `fuse` feature)
+    from-uki <efi-file> [-o <initrd-output-file>]    (requires the `uki` feature)
+    from-oci <oci-layout-directory>|<image-tarball> -o <output-file> [--platform <os>/<arch>[/<variant>]] [--layers]    (requires the `oci` feature)
+    flatten <initramfs-file>... -o <output-file>
+    create <directory> -o <output-file> [--filter <filter-file>] [--root-squash] [--watch] [-j <threads>] [--xattrs] [--acls] [--file-contexts <file-contexts>]
+    template rescue --busybox <busybox-binary> -o <output-file>
+    convert <initramfs-file>... -o <squashfs-output-file> [--block-size <bytes>] [--no-compress]
+    filter <initramfs-file>... <filter-file> [-o <output-file>]
+    label <initramfs-file>... <file-contexts> [-o <output-file>]
+    sort <initramfs-file>... [--order lexicographic|depth-first|breadth-first] [-o <output-file>]
+    packages <initramfs-file>...    (requires the `packages` feature, Linux only)
+    shrink <initramfs-file>... [--rule locales|docs|static-libs|pycache|dedup]... [-o <output-file>]
+    add <initramfs-file>... <source-file>|- <path> [--mode <octal-mode>|<rwxr-xr-x>] [--uid <uid>] [--gid <gid>] [-o <output-file>]
+    overlay <initramfs-file>... <directory> [-o <output-file>]
+    mv <initramfs-file>... <from> <to> [-o <output-file>]
+    cp <initramfs-file>... <from> <to> [-o <output-file>]
+
+Multiple <initramfs-file>s are concatenated like multiple initrd= entries of a bootloader.
+`-` as <initramfs-file> or <output-file> reads from stdin or writes to stdout.
+touch, chmod, chown, filter, label, sort, shrink, add, overlay, mv and cp accept --dry-run to print
+the changes of the extracted tree instead of writing the image.";
 
 fn main() {
     env_logger::init();
-    let filename = match std::env::args().nth(1) {
-        Some(filename) => filename,
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("stats") => stats(&args[1..]),
+        Some("duplicates") => duplicates(&args[1..]),
+        Some("info") => info(&args[1..]),
+        Some("which") => which(&args[1..]),
+        Some("init") => init(&args[1..]),
+        Some("verify-boot") => verify_boot(&args[1..]),
+        Some("verify") => verify(&args[1..]),
+        Some("verify-extract") => verify_extract(&args[1..]),
+        Some("lint") => lint(&args[1..]),
+        Some("diff") => diff(&args[1..]),
+        Some("secrets") => secrets(&args[1..]),
+        Some("profile") => profile(&args[1..]),
+        Some("tree") => tree(&args[1..]),
+        Some("du") => du(&args[1..]),
+        Some("ls") => ls(&args[1..]),
+        Some("touch") => touch(&args[1..]),
+        Some("chmod") => chmod(&args[1..]),
+        Some("chown") => chown(&args[1..]),
+        Some("extract") => extract(&args[1..]),
+        Some("mount") => mount(&args[1..]),
+        Some("from-uki") => from_uki(&args[1..]),
+        Some("from-oci") => from_oci(&args[1..]),
+        Some("flatten") => flatten(&args[1..]),
+        Some("create") => create(&args[1..]),
+        Some("filter") => filter(&args[1..]),
+        Some("label") => label(&args[1..]),
+        Some("add") => add(&args[1..]),
+        Some("overlay") => overlay(&args[1..]),
+        Some("template") => template(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("shrink") => shrink(&args[1..]),
+        Some("packages") => packages(&args[1..]),
+        Some("sort") => sort(&args[1..]),
+        Some("mv") => mv_cp(&args[1..], Archive::rename, "renamed"),
+        Some("cp") => mv_cp(&args[1..], Archive::copy, "copied"),
+        _ => rewrite(&args),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    std::process::exit(1);
+}
+
+/// Removes `name <value>` from the arguments, returning the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+    if index + 1 == args.len() {
+        usage();
+    }
+    args.remove(index);
+    Some(args.remove(index))
+}
+
+/// Removes `name` from the arguments, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != name);
+    args.len() != len
+}
+
+/// Splits positional arguments into the input images and the `N` trailing arguments of a command.
+fn split_inputs<const N: usize>(args: &[String]) -> (&[String], &[String; N]) {
+    if args.len() <= N {
+        usage();
+    }
+    let (inputs, rest) = args.split_at(args.len() - N);
+    (inputs, rest.try_into().unwrap())
+}
+
+/// Like [`split_inputs`] for an optional trailing argument, which is taken as another input
+/// if it's an existing file.
+fn split_inputs_optional(args: &[String]) -> (&[String], Option<&String>) {
+    match args {
+        [] => usage(),
+        [inputs @ .., last] if !inputs.is_empty() && last != "-" && !Path::new(last).is_file() => (inputs, Some(last)),
+        inputs => (inputs, None),
+    }
+}
+
+/// Reads and parses the concatenation of all input images, like a bootloader passing multiple
+/// `initrd=` entries, which aligns each of them to 4 bytes.
+fn read_initramfs(filenames: &[String]) -> (Vec<u8>, Initramfs) {
+    let content = read_inputs(filenames);
+    let initramfs = Initramfs::parse(&content).expect("parsing initramfs failed");
+    (content, initramfs)
+}
+
+/// Concatenates the inputs, 4-byte-aligned like bootloaders do.
+fn read_inputs(filenames: &[String]) -> Vec<u8> {
+    let mut content = Vec::new();
+    for filename in filenames {
+        content.resize(content.len().next_multiple_of(4), 0);
+        content.extend(read_input(filename));
+    }
+    content
+}
+
+/// The file to write to: `-o`, or the input if there is only one.
+fn output_file<'a>(output: &'a Option<String>, inputs: &'a [String]) -> &'a str {
+    match (output, inputs) {
+        (Some(output), _) => output,
+        (None, [input]) => input,
+        (None, _) => {
+            eprintln!("multiple input images require -o");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn write_initramfs(filename: &str, initramfs: &Initramfs) {
+    write_output(filename, &initramfs.to_bytes());
+}
+
+/// Writes the modified image, or for `--dry-run` (with `before` being the unmodified image)
+/// prints the changes instead.
+fn finish(output: &str, before: Option<Initramfs>, initramfs: &Initramfs) {
+    match before {
+        Some(before) => print_changes(&before, initramfs),
+        None => write_initramfs(output, initramfs),
+    }
+}
+
+/// Prints added, removed and modified paths of the effective tree and the size difference.
+fn print_changes(before: &Initramfs, after: &Initramfs) {
+    let changes = before.diff(after);
+    for change in &changes {
+        println!("{:<8} {}", change.kind.name(), String::from_utf8_lossy(change.path));
+    }
+    let (old_size, new_size) = (before.to_bytes().len(), after.to_bytes().len());
+    println!("{} changes, {old_size} -> {new_size} bytes ({:+})", changes.len(), new_size as i64 - old_size as i64);
+}
+
+/// Reads a file, or stdin for `-`.
+fn read_input(filename: &str) -> Vec<u8> {
+    if filename != "-" {
+        return std::fs::read(filename).expect("can't read file");
+    }
+    let mut content = Vec::new();
+    std::io::stdin().read_to_end(&mut content).expect("can't read stdin");
+    content
+}
+
+/// Writes a file, or stdout for `-`.
+fn write_output(filename: &str, data: &[u8]) {
+    match filename {
+        "-" => std::io::stdout().write_all(data).expect("can't write to stdout"),
+        _ => std::fs::write(filename, data).expect("can't write output file"),
+    }
+}
+
+/// Prints a line to stdout, or to stderr when the output image goes to stdout.
+fn report_line(output: Option<&str>, line: std::fmt::Arguments<'_>) {
+    match output {
+        Some("-") => eprintln!("{line}"),
+        _ => println!("{line}"),
+    }
+}
+
+/// Lists all files, optionally converts checksums, and writes the initramfs back.
+fn rewrite(args: &[String]) {
+    let mut args = args.to_vec();
+    let output = take_option(&mut args, "-o");
+    let mut convert = None;
+    let mut options = ParseOptions::default();
+    let mut positional = Vec::new();
+    for arg in &args {
+        match arg.as_str() {
+            "--crc" => convert = Some(CpioHeaderMagic::WithChecksum),
+            "--no-crc" => convert = Some(CpioHeaderMagic::WithoutChecksum),
+            "--preserve-layout" => options.preserve_layout = true,
+            "--lenient" => options.lenient = true,
+            "--warn-checksums" => options.warn_checksums = true,
+            _ => positional.push(arg),
+        }
+    }
+    // without -o, a second positional argument is the output file
+    let (inputs, output) = match (positional.as_slice(), &output) {
+        ([], _) => usage(),
+        (inputs, Some(output)) => (inputs.iter().map(|input| input.to_string()).collect::<Vec<_>>(), Some(output)),
+        ([input], None) => (vec![input.to_string()], None),
+        ([input, output], None) => (vec![input.to_string()], Some(*output)),
+        (_, None) => usage(),
+    };
+    let content = read_inputs(&inputs);
+    let mut initramfs = Initramfs::parse_with(&content, &options).expect("parsing initramfs failed");
+    if let Some(to) = convert {
+        initramfs.convert_checksums(to);
+    }
+    let output = output.map(|output| output.as_str());
+    for file in initramfs.files() {
+        report_line(output, format_args!("{}: {}", String::from_utf8_lossy(&file.filename), file.header.filesize));
+    }
+    let content2 = initramfs.to_bytes();
+    let report = initramfs.roundtrip_report(&content);
+    report_line(output, format_args!("equal: {}", report.is_equal()));
+    if let Some(divergence) = &report.divergence {
+        report_line(output, format_args!("first divergence: {divergence}"));
+    }
+    if let Some(output) = output {
+        write_output(output, &content2);
+    }
+}
+
+fn stats(args: &[String]) {
+    let (inputs, []) = split_inputs(args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let segments = initramfs.segment_report();
+    for (i, archive) in initramfs.archives.iter().enumerate() {
+        let archive = match archive {
+            MaybeRawArchive::Parsed(archive) => archive,
+            MaybeRawArchive::Raw(raw) => {
+                let segment = &segments[i];
+                let codec = segment.codec.map_or("unknown format", Codec::name);
+                print!("archive {i}: raw ({codec}), {} bytes", raw.len());
+                if let (Some(size), Some(ratio)) = (segment.decompressed_size, segment.ratio()) {
+                    print!(", {size} bytes decompressed (ratio {ratio:.2})");
+                }
+                match segment.entries {
+                    Some(entries) => println!(", {entries} entries"),
+                    None => println!(),
+                }
+                continue;
+            }
+        };
+        let stats = archive.stats();
+        println!("archive {i}: {} entries", stats.entries());
+        println!("  regular files:   {}", stats.regular_files);
+        println!("  directories:     {}", stats.directories);
+        println!("  symlinks:        {}", stats.symlinks);
+        println!("  char devices:    {}", stats.char_devices);
+        println!("  block devices:   {}", stats.block_devices);
+        println!("  fifos:           {}", stats.fifos);
+        println!("  sockets:         {}", stats.sockets);
+        println!("  unknown:         {}", stats.unknown);
+        println!("  data bytes:      {}", stats.data_bytes);
+        println!("  namespace bytes: {}", stats.namespace_bytes);
+        println!("  hardlink groups: {}", stats.hardlink_groups);
+        println!("  padding bytes:   {}", stats.padding_bytes);
+    }
+}
+
+/// Lists regular files with identical content, largest savings first.
+fn duplicates(args: &[String]) {
+    let mut args = args.to_vec();
+    let human = take_flag(&mut args, "-h");
+    let (inputs, []) = split_inputs(&args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let size = |size: usize| if human { human_size(size as u64) } else { format!("{size} bytes") };
+    let (mut total, mut linkable) = (0, 0);
+    for (i, archive) in initramfs.archives.iter().enumerate() {
+        let MaybeRawArchive::Parsed(archive) = archive else { continue };
+        for group in archive.duplicate_content() {
+            let note = if group.linkable { "" } else { ", differing mode or owner" };
+            println!("archive {i}: {} copies of {}, {} reclaimable{note}", group.indices.len(), size(group.size), size(group.reclaimable_bytes()));
+            for &index in &group.indices {
+                println!("  {}", String::from_utf8_lossy(&archive.files[index].filename));
+            }
+            total += group.reclaimable_bytes();
+            if group.linkable {
+                linkable += group.reclaimable_bytes();
+            }
+        }
+    }
+    println!("{} reclaimable, {} of it by `shrink --rule dedup`", size(total), size(linkable));
+}
+
+fn init(args: &[String]) {
+    let (inputs, []) = split_inputs(args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let report = initramfs.detect_init();
+    println!("init system: {:?}", report.init_system);
+    if let Some(entry_point) = &report.entry_point {
+        println!("entry point: {}", String::from_utf8_lossy(entry_point));
+    }
+    if let Some(interpreter) = &report.interpreter {
+        println!("interpreter: {}", String::from_utf8_lossy(interpreter));
+    }
+    if let Some(generator) = report.generator {
+        println!("generator:   {generator:?}");
+    }
+    for hook in &report.hooks {
+        println!("hook:        {}", String::from_utf8_lossy(hook));
+    }
+    for missing in initramfs.missing_interpreters() {
+        println!("missing interpreter {} of {}", String::from_utf8_lossy(missing.interpreter), String::from_utf8_lossy(missing.script));
+    }
+}
+
+/// One-screen summary of an image, or of the initrd of a UKI.
+fn info(args: &[String]) {
+    let (inputs, []) = split_inputs(args);
+    let content = read_inputs(inputs);
+    let (initrd, uki) = match uki_initrd(&content) {
+        Some((initrd, uki)) => (initrd, Some(uki)),
+        None => (&content[..], None),
+    };
+    let (initrd, bootconfig) = match initramfs::split_bootconfig(initrd) {
+        Some((initrd, bootconfig)) => (initrd, Some(bootconfig)),
+        None => (initrd, None),
+    };
+    let initramfs = parse_segments(initrd);
+    let segments = initramfs.segment_report();
+    let initramfs = parse_compressed_segments(initramfs);
+
+    if let Some(uki) = uki {
+        println!("container:  {uki}");
+    }
+    println!("size:       {} ({} bytes)", human_size(initrd.len() as u64), initrd.len());
+    println!("segments:   {}", segments.len());
+    for segment in &segments {
+        print!("  {}: {}, {}", segment.index, segment.codec.map_or("unknown format", Codec::name), human_size(segment.compressed_size as u64));
+        if let Some(size) = segment.decompressed_size.filter(|_| segment.codec != Some(Codec::Uncompressed)) {
+            print!(" ({} decompressed)", human_size(size as u64));
+        }
+        match segment.entries {
+            Some(entries) => println!(", {entries} entries"),
+            None => println!(),
+        }
+    }
+    let report = initramfs.detect_init();
+    println!("init:       {:?}", report.init_system);
+    match report.generator {
+        Some(generator) => println!("generator:  {generator:?}"),
+        None => println!("generator:  unknown"),
+    }
+    let kernels: Vec<_> = initramfs.module_inventory().iter()
+        .map(|inventory| {
+            let count = inventory.modules.len();
+            format!("{} ({count} module{})", String::from_utf8_lossy(inventory.kernel_version), if count == 1 { "" } else { "s" })
+        })
+        .collect();
+    println!("kernels:    {}", if kernels.is_empty() { "no modules".to_string() } else { kernels.join(", ") });
+    let microcode: Vec<_> = initramfs.effective_view().iter()
+        .filter(|(path, file)| path.starts_with(b"kernel/x86/microcode/") && file.header.file_type() == FileType::Regular)
+        .map(|(path, _)| String::from_utf8_lossy(&path[b"kernel/x86/microcode/".len()..]).into_owned())
+        .collect();
+    println!("microcode:  {}", if microcode.is_empty() { "no".to_string() } else { microcode.join(", ") });
+    match bootconfig {
+        Some(bootconfig) => println!("bootconfig: {} bytes", bootconfig.len()),
+        None => println!("bootconfig: no"),
+    }
+}
+
+/// Parses the uncompressed archives at the start of an image, keeping the rest as raw segment,
+/// which needn't be an archive.
+fn parse_segments(content: &[u8]) -> Initramfs {
+    let mut initramfs = Initramfs::new();
+    let mut index = 0;
+    while let Some(start) = content[index..].iter().position(|&b| b != 0).map(|offset| index + offset) {
+        if initramfs::detect_compression(&content[start..]) != Some(Codec::Uncompressed) {
+            initramfs.add_raw_archive(content[start..].to_vec());
+            break;
+        }
+        let (archive, end) = Archive::parse(content, start).expect("parsing archive failed");
+        initramfs.add_archive(archive);
+        index = end;
+    }
+    initramfs
+}
+
+/// Reports which segment (and input) provides the effective entry of a path, and the
+/// entries it shadows.
+fn which(args: &[String]) {
+    let (inputs, [path]) = split_inputs(args);
+    // inputs are parsed one by one to know which one each archive comes from
+    let mut initramfs = Initramfs::new();
+    let mut input_of_archive = Vec::new();
+    for (input, filename) in inputs.iter().enumerate() {
+        let parsed = Initramfs::parse(read_input(filename)).expect("parsing initramfs failed");
+        input_of_archive.extend(std::iter::repeat_n(input, parsed.archives.len()));
+        initramfs.archives.extend(parsed.archives);
+    }
+    let found = initramfs.lookup_all(path);
+    if found.is_empty() {
+        eprintln!("{path}: not found");
+        std::process::exit(1);
+    }
+    for (n, &((archive, index), file)) in found.iter().rev().enumerate() {
+        let status = if n == 0 { "effective" } else { "shadowed" };
+        let input = if inputs.len() > 1 { format!(" of {}", inputs[input_of_archive[archive]]) } else { String::new() };
+        println!(
+            "{status}: segment {archive}{input}, entry {index}: {} {} {}",
+            file.header.mode_string(), file.data.len(), String::from_utf8_lossy(&file.filename),
+        );
+    }
+}
+
+/// The `.initrd` section and a description of the UKI if `content` is one.
+#[cfg(feature = "uki")]
+fn uki_initrd(content: &[u8]) -> Option<(&[u8], String)> {
+    let initrd = initramfs::uki::initrd_section(content).ok()?;
+    // ukify records the kernel version, objcopy-based generators usually don't
+    let description = match initramfs::uki::section(content, b".uname") {
+        Ok(Some(uname)) => format!("UKI built by ukify, kernel {}", String::from_utf8_lossy(uname).trim_end_matches('\0').trim()),
+        _ => "UKI".to_string(),
+    };
+    Some((initrd, description))
+}
+
+#[cfg(not(feature = "uki"))]
+fn uki_initrd(_content: &[u8]) -> Option<(&[u8], String)> {
+    None
+}
+
+/// Replaces compressed segments by their archives, so their contents are inspected as well.
+#[cfg(feature = "compression")]
+fn parse_compressed_segments(mut initramfs: Initramfs) -> Initramfs {
+    for archive in &mut initramfs.archives {
+        if let MaybeRawArchive::Raw(raw) = archive {
+            if let Ok((_, parsed)) = Archive::parse_compressed(raw) {
+                *archive = MaybeRawArchive::Parsed(parsed);
+            }
+        }
+    }
+    initramfs
+}
+
+#[cfg(not(feature = "compression"))]
+fn parse_compressed_segments(initramfs: Initramfs) -> Initramfs {
+    initramfs
+}
+
+/// Runs all boot-relevant checks, printing one line per finding and exiting with 1 on failure.
+fn verify_boot(args: &[String]) {
+    let mut args = args.to_vec();
+    let kernel_config = take_option(&mut args, "--kernel-config")
+        .map(|path| KernelConfig::parse(&std::fs::read_to_string(path).expect("can't read kernel config")));
+    let root = take_option(&mut args, "--root");
+    let rootfstype = take_option(&mut args, "--rootfstype");
+    let (inputs, []) = split_inputs(&args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let mut failed = false;
+    let mut report = |status: &str, message: String| {
+        failed |= status == "FAIL";
+        println!("[{status}] {message}");
+    };
+
+    if let Some(config) = &kernel_config {
+        let issues = initramfs.kernel_config_issues(config);
+        if issues.is_empty() {
+            report("PASS", "kernel config: can unpack the image".to_string());
+        }
+        for issue in issues {
+            report(if issue.is_fatal() { "FAIL" } else { "WARN" }, format!("kernel config: {issue}"));
+        }
+    }
+
+    let init = initramfs.detect_init();
+    match &init.entry_point {
+        Some(entry_point) => report("PASS", format!("init: {:?} at {}", init.init_system, String::from_utf8_lossy(entry_point))),
+        None => report("FAIL", "init: no /init found".to_string()),
+    }
+    for missing in initramfs.missing_interpreters() {
+        report("FAIL", format!("interpreter {} of {} not found", String::from_utf8_lossy(missing.interpreter), String::from_utf8_lossy(missing.script)));
+    }
+
+    let missing_libraries = initramfs.missing_libraries();
+    if missing_libraries.is_empty() {
+        report("PASS", "ELF dependencies: all found".to_string());
+    }
+    for missing in &missing_libraries {
+        report("FAIL", format!("library {} of {} not found", String::from_utf8_lossy(missing.library), String::from_utf8_lossy(missing.binary)));
+    }
+
+    let inventories = initramfs.module_inventory();
+    if inventories.is_empty() {
+        report("INFO", "modules: none bundled".to_string());
+    }
+    for inventory in &inventories {
+        let version = String::from_utf8_lossy(inventory.kernel_version);
+        report("INFO", format!("modules: {} for kernel {version}", inventory.modules.len()));
+        if !inventory.modules.is_empty() && !inventory.has_modules_dep {
+            report("WARN", format!("modules: no modules.dep for kernel {version}, modprobe won't work"));
+        }
+        for (module, dependency) in &inventory.missing_dependencies {
+            report("FAIL", format!("module {} depends on missing {}", String::from_utf8_lossy(module), String::from_utf8_lossy(dependency)));
+        }
+    }
+
+    if let Some(root) = &root {
+        for requirement in initramfs.root_requirements(root, rootfstype.as_deref()) {
+            let alternatives = requirement.alternatives.join(" / ");
+            let bundled = requirement.alternatives.iter().any(|module| inventories.iter().any(|inventory| inventory.provides(module)));
+            let builtin = requirement.alternatives.iter().any(|module| kernel_config.as_ref().is_some_and(|config| config.is_module_builtin(module)));
+            if bundled || builtin {
+                report("PASS", format!("{}: {alternatives} available", requirement.reason));
+            } else if inventories.iter().any(|inventory| !inventory.builtin.is_empty()) || kernel_config.is_some() {
+                report("FAIL", format!("{}: {alternatives} neither bundled nor built in", requirement.reason));
+            } else {
+                report("WARN", format!("{}: {alternatives} not bundled, must be built into the kernel", requirement.reason));
+            }
+        }
+    }
+
+    println!("{}", if failed { "FAIL" } else { "PASS" });
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Checks the structure and checksums of an image while reading it, without loading it into memory.
+fn verify(args: &[String]) {
+    let (inputs, []) = split_inputs(args);
+    let readers = inputs.iter().map(|filename| -> Box<dyn Read> {
+        match filename.as_str() {
+            "-" => Box::new(std::io::stdin().lock()),
+            _ => Box::new(std::fs::File::open(filename).expect("can't open file")),
+        }
+    }).collect();
+    match initramfs::verify_stream(ConcatenatedInputs { readers, offset: 0, padding: 0 }) {
+        Ok(summary) => println!(
+            "OK: {} archives, {} entries, {} bytes of data, {} bytes total",
+            summary.archives, summary.entries, summary.data_bytes, summary.bytes,
+        ),
+        Err(e) => {
+            eprintln!("FAIL: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Streams multiple inputs like [`read_inputs`], aligning each of them to 4 bytes.
+struct ConcatenatedInputs {
+    readers: std::collections::VecDeque<Box<dyn Read>>,
+    offset: u64,
+    /// zeros to emit before the next input
+    padding: usize,
+}
+
+impl Read for ConcatenatedInputs {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.padding > 0 {
+                let len = self.padding.min(buf.len());
+                buf[..len].fill(0);
+                self.padding -= len;
+                self.offset += len as u64;
+                return Ok(len);
+            }
+            let Some(reader) = self.readers.front_mut() else { return Ok(0) };
+            let len = reader.read(buf)?;
+            if len > 0 || buf.is_empty() {
+                self.offset += len as u64;
+                return Ok(len);
+            }
+            self.readers.pop_front();
+            if !self.readers.is_empty() {
+                self.padding = (self.offset.next_multiple_of(4) - self.offset) as usize;
+            }
+        }
+    }
+}
+
+/// Compares an extracted tree against the image, reporting entries whose type, permissions,
+/// device numbers or content (including symlink targets) drifted. Ownership and timestamps
+/// are ignored, as extracting without root privileges doesn't keep them, and so are missing
+/// special files, which `extract` skips.
+fn verify_extract(args: &[String]) {
+    let (inputs, [directory]) = split_inputs(args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let mut extracted = Initramfs::new();
+    extracted.add_archive(Archive::from_dir(directory).expect("can't read directory"));
+    let mut drift = false;
+    for change in initramfs.diff(&extracted) {
+        let fields: Vec<_> = change.changed_fields().into_iter().filter(|field| ["type", "mode", "rdev", "data"].contains(field)).collect();
+        let path = String::from_utf8_lossy(change.path);
+        match change.kind {
+            ChangeKind::Modified if fields.is_empty() => continue,
+            ChangeKind::Removed if change.before.is_some_and(|file| {
+                matches!(file.header.file_type(), FileType::CharDevice | FileType::BlockDevice | FileType::Fifo | FileType::Socket)
+            }) => continue,
+            ChangeKind::Modified => println!("{:<8} {path} ({})", change.kind.name(), fields.join(", ")),
+            ChangeKind::Added | ChangeKind::Removed => println!("{:<8} {path}", change.kind.name()),
+        }
+        drift = true;
+    }
+    if drift {
+        std::process::exit(1);
+    }
+}
+
+/// Times reading, decompressing, parsing and verifying every segment of an image.
+fn profile(args: &[String]) {
+    let (inputs, []) = split_inputs(args);
+    let start = Instant::now();
+    let content = read_inputs(inputs);
+    print_phase("read", content.len(), start.elapsed());
+    // codec -> (compressed bytes, decompressed bytes, time decompressing)
+    let mut codecs: BTreeMap<Codec, (usize, usize, Duration)> = BTreeMap::new();
+    let mut index = 0;
+    let mut segment = 0;
+    while index < content.len() {
+        if content[index] == 0 {
+            index += 1;
+            continue;
+        }
+        let Some(codec) = initramfs::detect_compression(&content[index..]) else {
+            println!("segment {segment} at {index:#x}: unknown format, stopping");
+            break;
+        };
+        println!("segment {segment} at {index:#x}: {codec}");
+        let archives = if codec == Codec::Uncompressed {
+            let start = Instant::now();
+            let (archive, end) = Archive::parse(&content, index).expect("parsing archive failed");
+            print_phase("  parse", end - index, start.elapsed());
+            let stats = codecs.entry(codec).or_default();
+            stats.0 += end - index;
+            stats.1 += end - index;
+            index = end;
+            vec![archive]
+        } else {
+            let start = Instant::now();
+            let decompressed = match decompress(&content[index..]) {
+                Ok(decompressed) => decompressed,
+                Err(e) => {
+                    println!("  {e}, stopping");
+                    break;
+                }
+            };
+            let elapsed = start.elapsed();
+            print_phase("  decompress", decompressed.len(), elapsed);
+            let stats = codecs.entry(codec).or_default();
+            stats.0 += content.len() - index;
+            stats.1 += decompressed.len();
+            stats.2 += elapsed;
+            let start = Instant::now();
+            let initramfs = Initramfs::parse(&decompressed).expect("parsing decompressed archive failed");
+            print_phase("  parse", decompressed.len(), start.elapsed());
+            // the kernel treats the rest of the image as a single compressed stream
+            index = content.len();
+            initramfs.archives.into_iter().filter_map(|archive| match archive {
+                MaybeRawArchive::Parsed(archive) => Some(archive),
+                MaybeRawArchive::Raw(_) => None,
+            }).collect()
+        };
+        let start = Instant::now();
+        let data_bytes = archives.iter().flat_map(|archive| &archive.files).map(|file| file.data.len()).sum();
+        let checksums = archives.iter().flat_map(Archive::verify_checksums).filter(|status| *status != ChecksumStatus::Ok).count();
+        print_phase("  verify", data_bytes, start.elapsed());
+        if checksums > 0 {
+            println!("  {checksums} invalid checksums");
+        }
+        segment += 1;
+    }
+    println!("per codec:");
+    for (codec, (compressed, decompressed, elapsed)) in codecs {
+        print!("  {codec}: {compressed} -> {decompressed} bytes");
+        if codec != Codec::Uncompressed {
+            print!(", ratio {:.2}, decompressing at {}", decompressed as f64 / compressed as f64, throughput(decompressed, elapsed));
+        }
+        println!();
+    }
+}
+
+fn print_phase(phase: &str, bytes: usize, elapsed: Duration) {
+    println!("{phase}: {:.3} ms, {}", elapsed.as_secs_f64() * 1000.0, throughput(bytes, elapsed));
+}
+
+fn throughput(bytes: usize, elapsed: Duration) -> String {
+    format!("{}B/s", human_size((bytes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64))
+}
+
+#[cfg(feature = "compression")]
+fn decompress(segment: &[u8]) -> Result<Vec<u8>, String> {
+    initramfs::decompress(segment).map(|(_, decompressed)| decompressed).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_segment: &[u8]) -> Result<Vec<u8>, String> {
+    Err("decompressing requires initramfs to be built with the `compression` feature".to_string())
+}
+
+/// Prints all lint findings, failing if any is at least as severe as `--deny` (default `error`).
+fn lint(args: &[String]) {
+    let mut args = args.to_vec();
+    let deny = take_option(&mut args, "--deny")
+        .map(|severity| Severity::from_name(&severity).unwrap_or_else(|| usage()))
+        .unwrap_or(Severity::Error);
+    let mut config = LintConfig::default();
+    while let Some(rule) = take_option(&mut args, "--rule") {
+        let Some((code, severity)) = rule.split_once('=') else { usage() };
+        let Some(rule) = LintRule::from_code(code) else {
+            eprintln!("unknown lint rule {code}, expected one of {}", LintRule::ALL.map(LintRule::code).join(", "));
+            std::process::exit(1);
+        };
+        let severity = match severity {
+            "off" => None,
+            severity => Some(Severity::from_name(severity).unwrap_or_else(|| usage())),
+        };
+        config.overrides.insert(rule, severity);
+    }
+    while let Some(path) = take_option(&mut args, "--host-path") {
+        config.host_paths.push(path);
+    }
+    let (inputs, []) = split_inputs(&args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let findings = initramfs.lint(&config);
+    for (archive, finding) in &findings {
+        println!("archive {archive}: {finding}");
+    }
+    let denied = findings.iter().filter(|(_, finding)| finding.severity >= deny).count();
+    println!("{} findings, {denied} at or above {deny}", findings.len());
+    if denied > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Prints the cumulative size of every directory like `du`.
+fn du(args: &[String]) {
+    let mut args = args.to_vec();
+    let human = take_flag(&mut args, "-h");
+    let depth: Option<usize> = take_option(&mut args, "-d").map(|depth| depth.parse().unwrap_or_else(|_| usage()));
+    let by_size = match take_option(&mut args, "--sort").as_deref() {
+        Some("name") | None => false,
+        Some("size") => true,
+        Some(_) => usage(),
+    };
+    let (inputs, root) = split_inputs_optional(&args);
+    let root = root.map_or("", String::as_str).trim_start_matches('/').trim_end_matches('/').as_bytes();
+    let (_, initramfs) = read_initramfs(inputs);
+    let mut usages: Vec<_> = initramfs.du().into_iter()
+        .filter_map(|usage| {
+            let relative = match usage.path.strip_prefix(root)? {
+                [] => 0,
+                [b'/', rest @ ..] => rest.split(|&b| b == b'/').count(),
+                _ if root.is_empty() => usage.path.split(|&b| b == b'/').count(),
+                _ => return None,
+            };
+            depth.is_none_or(|depth| relative <= depth).then_some(usage)
+        })
+        .collect();
+    if by_size {
+        usages.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+    }
+    for usage in usages {
+        let size = if human { human_size(usage.bytes as u64) } else { usage.bytes.to_string() };
+        let path = if usage.path.is_empty() { ".".into() } else { String::from_utf8_lossy(&usage.path) };
+        println!("{size}\t{path}");
+    }
+}
+
+/// Renders the effective tree like `tree`, with entry counts and sizes of every directory.
+fn tree(args: &[String]) {
+    let mut args = args.to_vec();
+    let human = take_flag(&mut args, "-h");
+    let (inputs, root) = split_inputs_optional(&args);
+    let root = root.map_or("", String::as_str);
+    let (_, initramfs) = read_initramfs(inputs);
+    let view = initramfs.effective_view();
+    let mut children: BTreeMap<&[u8], BTreeSet<&[u8]>> = BTreeMap::new();
+    for (path, _) in view.iter() {
+        let mut path = path;
+        while let Some(slash) = path.iter().rposition(|&b| b == b'/') {
+            children.entry(&path[..slash]).or_default().insert(path);
+            path = &path[..slash];
+        }
+        children.entry(b"").or_default().insert(path);
+    }
+    let root = root.trim_start_matches('/').trim_end_matches('/').as_bytes();
+    if !root.is_empty() && view.get(root).is_none() && !children.contains_key(root) {
+        eprintln!("{} not found", String::from_utf8_lossy(root));
+        std::process::exit(1);
+    }
+    let format_size = |size: u64| if human { human_size(size) } else { format!("{size} bytes") };
+
+    let mut lines = Vec::new();
+    let (entries, bytes) = render_tree(&view, &children, root, "", &format_size, &mut lines);
+    println!("{} ({entries} entries, {})", if root.is_empty() { ".".into() } else { String::from_utf8_lossy(root) }, format_size(bytes));
+    for line in lines {
+        println!("{line}");
+    }
+}
+
+/// Renders the children of `dir` as lines of `tree`, returning the number of entries and bytes below it.
+fn render_tree(
+    view: &initramfs::EffectiveView<'_>, children: &BTreeMap<&[u8], BTreeSet<&[u8]>>, dir: &[u8], prefix: &str,
+    format_size: &dyn Fn(u64) -> String, lines: &mut Vec<String>,
+) -> (usize, u64) {
+    let (mut entries, mut bytes) = (0, 0);
+    let Some(dir_children) = children.get(dir) else { return (0, 0) };
+    for (i, &path) in dir_children.iter().enumerate() {
+        let name = String::from_utf8_lossy(&path[path.iter().rposition(|&b| b == b'/').map_or(0, |slash| slash + 1)..]);
+        let (branch, indent) = if i + 1 == dir_children.len() { ("└── ", "    ") } else { ("├── ", "│   ") };
+        let line = lines.len();
+        lines.push(format!("{prefix}{branch}{name}"));
+        let file = view.get(path);
+        entries += 1;
+        bytes += file.map_or(0, |file| file.data.len() as u64);
+        match file {
+            Some(file) if file.header.file_type() == FileType::Symlink => {
+                lines[line] += &format!(" -> {}", String::from_utf8_lossy(&file.data));
+            }
+            Some(file) if file.header.file_type() != FileType::Directory => (),
+            _ => {
+                let (dir_entries, dir_bytes) = render_tree(view, children, path, &format!("{prefix}{indent}"), format_size, lines);
+                lines[line] += &format!(" ({dir_entries} entries, {})", format_size(dir_bytes));
+                entries += dir_entries;
+                bytes += dir_bytes;
+            }
+        }
+    }
+    (entries, bytes)
+}
+
+/// Lists entries like `ls -l`, with aligned columns and colored file types.
+fn ls(args: &[String]) {
+    let mut args = args.to_vec();
+    let human = take_flag(&mut args, "-h");
+    let cpio = take_flag(&mut args, "--cpio");
+    let color = match take_option(&mut args, "--color").as_deref() {
+        Some("always") => true,
+        Some("never") => false,
+        Some("auto") | None => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        Some(_) => usage(),
+    };
+    let (inputs, pattern) = split_inputs_optional(&args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let files: Vec<_> = initramfs.files_without_trailers()
+        .filter(|file| pattern.is_none_or(|pattern| glob_match(pattern.as_bytes(), &file.filename)))
+        .collect();
+    if cpio {
+        for file in files {
+            println!("{}", file.listing());
+        }
+        return;
+    }
+    let rows: Vec<[String; 6]> = files.iter().map(|file| [
+        file.header.mode_string(),
+        file.header.nlink.to_string(),
+        file.header.uid.to_string(),
+        file.header.gid.to_string(),
+        match file.header.file_type() {
+            FileType::CharDevice | FileType::BlockDevice => format!("{}, {}", file.header.rmaj, file.header.rmin),
+            _ if human => human_size(file.data.len() as u64),
+            _ => file.data.len().to_string(),
+        },
+        file.header.mtime_date_time().to_string(),
+    ]).collect();
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.len());
+        }
+    }
+    for (file, [mode, nlink, uid, gid, size, mtime]) in files.iter().zip(&rows) {
+        let [_, nlink_width, uid_width, gid_width, size_width, _] = widths;
+        let name = String::from_utf8_lossy(&file.filename);
+        let name = match ls_color(file) {
+            Some(code) if color => format!("\x1b[{code}m{name}\x1b[0m"),
+            _ => name.into_owned(),
+        };
+        let target = match file.header.file_type() {
+            FileType::Symlink => format!(" -> {}", String::from_utf8_lossy(&file.data)),
+            _ => String::new(),
+        };
+        println!("{mode} {nlink:>nlink_width$} {uid:<uid_width$} {gid:<gid_width$} {size:>size_width$} {mtime} {name}{target}");
+    }
+}
+
+/// SGR color code of an entry, using the defaults of GNU `ls --color`.
+fn ls_color(file: &File) -> Option<&'static str> {
+    match file.header.file_type() {
+        FileType::Directory => Some("01;34"),
+        FileType::Symlink => Some("01;36"),
+        FileType::CharDevice | FileType::BlockDevice => Some("40;33;01"),
+        FileType::Fifo => Some("40;33"),
+        FileType::Socket => Some("01;35"),
+        _ if file.header.is_executable() => Some("01;32"),
+        _ => None,
+    }
+}
+
+/// Size with a binary unit suffix like `ls -h`, e.g. `4.0K` or `123M`.
+fn human_size(size: u64) -> String {
+    let mut value = size as f64;
+    for unit in ["", "K", "M", "G", "T"] {
+        if value < 1024.0 || unit == "T" {
+            return match unit {
+                "" => size.to_string(),
+                _ if value < 10.0 => format!("{:.1}{unit}", (value * 10.0).ceil() / 10.0),
+                _ => format!("{}{unit}", value.ceil()),
+            };
+        }
+        value /= 1024.0;
+    }
+    unreachable!()
+}
+
+fn touch(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let mtime = match take_option(&mut args, "--mtime") {
+        Some(mtime) => mtime.strip_prefix('@').unwrap_or(&mtime).parse().unwrap_or_else(|_| usage()),
         None => {
-            eprintln!("Usage: <initramfs-file>");
+            let secs = SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                .expect("system time before epoch").as_secs();
+            u32::try_from(secs).unwrap_or_else(|_| {
+                eprintln!("{}", initramfs::Error::MtimeOutOfRange(secs as i64));
+                std::process::exit(1);
+            })
+        }
+    };
+    let (inputs, [pattern]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let mut matched = 0;
+    for file in initramfs.files_without_trailers_mut() {
+        if glob_match(pattern.as_bytes(), &file.filename) {
+            file.header.mtime = mtime;
+            matched += 1;
+        }
+    }
+    eprintln!("updated {matched} entries");
+    finish(output, before, &initramfs);
+}
+
+fn chmod(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let (inputs, [pattern, mode]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let absolute = u32::from_str_radix(mode, 8).ok().or_else(|| initramfs::parse_mode_string(mode));
+    let symbolic = initramfs::parse_symbolic_mode(mode);
+    if absolute.is_none() && symbolic.is_none() {
+        usage();
+    }
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let matched: usize = initramfs.archives.iter_mut().filter_map(|archive| match archive {
+        MaybeRawArchive::Parsed(archive) => Some(match (absolute, &symbolic) {
+            (Some(mode), _) => archive.chmod_glob(pattern, mode),
+            (None, Some(symbolic)) => archive.chmod_symbolic_glob(pattern, symbolic),
+            (None, None) => unreachable!(),
+        }),
+        MaybeRawArchive::Raw(_) => None,
+    }).sum();
+    eprintln!("updated {matched} entries");
+    finish(output, before, &initramfs);
+}
+
+fn chown(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let (inputs, [pattern, owner]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let (uid, gid) = owner.split_once(':')
+        .and_then(|(uid, gid)| Some((uid.parse().ok()?, gid.parse().ok()?)))
+        .unwrap_or_else(|| usage());
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let matched: usize = initramfs.archives.iter_mut().filter_map(|archive| match archive {
+        MaybeRawArchive::Parsed(archive) => Some(archive.chown_glob(pattern, uid, gid)),
+        MaybeRawArchive::Raw(_) => None,
+    }).sum();
+    eprintln!("updated {matched} entries");
+    finish(output, before, &initramfs);
+}
+
+fn extract(args: &[String]) {
+    let mut args = args.to_vec();
+    let mut options = ExtractOptions::default();
+    options.portable |= take_flag(&mut args, "--portable");
+    let acls = take_flag(&mut args, "--acls");
+    #[cfg(feature = "xattrs")] {
+        options.acls = acls;
+    }
+    #[cfg(not(feature = "xattrs"))]
+    if acls {
+        requires_xattrs("--acls");
+    }
+    let (inputs, [dir]) = split_inputs(&args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let report = initramfs.extract(dir, &options).expect("can't create output directory");
+    for (path, target) in &report.renamed {
+        eprintln!("renamed {} to {}", String::from_utf8_lossy(path), target.display());
+    }
+    for path in &report.emulated_symlinks {
+        eprintln!("copied target of symlink {}", String::from_utf8_lossy(path));
+    }
+    for (path, reason) in &report.skipped {
+        eprintln!("skipped {}: {reason:?}", String::from_utf8_lossy(path));
+    }
+    #[cfg(feature = "xattrs")]
+    for (path, kind) in &report.failed_acls {
+        eprintln!("can't set ACLs of {}: {kind}", String::from_utf8_lossy(path));
+    }
+    eprintln!("extracted {} entries, skipped {}", report.extracted, report.skipped.len());
+}
+
+#[cfg(not(feature = "xattrs"))]
+fn requires_xattrs(flag: &str) -> ! {
+    eprintln!("{flag} requires initramfs to be built with the `xattrs` feature");
+    std::process::exit(1);
+}
+
+#[cfg(feature = "fuse")]
+fn mount(args: &[String]) {
+    let (inputs, [mountpoint]) = split_inputs(args);
+    let (_, initramfs) = read_initramfs(inputs);
+    initramfs::fuse::mount(&initramfs, mountpoint).expect("mounting failed");
+}
+
+#[cfg(not(feature = "fuse"))]
+fn mount(_args: &[String]) {
+    eprintln!("mount requires initramfs to be built with the `fuse` feature");
+    std::process::exit(1);
+}
+
+/// Lists the files of the `.initrd` section of a UKI, optionally writing the section to a file.
+#[cfg(feature = "uki")]
+fn from_uki(args: &[String]) {
+    let mut args = args.to_vec();
+    let output = take_option(&mut args, "-o");
+    let [filename] = args.as_slice() else { usage() };
+    let content = read_input(filename);
+    let initrd = initramfs::uki::initrd_section(&content).expect("can't extract initrd");
+    if let Some(output) = &output {
+        write_output(output, initrd);
+    }
+    let initramfs = Initramfs::parse(initrd).expect("parsing initramfs failed");
+    for file in initramfs.files() {
+        report_line(output.as_deref(), format_args!("{}: {}", String::from_utf8_lossy(&file.filename), file.header.filesize));
+    }
+}
+
+#[cfg(not(feature = "uki"))]
+fn from_uki(_args: &[String]) {
+    eprintln!("from-uki requires initramfs to be built with the `uki` feature");
+    std::process::exit(1);
+}
+
+/// Flattens the layers of a container image into a new image.
+#[cfg(feature = "oci")]
+fn from_oci(args: &[String]) {
+    let mut args = args.to_vec();
+    let output = take_option(&mut args, "-o").unwrap_or_else(|| usage());
+    let platform = take_option(&mut args, "--platform");
+    let layers = take_flag(&mut args, "--layers");
+    let [image] = args.as_slice() else { usage() };
+    let options = initramfs::OciOptions { platform };
+    let result = if layers {
+        Initramfs::from_oci_layers(image, &options)
+    } else {
+        Archive::from_oci(image, &options).map(|archive| {
+            let mut initramfs = Initramfs::new();
+            initramfs.add_archive(archive);
+            initramfs
+        })
+    };
+    let mut initramfs = result.unwrap_or_else(|e| {
+        eprintln!("{image}: {e}");
+        std::process::exit(1);
+    });
+    for archive in initramfs.archives.iter_mut() {
+        if let MaybeRawArchive::Parsed(archive) = archive {
+            archive.add_trailer();
+        }
+    }
+    write_initramfs(&output, &initramfs);
+    eprintln!("wrote {output}");
+}
+
+#[cfg(not(feature = "oci"))]
+fn from_oci(_args: &[String]) {
+    eprintln!("from-oci requires initramfs to be built with the `oci` feature");
+    std::process::exit(1);
+}
+
+/// Merges the archives of an image like overlay layers, applying their whiteouts.
+fn flatten(args: &[String]) {
+    let mut args = args.to_vec();
+    let output = take_option(&mut args, "-o").unwrap_or_else(|| usage());
+    let (inputs, []) = split_inputs(&args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let mut archive = initramfs.flatten();
+    archive.add_trailer();
+    eprintln!("flattened {} archives into {} entries", initramfs.archives.len(), archive.files.len() - 1);
+    let mut flattened = Initramfs::new();
+    flattened.add_archive(archive);
+    write_initramfs(&output, &flattened);
+    eprintln!("wrote {output}");
+}
+
+fn sort(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let order = match take_option(&mut args, "--order").as_deref() {
+        Some("lexicographic") => SortOrder::Lexicographic,
+        Some("depth-first") | None => SortOrder::DepthFirst,
+        Some("breadth-first") => SortOrder::BreadthFirst,
+        Some(_) => usage(),
+    };
+    let (inputs, []) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    for archive in &mut initramfs.archives {
+        if let MaybeRawArchive::Parsed(archive) = archive {
+            archive.sort(order);
+        }
+    }
+    finish(output, before, &initramfs);
+}
+
+/// Reports which host packages own the entries, and which entries aren't owned by any package.
+#[cfg(all(feature = "packages", target_os = "linux"))]
+fn packages(args: &[String]) {
+    let (inputs, []) = split_inputs(args);
+    let (_, initramfs) = read_initramfs(inputs);
+    let db = initramfs::packages::PackageDb::from_host().expect("can't read package database");
+    for (i, archive) in initramfs.archives.iter().enumerate() {
+        let MaybeRawArchive::Parsed(archive) = archive else { continue };
+        let report = archive.package_ownership(&db);
+        println!("archive {i}:");
+        for (package, files) in &report.packages {
+            println!("  {package}: {} entries", files.len());
+        }
+        println!("  unowned: {} entries", report.unowned.len());
+        for file in &report.unowned {
+            println!("    {}", String::from_utf8_lossy(&file.filename));
+        }
+    }
+}
+
+#[cfg(not(all(feature = "packages", target_os = "linux")))]
+fn packages(_args: &[String]) {
+    eprintln!("packages requires initramfs to be built with the `packages` feature on Linux");
+    std::process::exit(1);
+}
+
+fn template(args: &[String]) {
+    let mut args = args.to_vec();
+    let output = take_option(&mut args, "-o").unwrap_or_else(|| usage());
+    let busybox = take_option(&mut args, "--busybox").unwrap_or_else(|| usage());
+    let [name] = args.as_slice() else { usage() };
+    if name != "rescue" {
+        usage();
+    }
+    let busybox = std::fs::read(&busybox).expect("can't read busybox binary");
+    let mut initramfs = Initramfs::new();
+    initramfs.add_archive(Archive::rescue_template(busybox));
+    write_initramfs(&output, &initramfs);
+    eprintln!("wrote {output}");
+}
+
+/// Writes the extracted tree as a squashfs image, e.g. for a small cpio shim mounting the rest
+/// of the system from it.
+fn convert(args: &[String]) {
+    let mut args = args.to_vec();
+    let no_compress = take_flag(&mut args, "--no-compress");
+    let output = take_option(&mut args, "-o").unwrap_or_else(|| usage());
+    let mut options = SquashfsOptions { compress: !no_compress, ..SquashfsOptions::default() };
+    if let Some(block_size) = take_option(&mut args, "--block-size") {
+        options.block_size = block_size.parse().unwrap_or_else(|_| usage());
+    }
+    if args.is_empty() {
+        usage();
+    }
+    #[cfg(not(feature = "compression"))]
+    if !no_compress {
+        eprintln!("initramfs was built without the `compression` feature, writing an uncompressed squashfs image");
+    }
+    let (_, initramfs) = read_initramfs(&args);
+    let image = initramfs.to_squashfs(&options).unwrap_or_else(|e| {
+        eprintln!("can't convert: {e}");
+        std::process::exit(1);
+    });
+    write_output(&output, &image);
+    eprintln!("wrote {output}");
+}
+
+fn create(args: &[String]) {
+    let mut args = args.to_vec();
+    let watch = take_flag(&mut args, "--watch");
+    let root_squash = take_flag(&mut args, "--root-squash");
+    let xattrs = take_flag(&mut args, "--xattrs");
+    let acls = take_flag(&mut args, "--acls");
+    let output = take_option(&mut args, "-o").unwrap_or_else(|| usage());
+    let filters = take_option(&mut args, "--filter").map(|filename| read_filters(&filename));
+    let contexts = take_option(&mut args, "--file-contexts").map(|filename| read_file_contexts(&filename));
+    let threads = match take_option(&mut args, "-j") {
+        Some(threads) => threads.parse().unwrap_or_else(|_| usage()),
+        None => std::thread::available_parallelism().map_or(1, usize::from),
+    };
+    let [dir] = args.as_slice() else { usage() };
+    #[cfg(feature = "xattrs")]
+    let options = DirOptions { threads, xattrs, acls, ..DirOptions::default() };
+    #[cfg(not(feature = "xattrs"))]
+    let options = {
+        if xattrs || acls {
+            requires_xattrs(if xattrs { "--xattrs" } else { "--acls" });
+        }
+        DirOptions { threads, ..DirOptions::default() }
+    };
+    // the entries read from the directory, whose data is reused for unchanged files
+    let mut read = Archive::new();
+    let mut build = |changed: &(dyn Fn(&Path) -> bool + Sync)| -> std::io::Result<()> {
+        read = Archive::from_dir_reusing(dir, &options, &read, changed)?;
+        let mut archive = read.clone();
+        if let Some(filters) = &filters {
+            archive.apply_filters(filters);
+        }
+        if root_squash {
+            archive.root_squash();
+        }
+        if let Some(contexts) = &contexts {
+            archive.apply_file_contexts(contexts);
+        }
+        archive.add_trailer();
+        let mut initramfs = Initramfs::new();
+        initramfs.add_archive(archive);
+        let data = initramfs.to_bytes();
+        match output.as_str() {
+            "-" => std::io::stdout().write_all(&data)?,
+            _ => std::fs::write(&output, &data)?,
+        }
+        eprintln!("wrote {output}");
+        Ok(())
+    };
+    // state of the directory at the last successful build
+    let mut built = dir_snapshot(Path::new(dir));
+    if let Err(e) = build(&|_| true) {
+        eprintln!("can't build {dir}: {e}");
+        if !watch {
             std::process::exit(1);
         }
+        built.clear();
+    }
+    if !watch {
+        return;
+    }
+    let mut seen = built.clone();
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        let current = dir_snapshot(Path::new(dir));
+        if current == seen {
+            continue;
+        }
+        seen = current.clone();
+        let unchanged: BTreeSet<_> = built.iter().collect();
+        let changed: BTreeSet<PathBuf> = current.iter()
+            .filter(|entry| !unchanged.contains(entry))
+            .map(|(path, ..)| path.clone())
+            .collect();
+        match build(&|path| changed.contains(path)) {
+            Ok(()) => built = current,
+            // e.g. a file deleted while reading the directory, retried on the next change
+            Err(e) => eprintln!("can't build {dir}: {e}"),
+        }
+    }
+}
+
+fn read_filters(filename: &str) -> FilterSet {
+    let config = std::fs::read_to_string(filename).expect("can't read filter file");
+    FilterSet::parse(&config).unwrap_or_else(|e| {
+        eprintln!("{filename}: {e}");
+        std::process::exit(1);
+    })
+}
+
+fn filter(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let (inputs, [filter_file]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let filters = read_filters(filter_file);
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let removed: usize = initramfs.archives.iter_mut().filter_map(|archive| match archive {
+        MaybeRawArchive::Parsed(archive) => Some(archive.apply_filters(&filters)),
+        MaybeRawArchive::Raw(_) => None,
+    }).sum();
+    eprintln!("removed {removed} entries");
+    finish(output, before, &initramfs);
+}
+
+/// Compares the extracted trees of two images, exiting with 1 if they differ.
+///
+/// The JSON output has the schema version 1: `{"version": 1, "changes": [<change>...]}` with
+/// changes sorted by path, each `{"path", "change": "added"|"removed"|"modified", "fields",
+/// "before", "after"}`. `fields` lists the changed fields of modified entries, `before` and
+/// `after` are `null` or `{"type", "mode", "uid", "gid", "size", "mtime", "link_target", "sha256"}`.
+fn diff(args: &[String]) {
+    let mut args = args.to_vec();
+    let json = take_flag(&mut args, "--json");
+    let [old, new] = args.as_slice() else { usage() };
+    let (_, old) = read_initramfs(std::slice::from_ref(old));
+    let (_, new) = read_initramfs(std::slice::from_ref(new));
+    let changes = old.diff(&new);
+    if !json {
+        for change in &changes {
+            let fields = change.changed_fields();
+            let fields = if fields.is_empty() { String::new() } else { format!(" ({})", fields.join(", ")) };
+            println!("{:<8} {}{fields}", change.kind.name(), String::from_utf8_lossy(change.path));
+        }
+    } else {
+        let changes: Vec<_> = changes.iter().map(|change| {
+            let fields: Vec<_> = change.changed_fields().iter().map(|field| json_string(field.as_bytes())).collect();
+            format!(
+                "{{\"path\":{},\"change\":\"{}\",\"fields\":[{}],\"before\":{},\"after\":{}}}",
+                json_string(change.path), change.kind.name(), fields.join(","), json_entry(change.before), json_entry(change.after),
+            )
+        }).collect();
+        println!("{{\"version\":1,\"changes\":[{}]}}", changes.join(","));
+    }
+    if !changes.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn json_entry(file: Option<&File>) -> String {
+    let Some(file) = file else { return "null".into() };
+    let meta = file.meta();
+    let file_type = match meta.file_type {
+        FileType::Regular => "regular",
+        FileType::Directory => "directory",
+        FileType::Symlink => "symlink",
+        FileType::CharDevice => "char-device",
+        FileType::BlockDevice => "block-device",
+        FileType::Fifo => "fifo",
+        FileType::Socket => "socket",
+        FileType::Unknown => "unknown",
     };
-    let content = std::fs::read(filename).expect("can't read file");
-    let initramfs = Initramfs::parse(&content).expect("parsing initramfs failed");
-    let files = initramfs.archives.iter().filter_map(|archive| match archive {
-        MaybeRawArchive::Parsed(archive) => Some(&archive.files),
+    format!(
+        "{{\"type\":\"{file_type}\",\"mode\":\"{:04o}\",\"uid\":{},\"gid\":{},\"size\":{},\"mtime\":{},\"link_target\":{},\"sha256\":\"{}\"}}",
+        meta.mode, meta.uid, meta.gid, meta.size, meta.mtime,
+        meta.link_target.map_or("null".into(), json_string), hex::encode(file.sha256()),
+    )
+}
+
+/// Encodes bytes as a JSON string, replacing invalid UTF-8.
+fn json_string(s: &[u8]) -> String {
+    let mut json = String::from("\"");
+    for c in String::from_utf8_lossy(s).chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Reports secrets found by the selected (by default all) detectors, exiting with 1 if there are
+/// any unless they are redacted.
+fn secrets(args: &[String]) {
+    let mut args = args.to_vec();
+    let output = take_option(&mut args, "-o");
+    let redaction = take_option(&mut args, "--redact").map(|redaction| match redaction.as_str() {
+        "remove" => Redaction::Remove,
+        "replace" => Redaction::Replace,
+        _ => usage(),
+    });
+    let mut detectors = Vec::new();
+    while let Some(detector) = take_option(&mut args, "--detector") {
+        detectors.push(SecretDetector::from_name(&detector).unwrap_or_else(|| usage()));
+    }
+    if detectors.is_empty() {
+        detectors = SecretDetector::ALL.to_vec();
+    }
+    let (inputs, []) = split_inputs(&args);
+    if redaction.is_some() != output.is_some() {
+        usage();
+    }
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let mut found = 0;
+    for (i, archive) in initramfs.archives.iter_mut().enumerate() {
+        let MaybeRawArchive::Parsed(archive) = archive else { continue };
+        let paths: Vec<_> = archive.files.iter().map(|file| String::from_utf8_lossy(&file.filename).into_owned()).collect();
+        let matches = match redaction {
+            Some(redaction) => archive.redact_secrets(&detectors, redaction),
+            None => archive.scan_secrets(&detectors),
+        };
+        for m in &matches {
+            report_line(output.as_deref(), format_args!("archive {i}: {}: {} at offset {:#x}", paths[m.index], m.detector, m.offset));
+        }
+        found += matches.len();
+    }
+    eprintln!("{found} possible secrets");
+    match &output {
+        Some(output) => write_initramfs(output, &initramfs),
+        None if found > 0 => std::process::exit(1),
+        None => (),
+    }
+}
+
+fn read_file_contexts(filename: &str) -> FileContexts {
+    let specs = std::fs::read_to_string(filename).expect("can't read file contexts");
+    FileContexts::parse(&specs).unwrap_or_else(|e| {
+        eprintln!("{filename}: {e}");
+        std::process::exit(1);
+    })
+}
+
+fn label(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let (inputs, [contexts_file]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let contexts = read_file_contexts(contexts_file);
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let labeled = initramfs.apply_file_contexts(&contexts);
+    eprintln!("labeled {labeled} entries");
+    finish(output, before, &initramfs);
+}
+
+/// Applies the selected (by default all) shrink rules and reports the savings of each.
+fn shrink(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let mut rules = Vec::new();
+    while let Some(rule) = take_option(&mut args, "--rule") {
+        rules.push(ShrinkRule::from_name(&rule).unwrap_or_else(|| usage()));
+    }
+    if rules.is_empty() {
+        rules = ShrinkRule::ALL.to_vec();
+    }
+    let (inputs, []) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let (content, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    for (i, archive) in initramfs.archives.iter_mut().enumerate() {
+        let MaybeRawArchive::Parsed(archive) = archive else { continue };
+        for report in archive.shrink(&rules) {
+            report_line(Some(output), format_args!("archive {i}: {:<12} {:>6} entries {:>10} bytes saved", report.rule.name(), report.entries, report.bytes_saved));
+        }
+    }
+    let shrunk = initramfs.to_bytes();
+    report_line(Some(output), format_args!("total: {} -> {} bytes", content.len(), shrunk.len()));
+    match before {
+        Some(before) => print_changes(&before, &initramfs),
+        None => write_output(output, &shrunk),
+    }
+}
+
+/// Path, modification time and size of every entry below `dir`, used to detect changes.
+fn dir_snapshot(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>, u64)> {
+    let mut snapshot = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let Ok(metadata) = std::fs::symlink_metadata(entry.path()) else { continue };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            }
+            snapshot.push((entry.path(), metadata.modified().ok(), metadata.len()));
+        }
+    }
+    snapshot.sort();
+    snapshot
+}
+
+/// Adds a regular file with the content of a host file or stdin to the last archive, replacing
+/// an existing entry at the same path.
+fn add(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let mode = take_option(&mut args, "--mode")
+        .map(|mode| u32::from_str_radix(&mode, 8).ok().or_else(|| initramfs::parse_mode_string(&mode)).unwrap_or_else(|| usage()));
+    let uid = take_option(&mut args, "--uid").map(|uid| uid.parse().unwrap_or_else(|_| usage()));
+    let gid = take_option(&mut args, "--gid").map(|gid| gid.parse().unwrap_or_else(|_| usage()));
+    let (inputs, [source, path]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    if source == "-" && inputs.iter().any(|input| input == "-") {
+        eprintln!("can't read both the image and the file content from stdin");
+        std::process::exit(1);
+    }
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let mut file = File::new(path.trim_start_matches('/').to_string(), read_input(source));
+    file.header.set_permissions(mode.unwrap_or(0o644));
+    file.header.uid = uid.unwrap_or(0);
+    file.header.gid = gid.unwrap_or(0);
+    file.header.nlink = 1;
+
+    let archive = last_archive(&mut initramfs);
+    let parent = path.trim_matches('/').rsplit_once('/').map(|(parent, _)| parent);
+    if parent.is_some_and(|parent| archive.lookup(parent).is_none()) {
+        eprintln!("warning: parent directory of {path} doesn't exist in the last archive");
+    }
+    // a new inode, as the replaced entry may have been a hard link
+    file.header.ino = archive.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+    match archive.lookup_mut(path) {
+        Some(existing) => {
+            *existing = file;
+            eprintln!("replaced {path}");
+        }
+        None => {
+            archive.append(file);
+            eprintln!("added {path}");
+        }
+    }
+    finish(output, before, &initramfs);
+}
+
+/// Copies a host directory on top of the last archive.
+fn overlay(args: &[String]) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let (inputs, [dir]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let count = last_archive(&mut initramfs).overlay_dir(dir).expect("can't read directory");
+    eprintln!("overlaid {count} entries");
+    finish(output, before, &initramfs);
+}
+
+/// The last archive to add entries to, which is appended if the image ends with a compressed
+/// or unparsed segment, as entries of earlier archives would be overridden.
+fn last_archive(initramfs: &mut Initramfs) -> &mut Archive {
+    if !matches!(initramfs.archives.last(), Some(MaybeRawArchive::Parsed(_))) {
+        let mut archive = Archive::new();
+        archive.add_trailer();
+        initramfs.add_archive(archive);
+    }
+    let Some(MaybeRawArchive::Parsed(archive)) = initramfs.archives.last_mut() else { unreachable!() };
+    archive
+}
+
+fn mv_cp(args: &[String], op: fn(&mut Archive, &str, &str) -> usize, verb: &str) {
+    let mut args = args.to_vec();
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let output = take_option(&mut args, "-o");
+    let (inputs, [from, to]) = split_inputs(&args);
+    let output = output_file(&output, inputs);
+    let (_, mut initramfs) = read_initramfs(inputs);
+    let before = dry_run.then(|| initramfs.clone());
+    let count: usize = initramfs.archives.iter_mut().filter_map(|archive| match archive {
+        MaybeRawArchive::Parsed(archive) => Some(op(archive, from, to)),
         MaybeRawArchive::Raw(_) => None,
-    }).flatten();
-    for file in files {
-        println!("{}: {}", String::from_utf8_lossy(&file.filename), file.header.filesize);
+    }).sum();
+    if count == 0 {
+        eprintln!("{from} not found");
+        std::process::exit(1);
     }
-    let mut content2 = Vec::new();
-    initramfs.write(&mut content2);
-    println!("equal: {}", content == content2);
+    eprintln!("{verb} {count} entries");
+    finish(output, before, &initramfs);
 }
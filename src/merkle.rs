@@ -0,0 +1,219 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use sha2::digest::{Digest, Output};
+
+use crate::{normalize_path, Archive, File};
+
+// leaves and inner nodes are hashed with different prefixes (like RFC 6962), so an inner node
+// can't be passed off as an entry
+const LEAF: u8 = 0;
+const NODE: u8 = 1;
+
+/// Merkle tree over the entries of an archive, see [`Archive::hash_tree`].
+///
+/// An odd node at the end of a level is promoted to the next level unchanged, so subtrees over
+/// the same leaves have the same hash in trees of different sizes.
+#[derive(Debug, Clone)]
+pub struct HashTree<D: Digest> {
+    /// index in [`Archive::files`] of the entry of each leaf
+    pub entries: Vec<usize>,
+    /// levels from the leaves up to the root, which is the only node of the last level
+    levels: Vec<Vec<Output<D>>>,
+}
+
+/// Proof that an entry is part of a [`HashTree`], see [`HashTree::proof`].
+#[derive(Debug, Clone)]
+pub struct InclusionProof<D: Digest> {
+    /// leaf index
+    pub leaf: usize,
+    /// sibling hashes from the leaf up to the root, `None` where a node was promoted
+    pub siblings: Vec<Option<Output<D>>>,
+}
+
+impl Archive {
+    /// Builds a Merkle tree over all entries except trailers, e.g.
+    /// `archive.hash_tree::<sha2::Sha256>()` or `archive.hash_tree::<blake3::Hasher>()`
+    /// (with blake3's `traits-preview` feature).
+    ///
+    /// Entries are hashed canonicalized (see [`entry_hash`]), so the tree doesn't depend on
+    /// inode numbers, checksums or the header encoding.
+    pub fn hash_tree<D: Digest>(&self) -> HashTree<D> {
+        let entries: Vec<_> = (0..self.files.len()).filter(|&i| !self.files[i].is_trailer()).collect();
+        let mut levels = vec![entries.iter().map(|&i| entry_hash::<D>(&self.files[i])).collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap().chunks(2).map(|pair| match pair {
+                [left, right] => node_hash::<D>(left, right),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            }).collect();
+            levels.push(level);
+        }
+        HashTree { entries, levels }
+    }
+}
+
+/// Hash of the normalized path, mode, uid, gid, mtime, device numbers and data of an entry.
+pub fn entry_hash<D: Digest>(file: &File) -> Output<D> {
+    let path = normalize_path(&file.filename);
+    let header = &file.header;
+    let mut digest = D::new();
+    digest.update([LEAF]);
+    digest.update((path.len() as u64).to_le_bytes());
+    digest.update(path);
+    for value in [header.mode, header.uid, header.gid, header.mtime, header.rmaj, header.rmin] {
+        digest.update(value.to_le_bytes());
+    }
+    digest.update((file.data.len() as u64).to_le_bytes());
+    digest.update(&file.data);
+    digest.finalize()
+}
+
+fn node_hash<D: Digest>(left: &Output<D>, right: &Output<D>) -> Output<D> {
+    let mut digest = D::new();
+    digest.update([NODE]);
+    digest.update(left);
+    digest.update(right);
+    digest.finalize()
+}
+
+impl<D: Digest> HashTree<D> {
+    /// Root hash, the hash of nothing for archives without entries.
+    pub fn root(&self) -> Output<D> {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => root.clone(),
+            None => D::digest([]),
+        }
+    }
+
+    pub fn leaves(&self) -> &[Output<D>] {
+        &self.levels[0]
+    }
+
+    /// Sibling hashes needed to recompute the root from the leaf, `None` if out of range.
+    pub fn proof(&self, leaf: usize) -> Option<InclusionProof<D>> {
+        if leaf >= self.leaves().len() {
+            return None;
+        }
+        let siblings = self.levels[..self.levels.len() - 1].iter().enumerate()
+            .map(|(depth, level)| level.get((leaf >> depth) ^ 1).cloned())
+            .collect();
+        Some(InclusionProof { leaf, siblings })
+    }
+
+    /// Leaf indices whose entries differ from those of `other`, including leaves only one of the
+    /// trees has. Only subtrees with differing hashes are descended into, so few changes in
+    /// large archives are found quickly, as long as no entries were inserted or removed before them.
+    pub fn changed_leaves(&self, other: &HashTree<D>) -> Vec<usize> {
+        let mut changed = Vec::new();
+        let depth = self.levels.len().max(other.levels.len());
+        if depth > 0 {
+            self.collect_changes(other, depth - 1, 0, &mut changed);
+        }
+        changed
+    }
+
+    fn collect_changes(&self, other: &HashTree<D>, depth: usize, index: usize, changed: &mut Vec<usize>) {
+        let leaves = |tree: &HashTree<D>| {
+            let len = tree.leaves().len();
+            (index << depth).min(len)..((index + 1) << depth).min(len)
+        };
+        let (ours, theirs) = (leaves(self), leaves(other));
+        if ours.is_empty() && theirs.is_empty() {
+            return;
+        }
+        let node = self.levels.get(depth).and_then(|level| level.get(index));
+        if ours == theirs && node.is_some() && node == other.levels.get(depth).and_then(|level| level.get(index)) {
+            return;
+        }
+        if depth == 0 {
+            changed.push(index);
+            return;
+        }
+        self.collect_changes(other, depth - 1, 2 * index, changed);
+        self.collect_changes(other, depth - 1, 2 * index + 1, changed);
+    }
+}
+
+impl<D: Digest> InclusionProof<D> {
+    /// Whether `entry` is at the proven leaf of the tree with the given root.
+    pub fn verify(&self, entry: &File, root: &Output<D>) -> bool {
+        let mut hash = entry_hash::<D>(entry);
+        for (depth, sibling) in self.siblings.iter().enumerate() {
+            hash = match sibling {
+                None => hash,
+                Some(sibling) if (self.leaf >> depth) & 1 == 0 => node_hash::<D>(&hash, sibling),
+                Some(sibling) => node_hash::<D>(sibling, &hash),
+            };
+        }
+        hash == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use sha2::Sha256;
+
+    fn archive(count: usize) -> Archive {
+        let mut archive = Archive::new();
+        for i in 0..count {
+            archive.add_file(File::new(alloc::format!("file{i}"), alloc::format!("{i}").into_bytes()));
+        }
+        archive.add_trailer();
+        archive
+    }
+
+    #[test]
+    fn proofs_verify_for_every_leaf() {
+        for count in 1..=9 {
+            let archive = archive(count);
+            let tree = archive.hash_tree::<Sha256>();
+            assert_eq!(tree.entries, (0..count).collect::<Vec<_>>());
+            let root = tree.root();
+            for leaf in 0..count {
+                let proof = tree.proof(leaf).unwrap();
+                assert!(proof.verify(&archive.files[leaf], &root), "leaf {leaf} of {count}");
+                let other = (leaf + 1) % count;
+                assert_eq!(proof.verify(&archive.files[other], &root), other == leaf);
+            }
+            assert!(tree.proof(count).is_none());
+        }
+    }
+
+    #[test]
+    fn proof_rejects_modified_entry() {
+        let archive = archive(5);
+        let tree = archive.hash_tree::<Sha256>();
+        let mut entry = archive.files[3].clone();
+        entry.header.uid = 1000;
+        assert!(!tree.proof(3).unwrap().verify(&entry, &tree.root()));
+    }
+
+    #[test]
+    fn hash_ignores_inode_numbers() {
+        let mut renumbered = archive(3);
+        for file in &mut renumbered.files {
+            file.header.ino += 100;
+        }
+        assert_eq!(archive(3).hash_tree::<Sha256>().root(), renumbered.hash_tree::<Sha256>().root());
+    }
+
+    #[test]
+    fn changed_leaves_finds_modified_and_added_entries() {
+        let before = archive(7).hash_tree::<Sha256>();
+        assert!(before.changed_leaves(&before).is_empty());
+
+        let mut modified = archive(7);
+        modified.files[2].data = b"changed".to_vec();
+        modified.files[6].header.mode = 0o100755;
+        assert_eq!(before.changed_leaves(&modified.hash_tree()), [2, 6]);
+
+        let mut extended = archive(7);
+        extended.append(File::new("file7".to_string(), Vec::new()));
+        extended.append(File::new("file8".to_string(), Vec::new()));
+        assert_eq!(before.changed_leaves(&extended.hash_tree()), [7, 8]);
+        assert_eq!(extended.hash_tree::<Sha256>().changed_leaves(&before), [7, 8]);
+    }
+}
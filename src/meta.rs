@@ -0,0 +1,43 @@
+use alloc::vec::Vec;
+
+use crate::{normalize_path, Archive, File, FileType};
+
+/// Header fields of an entry without its contents, see [`Archive::metadata`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct EntryMeta<'a> {
+    /// normalized path
+    pub path: &'a [u8],
+    pub file_type: FileType,
+    /// permission bits including setuid, setgid and sticky bit
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+    pub mtime: u32,
+    /// target of symlinks, `None` for other entries
+    pub link_target: Option<&'a [u8]>,
+}
+
+impl File {
+    pub fn meta(&self) -> EntryMeta<'_> {
+        let file_type = self.header.file_type();
+        EntryMeta {
+            path: normalize_path(&self.filename),
+            file_type,
+            mode: self.header.permissions(),
+            uid: self.header.uid,
+            gid: self.header.gid,
+            size: self.header.filesize,
+            mtime: self.header.mtime,
+            link_target: (file_type == FileType::Symlink).then_some(&self.data[..]),
+        }
+    }
+}
+
+impl Archive {
+    /// Returns the metadata of all entries except the trailer in archive order, borrowing
+    /// instead of copying. Only symlink targets are read from the file data.
+    pub fn metadata(&self) -> Vec<EntryMeta<'_>> {
+        self.files.iter().filter(|file| !file.is_trailer()).map(File::meta).collect()
+    }
+}
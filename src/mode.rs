@@ -0,0 +1,284 @@
+//! Bits of [`CpioHeader::mode`], with the values of `<sys/stat.h>`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{CpioHeader, FileType};
+
+/// file type bits
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFSOCK: u32 = 0o140000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFBLK: u32 = 0o060000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFIFO: u32 = 0o010000;
+
+pub const S_ISUID: u32 = 0o4000;
+pub const S_ISGID: u32 = 0o2000;
+/// sticky bit
+pub const S_ISVTX: u32 = 0o1000;
+/// permission bits including setuid, setgid and sticky bit, i.e. everything but the file type
+pub const PERMISSION_BITS: u32 = 0o7777;
+/// execute bits of owner, group and others
+pub const EXECUTE_BITS: u32 = 0o111;
+/// write bit of others
+pub const S_IWOTH: u32 = 0o002;
+
+/// mode of directories created implicitly or by [`File::new`](crate::File::new), `drwxr-xr-x`
+pub const DEFAULT_DIR_MODE: u32 = S_IFDIR | 0o755;
+/// mode of regular files created by [`File::new`](crate::File::new), `-rw-r--r--`
+pub const DEFAULT_FILE_MODE: u32 = S_IFREG | 0o644;
+/// mode of symlinks, `lrwxrwxrwx`, which Linux ignores
+pub const DEFAULT_SYMLINK_MODE: u32 = S_IFLNK | 0o777;
+
+/// `ls -l` file type characters by `S_IFMT` value
+const FILE_TYPES: [(char, u32); 7] = [
+    ('-', S_IFREG), ('d', S_IFDIR), ('l', S_IFLNK), ('c', S_IFCHR), ('b', S_IFBLK), ('p', S_IFIFO), ('s', S_IFSOCK),
+];
+
+/// (shift of the rwx bits, special bit, execute char with special bit, char for special bit without execute)
+const TRIPLETS: [(u32, u32, char, char); 3] = [(6, S_ISUID, 's', 'S'), (3, S_ISGID, 's', 'S'), (0, S_ISVTX, 't', 'T')];
+
+impl FileType {
+    /// Determines the file type from the `S_IFMT` bits of a mode.
+    pub fn from_mode(mode: u32) -> FileType {
+        match mode & S_IFMT {
+            S_IFREG => FileType::Regular,
+            S_IFDIR => FileType::Directory,
+            S_IFLNK => FileType::Symlink,
+            S_IFCHR => FileType::CharDevice,
+            S_IFBLK => FileType::BlockDevice,
+            S_IFIFO => FileType::Fifo,
+            S_IFSOCK => FileType::Socket,
+            _ => FileType::Unknown,
+        }
+    }
+
+    /// The `S_IFMT` bits of the file type, `0` for [`FileType::Unknown`].
+    pub fn mode_bits(self) -> u32 {
+        match self {
+            FileType::Regular => S_IFREG,
+            FileType::Directory => S_IFDIR,
+            FileType::Symlink => S_IFLNK,
+            FileType::CharDevice => S_IFCHR,
+            FileType::BlockDevice => S_IFBLK,
+            FileType::Fifo => S_IFIFO,
+            FileType::Socket => S_IFSOCK,
+            FileType::Unknown => 0,
+        }
+    }
+}
+
+impl CpioHeader {
+    /// The mode without the file type bits.
+    pub fn permissions(&self) -> u32 {
+        self.mode & PERMISSION_BITS
+    }
+
+    /// Replaces the permission bits, keeping the file type.
+    pub fn set_permissions(&mut self, permissions: u32) {
+        self.mode = (self.mode & S_IFMT) | (permissions & PERMISSION_BITS);
+    }
+
+    /// Whether this is a regular file with any execute bit set.
+    pub fn is_executable(&self) -> bool {
+        self.file_type() == FileType::Regular && self.mode & EXECUTE_BITS != 0
+    }
+
+    pub fn is_setuid(&self) -> bool {
+        self.mode & S_ISUID != 0
+    }
+
+    pub fn is_setgid(&self) -> bool {
+        self.mode & S_ISGID != 0
+    }
+
+    pub fn is_sticky(&self) -> bool {
+        self.mode & S_ISVTX != 0
+    }
+
+    /// Whether others may write, the sticky bit of directories doesn't matter.
+    pub fn is_world_writable(&self) -> bool {
+        self.mode & S_IWOTH != 0
+    }
+
+    /// `ls -l` style mode, e.g. `drwxr-xr-x`. Unknown file types are shown as `?`.
+    pub fn mode_string(&self) -> String {
+        let file_type = FILE_TYPES.iter()
+            .find(|&&(_, bits)| bits == self.mode & S_IFMT)
+            .map_or('?', |&(c, _)| c);
+        let mut string = String::from(file_type);
+        for (shift, special, set, unset) in TRIPLETS {
+            let bits = self.mode >> shift;
+            string.push(if bits & 4 != 0 { 'r' } else { '-' });
+            string.push(if bits & 2 != 0 { 'w' } else { '-' });
+            string.push(match (bits & 1 != 0, self.mode & special != 0) {
+                (true, true) => set,
+                (false, true) => unset,
+                (true, false) => 'x',
+                (false, false) => '-',
+            });
+        }
+        string
+    }
+}
+
+/// Parses an `ls -l` style mode like `drwxr-xr-x` back into mode bits, the reverse of
+/// [`CpioHeader::mode_string`].
+///
+/// The file type character is optional: `rwxr-xr-x` results in only the permission bits.
+pub fn parse_mode_string(mode: &str) -> Option<u32> {
+    let mode = mode.as_bytes();
+    let (mut bits, permissions) = match mode.len() {
+        9 => (0, mode),
+        10 => {
+            let &(_, bits) = FILE_TYPES.iter().find(|&&(c, _)| c as u8 == mode[0])?;
+            (bits, &mode[1..])
+        }
+        _ => return None,
+    };
+    for ((shift, special, set, unset), triplet) in TRIPLETS.into_iter().zip(permissions.chunks(3)) {
+        match triplet[0] {
+            b'r' => bits |= 4 << shift,
+            b'-' => (),
+            _ => return None,
+        }
+        match triplet[1] {
+            b'w' => bits |= 2 << shift,
+            b'-' => (),
+            _ => return None,
+        }
+        match triplet[2] {
+            b'x' => bits |= 1 << shift,
+            b'-' => (),
+            c if c == set as u8 => bits |= special | 1 << shift,
+            c if c == unset as u8 => bits |= special,
+            _ => return None,
+        }
+    }
+    Some(bits)
+}
+
+/// Parsed symbolic mode like `u+x`, `go-w` or `a=rX,u+s`, as accepted by `chmod`.
+/// See [`parse_symbolic_mode`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SymbolicMode {
+    actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Action {
+    /// permission bits affected by the clause
+    who: u32,
+    /// one of `+`, `-` and `=`
+    op: u8,
+    /// bits of `rwxst`
+    bits: u32,
+    /// `X`: execute only for directories and entries with an execute bit
+    conditional_execute: bool,
+    /// shift of the permission triplet copied by `u`, `g` or `o`
+    copy_from: Option<u32>,
+}
+
+/// Parses a comma separated list of `[ugoa]*([-+=]([rwxXst]*|[ugo]))+` clauses.
+/// Without `ugoa`, all permissions are affected (no umask is applied).
+pub fn parse_symbolic_mode(mode: &str) -> Option<SymbolicMode> {
+    let mut actions = Vec::new();
+    for clause in mode.split(',') {
+        let mut chars = clause.bytes().peekable();
+        let mut who = 0;
+        while let Some(&c) = chars.peek() {
+            who |= match c {
+                b'u' => 0o4700,
+                b'g' => 0o2070,
+                b'o' => 0o1007,
+                b'a' => PERMISSION_BITS,
+                _ => break,
+            };
+            chars.next();
+        }
+        if who == 0 {
+            who = PERMISSION_BITS;
+        }
+        let mut has_action = false;
+        while let Some(op) = chars.next_if(|&c| matches!(c, b'+' | b'-' | b'=')) {
+            let mut action = Action { who, op, bits: 0, conditional_execute: false, copy_from: None };
+            while let Some(c) = chars.next_if(|&c| !matches!(c, b'+' | b'-' | b'=')) {
+                match c {
+                    b'r' => action.bits |= 0o444,
+                    b'w' => action.bits |= 0o222,
+                    b'x' => action.bits |= EXECUTE_BITS,
+                    b'X' => action.conditional_execute = true,
+                    b's' => action.bits |= S_ISUID | S_ISGID,
+                    b't' => action.bits |= S_ISVTX,
+                    b'u' if action.bits == 0 && action.copy_from.is_none() => action.copy_from = Some(6),
+                    b'g' if action.bits == 0 && action.copy_from.is_none() => action.copy_from = Some(3),
+                    b'o' if action.bits == 0 && action.copy_from.is_none() => action.copy_from = Some(0),
+                    _ => return None,
+                }
+                if action.copy_from.is_some() && (action.bits != 0 || action.conditional_execute) {
+                    return None;
+                }
+            }
+            actions.push(action);
+            has_action = true;
+        }
+        if !has_action || chars.next().is_some() {
+            return None;
+        }
+    }
+    Some(SymbolicMode { actions })
+}
+
+impl SymbolicMode {
+    /// Applies the mode to all permission bits of `mode`, keeping the file type.
+    pub fn apply(&self, mut mode: u32) -> u32 {
+        for action in &self.actions {
+            let mut bits = action.bits;
+            if action.conditional_execute && (mode & S_IFMT == S_IFDIR || mode & EXECUTE_BITS != 0) {
+                bits |= EXECUTE_BITS;
+            }
+            if let Some(shift) = action.copy_from {
+                // multiplying by 0o111 repeats the rwx triplet for user, group and others
+                bits |= ((mode >> shift) & 0o7) * 0o111;
+            }
+            bits &= action.who;
+            mode = match action.op {
+                b'+' => mode | bits,
+                b'-' => mode & !bits,
+                _ => (mode & !action.who) | bits,
+            };
+        }
+        mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbolic_mode_apply() {
+        let apply = |mode: &str, to: u32| parse_symbolic_mode(mode).unwrap().apply(to);
+        assert_eq!(apply("u+x", 0o100644), 0o100744);
+        assert_eq!(apply("go-w", 0o100666), 0o100644);
+        assert_eq!(apply("a-x,u+x", 0o100755), 0o100744);
+        assert_eq!(apply("=", 0o120777), 0o120000);
+        assert_eq!(apply("+t", 0o040777), 0o041777);
+        assert_eq!(apply("u+s,g=u", 0o100750), 0o104770);
+        assert_eq!(apply("o=g", 0o100750), 0o100755);
+        // `X` only adds execute permissions to directories and executables
+        assert_eq!(apply("a=rX", 0o100600), 0o100444);
+        assert_eq!(apply("a=rX", 0o100700), 0o100555);
+        assert_eq!(apply("a=rX", 0o040700), 0o040555);
+    }
+
+    #[test]
+    fn invalid_symbolic_modes() {
+        for mode in ["", "644", "u", "u+q", "u+ru", "u+gr", "u+r,"] {
+            assert_eq!(parse_symbolic_mode(mode), None, "{mode}");
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{FileType, Initramfs};
+
+/// Kernel modules bundled for one kernel version, see [`Initramfs::module_inventory`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModuleInventory<'a> {
+    pub kernel_version: &'a [u8],
+    /// module name (with `-` replaced by `_`) -> normalized path
+    pub modules: BTreeMap<String, &'a [u8]>,
+    /// names of modules built into the kernel according to `modules.builtin`
+    pub builtin: BTreeSet<String>,
+    pub has_modules_dep: bool,
+    /// (module, dependency) paths relative to the module directory, from `modules.dep`
+    pub missing_dependencies: Vec<(&'a [u8], &'a [u8])>,
+}
+
+impl ModuleInventory<'_> {
+    /// Whether a module is available, either as file or built into the kernel.
+    pub fn provides(&self, name: &str) -> bool {
+        let name = module_name(name.as_bytes());
+        self.modules.contains_key(&name) || self.builtin.contains(&name)
+    }
+}
+
+/// Modules needed for mounting the root filesystem, any one of `alternatives` suffices.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModuleRequirement {
+    pub reason: &'static str,
+    pub alternatives: Vec<String>,
+}
+
+impl Initramfs {
+    /// Collects the modules below `lib/modules/<version>/` (or `usr/lib/modules/<version>/`)
+    /// in the effective tree, one inventory per kernel version.
+    pub fn module_inventory(&self) -> Vec<ModuleInventory<'_>> {
+        let view = self.effective_view();
+        let mut inventories: BTreeMap<&[u8], ModuleInventory<'_>> = BTreeMap::new();
+        let mut modules_deps = BTreeMap::new();
+        for (path, file) in view.iter() {
+            let Some(rest) = path.strip_prefix(b"lib/modules/").or_else(|| path.strip_prefix(b"usr/lib/modules/")) else { continue };
+            let Some(slash) = rest.iter().position(|&b| b == b'/') else { continue };
+            let (kernel_version, relative) = (&rest[..slash], &rest[slash + 1..]);
+            let inventory = inventories.entry(kernel_version).or_insert_with(|| ModuleInventory {
+                kernel_version,
+                modules: BTreeMap::new(),
+                builtin: BTreeSet::new(),
+                has_modules_dep: false,
+                missing_dependencies: Vec::new(),
+            });
+            if file.header.file_type() == FileType::Directory {
+                continue;
+            }
+            match relative {
+                b"modules.builtin" => inventory.builtin.extend(file.data.split(|&b| b == b'\n')
+                    .filter(|line| !line.is_empty())
+                    .map(|line| module_name(basename(line)))),
+                b"modules.dep" => {
+                    inventory.has_modules_dep = true;
+                    modules_deps.insert(kernel_version, file);
+                }
+                _ if is_module(relative) => {
+                    inventory.modules.insert(module_name(basename(relative)), path);
+                }
+                _ => (),
+            }
+        }
+
+        for (kernel_version, modules_dep) in modules_deps {
+            let inventory = inventories.get_mut(kernel_version).unwrap();
+            for line in modules_dep.data.split(|&b| b == b'\n') {
+                let Some(colon) = line.iter().position(|&b| b == b':') else { continue };
+                let module = &line[..colon];
+                if !inventory.modules.contains_key(&module_name(basename(module))) {
+                    continue;
+                }
+                for dependency in line[colon + 1..].split(|&b| b == b' ').filter(|d| !d.is_empty()) {
+                    if !inventory.modules.contains_key(&module_name(basename(dependency))) {
+                        inventory.missing_dependencies.push((module, dependency));
+                    }
+                }
+            }
+        }
+        inventories.into_values().collect()
+    }
+
+    /// Derives the modules needed to mount `root` (the value of the `root=` kernel parameter)
+    /// with the given `rootfstype=`, or any common filesystem if unknown.
+    ///
+    /// `dm_crypt` is additionally required if the root is a LUKS mapping or the image contains
+    /// `/etc/crypttab`.
+    pub fn root_requirements(&self, root: &str, rootfstype: Option<&str>) -> Vec<ModuleRequirement> {
+        let requirement = |reason, alternatives: &[&str]| ModuleRequirement { reason, alternatives: alternatives.iter().map(|name| name.to_string()).collect() };
+        let mut requirements = Vec::new();
+        let device = root.strip_prefix("/dev/").unwrap_or("");
+        let block_drivers = [
+            ("sd", "SCSI / SATA disk", "sd_mod"),
+            ("nvme", "NVMe disk", "nvme"),
+            ("vd", "virtio disk", "virtio_blk"),
+            ("xvd", "Xen disk", "xen_blkfront"),
+            ("mmcblk", "MMC / SD card", "mmc_block"),
+            ("md", "software RAID", "md_mod"),
+            ("mapper/", "device mapper", "dm_mod"),
+            ("dm-", "device mapper", "dm_mod"),
+        ];
+        if device == "nfs" {
+            requirements.push(requirement("NFS root", &["nfs"]));
+            return requirements;
+        }
+        if let Some(&(_, reason, module)) = block_drivers.iter().find(|(prefix, _, _)| device.starts_with(prefix)) {
+            requirements.push(requirement(reason, &[module]));
+        }
+        let is_luks = device.starts_with("mapper/luks") || device.starts_with("mapper/crypt");
+        if is_luks || self.effective_view().get(b"etc/crypttab").is_some() {
+            requirements.push(requirement("encrypted root", &["dm_crypt"]));
+        }
+        requirements.push(match rootfstype {
+            Some("ext2" | "ext3" | "ext4") => requirement("root filesystem", &["ext4", "ext3", "ext2"]),
+            Some(fstype) => ModuleRequirement { reason: "root filesystem", alternatives: vec![fstype.to_string()] },
+            None => requirement("root filesystem", &["ext4", "xfs", "btrfs", "f2fs"]),
+        });
+        requirements
+    }
+}
+
+fn is_module(path: &[u8]) -> bool {
+    [&b".ko"[..], b".ko.xz", b".ko.zst", b".ko.gz"].iter().any(|extension| path.ends_with(extension))
+}
+
+fn basename(path: &[u8]) -> &[u8] {
+    path.rsplit(|&b| b == b'/').next().unwrap()
+}
+
+/// Strips the extension of a module filename and replaces `-` by `_` like `modprobe`.
+fn module_name(filename: &[u8]) -> String {
+    let name = filename.iter().position(|&b| b == b'.').map_or(filename, |dot| &filename[..dot]);
+    String::from_utf8_lossy(name).replace('-', "_")
+}
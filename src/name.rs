@@ -0,0 +1,59 @@
+use core::fmt::{self, Display, Formatter};
+use core::str::Utf8Error;
+
+use crate::File;
+
+/// Error of [`File::path_str`] for filenames that aren't valid UTF-8.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct NonUtf8Name(pub Utf8Error);
+
+impl Display for NonUtf8Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "filename is not valid UTF-8: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NonUtf8Name {}
+
+impl File {
+    /// The filename as string, without any normalization.
+    pub fn path_str(&self) -> Result<&str, NonUtf8Name> {
+        core::str::from_utf8(&self.filename).map_err(NonUtf8Name)
+    }
+
+    /// Renames the entry, updating `namesize`.
+    pub fn set_path_str(&mut self, path: &str) {
+        self.set_filename(path.as_bytes().into());
+    }
+
+    /// The filename as host path. On unix every filename can be represented, other platforms
+    /// require UTF-8.
+    #[cfg(feature = "std")]
+    pub fn path(&self) -> Result<&std::path::Path, NonUtf8Name> {
+        #[cfg(unix)] {
+            use std::os::unix::ffi::OsStrExt;
+            Ok(std::path::Path::new(std::ffi::OsStr::from_bytes(&self.filename)))
+        }
+        #[cfg(not(unix))] {
+            self.path_str().map(std::path::Path::new)
+        }
+    }
+
+    /// Renames the entry to a host path, updating `namesize`. On unix the path is taken over
+    /// byte by byte, other platforms require it to be valid UTF-8.
+    #[cfg(feature = "std")]
+    pub fn set_path(&mut self, path: &std::path::Path) -> Result<(), NonUtf8Name> {
+        #[cfg(unix)] {
+            use std::os::unix::ffi::OsStrExt;
+            self.set_filename(path.as_os_str().as_bytes().into());
+            Ok(())
+        }
+        #[cfg(not(unix))] {
+            match path.to_str() {
+                Some(path) => Ok(self.set_path_str(path)),
+                None => Err(NonUtf8Name(core::str::from_utf8(path.as_os_str().as_encoded_bytes()).unwrap_err())),
+            }
+        }
+    }
+}
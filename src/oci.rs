@@ -0,0 +1,594 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::mode::PERMISSION_BITS;
+use crate::whiteout::{apply_layer, tree_to_archive, Tree};
+use crate::{detect_compression, normalize_path, Archive, Codec, File, FileType, Initramfs};
+
+/// Options for [`Archive::from_oci`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct OciOptions {
+    /// `os/architecture[/variant]` (e.g. `linux/arm64/v8`) selecting the image of a multi-platform
+    /// index, required if it contains more than one
+    pub platform: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum OciError {
+    Io(io::Error),
+    /// (description) missing or malformed `index.json`, `manifest.json` or image manifest
+    InvalidManifest(&'static str),
+    /// (number of images) the index contains multiple images and no platform was given
+    AmbiguousImage(usize),
+    /// (platform) the index doesn't contain an image for the platform
+    NoMatchingPlatform(String),
+    /// (digest or path) referenced blob not found in the image
+    MissingBlob(String),
+    /// (digest) blob content doesn't match its digest
+    DigestMismatch(String),
+    /// (layer index) layer isn't a (compressed) tar archive or uses an unsupported compression
+    InvalidLayer(usize),
+    /// (path) file larger than 4 GiB, which cpio can't store
+    FileTooLarge(Vec<u8>),
+}
+
+impl Display for OciError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OciError::Io(e) => write!(f, "I/O error: {e}"),
+            OciError::InvalidManifest(description) => write!(f, "invalid image: {description}"),
+            OciError::AmbiguousImage(count) => write!(f, "image index contains {count} images, select one by platform"),
+            OciError::NoMatchingPlatform(platform) => write!(f, "image index doesn't contain an image for {platform}"),
+            OciError::MissingBlob(blob) => write!(f, "blob {blob} not found"),
+            OciError::DigestMismatch(digest) => write!(f, "content of blob {digest} doesn't match its digest"),
+            OciError::InvalidLayer(index) => write!(f, "layer {index} isn't a tar archive or uses an unsupported compression"),
+            OciError::FileTooLarge(path) => write!(f, "{} is larger than 4 GiB", String::from_utf8_lossy(path)),
+        }
+    }
+}
+
+impl std::error::Error for OciError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OciError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for OciError {
+    fn from(e: io::Error) -> OciError {
+        OciError::Io(e)
+    }
+}
+
+impl Archive {
+    /// Creates an archive from the root filesystem of a container image, given as an OCI image
+    /// layout directory or a tarball of one, or as a `docker save` tarball.
+    ///
+    /// The layers are applied in order, with whiteouts (`.wh.<name>` and `.wh..wh..opq`) removing
+    /// paths of lower layers. Like [`Archive::from_dir`], hard links are stored as separate
+    /// files, entries are sorted by path and no trailer is added.
+    pub fn from_oci(path: impl AsRef<Path>, options: &OciOptions) -> Result<Archive, OciError> {
+        let (_, tree) = load_layers(path.as_ref(), options)?;
+        Ok(tree_to_archive(tree))
+    }
+}
+
+impl Initramfs {
+    /// Like [`Archive::from_oci`], but keeps each layer as an archive, with its whiteouts as
+    /// markers (see [`File::whiteout`]), so [`Initramfs::flatten`] gives the same result.
+    ///
+    /// Hard links are copies of their target, which may be in a lower layer.
+    pub fn from_oci_layers(path: impl AsRef<Path>, options: &OciOptions) -> Result<Initramfs, OciError> {
+        let (archives, _) = load_layers(path.as_ref(), options)?;
+        let mut initramfs = Initramfs::new();
+        for archive in archives {
+            initramfs.add_archive(archive);
+        }
+        Ok(initramfs)
+    }
+}
+
+/// Reads the layers of an image, returning them as archives together with the flattened tree.
+fn load_layers(path: &Path, options: &OciOptions) -> Result<(Vec<Archive>, Tree), OciError> {
+    let source = if path.is_dir() {
+        Source::Dir(path.to_path_buf())
+    } else {
+        let tarball = std::fs::read(path)?;
+        let entries = TarReader::new(&tarball).collect::<Option<Vec<_>>>().ok_or(OciError::InvalidManifest("not a tar archive"))?;
+        let blobs = entries.into_iter()
+            .filter(|entry| entry.file_type == FileType::Regular)
+            .map(|entry| (normalize_path(&entry.path).to_vec(), entry.data.to_vec()))
+            .collect();
+        Source::Tar(blobs)
+    };
+
+    let layers = match source.read("index.json")? {
+        Some(index) => {
+            let manifest = select_manifest(&source, &parse_json(&index)?, options)?;
+            let layers = manifest.get("layers").and_then(Value::as_array).ok_or(OciError::InvalidManifest("image manifest without layers"))?;
+            layers.iter()
+                .map(|layer| source.blob(layer.get("digest").and_then(Value::as_str).ok_or(OciError::InvalidManifest("layer without digest"))?))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        None => {
+            let manifest = source.read("manifest.json")?.ok_or(OciError::InvalidManifest("neither index.json nor manifest.json found"))?;
+            let images = parse_json(&manifest)?;
+            let [image] = images.as_array().map(Vec::as_slice).unwrap_or_default() else {
+                return Err(match images.as_array() {
+                    Some(images) if images.len() > 1 => OciError::AmbiguousImage(images.len()),
+                    _ => OciError::InvalidManifest("manifest.json doesn't list an image"),
+                });
+            };
+            let layers = image.get("Layers").and_then(Value::as_array).ok_or(OciError::InvalidManifest("image without layers"))?;
+            layers.iter()
+                .map(|layer| {
+                    let path = layer.as_str().ok_or(OciError::InvalidManifest("invalid layer path"))?;
+                    source.read(path)?.ok_or_else(|| OciError::MissingBlob(path.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let mut tree = Tree::new();
+    let mut archives = Vec::new();
+    for (i, layer) in layers.iter().enumerate() {
+        log::debug!("applying layer {i}");
+        let decompressed;
+        let layer = match detect_compression(layer) {
+            Some(Codec::Uncompressed) | None => &layer[..],
+            Some(_) => {
+                decompressed = crate::decompress(layer).map_err(|_| OciError::InvalidLayer(i))?.1;
+                &decompressed[..]
+            }
+        };
+        let entries = TarReader::new(layer).collect::<Option<Vec<_>>>().ok_or(OciError::InvalidLayer(i))?;
+        let archive = layer_archive(&entries, &tree)?;
+        apply_layer(&mut tree, &archive.files);
+        archives.push(archive);
+    }
+    Ok((archives, tree))
+}
+
+enum Source {
+    Dir(PathBuf),
+    /// regular files of the tarball by normalized path
+    Tar(BTreeMap<Vec<u8>, Vec<u8>>),
+}
+
+impl Source {
+    /// Reads the file at `path` relative to the image root, which must not contain `..`.
+    fn read(&self, path: &str) -> Result<Option<Vec<u8>>, OciError> {
+        let components: Vec<_> = path.split('/').filter(|component| !component.is_empty() && *component != ".").collect();
+        if components.contains(&"..") {
+            return Err(OciError::InvalidManifest("path outside of the image"));
+        }
+        let path = components.join("/");
+        match self {
+            Source::Dir(dir) => match std::fs::read(dir.join(path)) {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            Source::Tar(blobs) => Ok(blobs.get(path.as_bytes()).cloned()),
+        }
+    }
+
+    /// Reads the blob `<algorithm>:<hex>`, verifying `sha256` digests.
+    fn blob(&self, digest: &str) -> Result<Vec<u8>, OciError> {
+        let (algorithm, hex) = digest.split_once(':')
+            .filter(|(algorithm, hex)| !algorithm.contains(['/', '.']) && !hex.contains(['/', '.'])).ok_or(OciError::InvalidManifest("invalid digest"))?;
+        let blob = self.read(&alloc::format!("blobs/{algorithm}/{hex}"))?.ok_or_else(|| OciError::MissingBlob(digest.to_string()))?;
+        if algorithm == "sha256" {
+            use sha2::Digest;
+            if !hex.eq_ignore_ascii_case(&hex::encode(sha2::Sha256::digest(&blob))) {
+                return Err(OciError::DigestMismatch(digest.to_string()));
+            }
+        }
+        Ok(blob)
+    }
+}
+
+fn parse_json(data: &[u8]) -> Result<Value, OciError> {
+    serde_json::from_slice(data).map_err(|_| OciError::InvalidManifest("invalid JSON"))
+}
+
+/// Follows (nested) image indexes to the image manifest for the platform.
+fn select_manifest(source: &Source, index: &Value, options: &OciOptions) -> Result<Value, OciError> {
+    let manifests = index.get("manifests").and_then(Value::as_array).ok_or(OciError::InvalidManifest("index without manifests"))?;
+    // attestations and signatures of BuildKit and cosign are stored with platform `unknown/unknown`
+    let images: Vec<_> = manifests.iter()
+        .filter(|manifest| manifest.pointer("/platform/os").and_then(Value::as_str) != Some("unknown"))
+        .collect();
+    let image = match &options.platform {
+        Some(platform) => {
+            let matching: Vec<_> = images.iter().filter(|manifest| matches_platform(manifest, platform)).collect();
+            match matching[..] {
+                [] => return Err(OciError::NoMatchingPlatform(platform.clone())),
+                [image, ..] => *image,
+            }
+        }
+        None => match images[..] {
+            [image] => image,
+            [] => return Err(OciError::InvalidManifest("index without images")),
+            _ => return Err(OciError::AmbiguousImage(images.len())),
+        },
+    };
+    let digest = image.get("digest").and_then(Value::as_str).ok_or(OciError::InvalidManifest("manifest without digest"))?;
+    let manifest = parse_json(&source.blob(digest)?)?;
+    if manifest.get("manifests").is_some() {
+        select_manifest(source, &manifest, options)
+    } else {
+        Ok(manifest)
+    }
+}
+
+/// Entries without platform (which can be nested indexes) match every platform.
+fn matches_platform(manifest: &Value, platform: &str) -> bool {
+    let Some(actual) = manifest.get("platform") else { return true };
+    let mut wanted = platform.split('/');
+    ["os", "architecture", "variant"].iter().all(|key| match wanted.next() {
+        Some(value) => actual.get(key).and_then(Value::as_str) == Some(value),
+        None => true,
+    })
+}
+
+/// Converts a layer to an archive of its whiteout markers (see [`File::whiteout`]) followed by
+/// its other entries in tar order. Hard links are resolved to copies of their target in this layer or in
+/// the flattened lower layers.
+fn layer_archive(entries: &[TarEntry<'_>], lower: &Tree) -> Result<Archive, OciError> {
+    let mut archive = Archive::new();
+    let mut whiteouts = Vec::new();
+    for entry in entries {
+        let mut marker = File::new(String::new(), Vec::new());
+        marker.set_filename(normalize_path(&entry.path).to_vec());
+        if let Some(whiteout) = marker.whiteout() {
+            whiteouts.push(whiteout);
+            archive.add_file(marker);
+        }
+    }
+    for entry in entries {
+        let path = normalize_path(&entry.path);
+        let name = path.rsplit(|&b| b == b'/').next().unwrap_or_default();
+        if path.is_empty() || name.starts_with(b".wh.") {
+            continue;
+        }
+        let mut file = match entry.link_kind {
+            // hard links refer to a path of this or a lower layer
+            Some(LinkKind::Hard) => {
+                let target = normalize_path(&entry.link);
+                let own = archive.files.iter().rev().find(|file| file.filename == target && file.whiteout().is_none());
+                let lower = || lower.get(target).filter(|_| !whiteouts.iter().any(|whiteout| whiteout.hides(target))).map(|entry| &entry[0]);
+                match own.or_else(lower) {
+                    Some(target) => target.clone(),
+                    None => {
+                        log::warn!("skipping hard link {} to missing {}", String::from_utf8_lossy(path), String::from_utf8_lossy(&entry.link));
+                        continue;
+                    }
+                }
+            }
+            Some(LinkKind::Symbolic) => File::new_symlink(String::new(), entry.link.clone()),
+            None => {
+                let mut file = File::new(String::new(), entry.data.to_vec());
+                file.header.filesize = u32::try_from(entry.data.len()).map_err(|_| OciError::FileTooLarge(path.to_vec()))?;
+                file.header.mode = entry.file_type.mode_bits() | (entry.mode & PERMISSION_BITS);
+                file.header.rmaj = entry.rdev.0;
+                file.header.rmin = entry.rdev.1;
+                file
+            }
+        };
+        if entry.link_kind != Some(LinkKind::Hard) {
+            file.header.uid = entry.uid;
+            file.header.gid = entry.gid;
+            file.header.mtime = entry.mtime;
+            file.header.nlink = if entry.file_type == FileType::Directory { 2 } else { 1 };
+        }
+        file.set_filename(path.to_vec());
+        archive.add_file(file);
+    }
+    Ok(archive)
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum LinkKind {
+    Hard,
+    Symbolic,
+}
+
+#[derive(Debug, Clone)]
+struct TarEntry<'a> {
+    path: Vec<u8>,
+    file_type: FileType,
+    link_kind: Option<LinkKind>,
+    link: Vec<u8>,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u32,
+    /// (major, minor)
+    rdev: (u32, u32),
+    data: &'a [u8],
+}
+
+/// Reads ustar archives with GNU long names and pax path, link and size records, yielding
+/// `None` and stopping at malformed headers.
+struct TarReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    done: bool,
+}
+
+impl<'a> TarReader<'a> {
+    fn new(data: &'a [u8]) -> TarReader<'a> {
+        TarReader { data, pos: 0, done: false }
+    }
+
+    /// `Some(None)` at the end of the archive, `None` for malformed headers.
+    fn next_entry(&mut self) -> Option<Option<TarEntry<'a>>> {
+        let (mut long_path, mut long_link, mut pax_size) = (None, None, None);
+        let mut pax: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        loop {
+            let Some(header) = self.data.get(self.pos..self.pos + 512) else {
+                // archives without end-of-archive blocks are common enough
+                return (self.pos >= self.data.len()).then_some(None);
+            };
+            if header.iter().all(|&b| b == 0) {
+                return Some(None);
+            }
+            let stored_checksum = octal(&header[148..156])?;
+            let checksum: u64 = header.iter().enumerate()
+                .map(|(i, &b)| if (148..156).contains(&i) { u64::from(b' ') } else { u64::from(b) })
+                .sum();
+            if stored_checksum != checksum {
+                return None;
+            }
+            let size = match pax_size.take() {
+                Some(size) => size,
+                None => octal(&header[124..136])?,
+            };
+            let start = self.pos + 512;
+            let data = self.data.get(start..start.checked_add(usize::try_from(size).ok()?)?)?;
+            self.pos = start + data.len().next_multiple_of(512);
+            let type_flag = header[156];
+            match type_flag {
+                b'L' => long_path = Some(until_nul(data).to_vec()),
+                b'K' => long_link = Some(until_nul(data).to_vec()),
+                b'x' => {
+                    parse_pax(data, &mut pax)?;
+                    pax_size = pax.get(&b"size"[..]).and_then(|size| core::str::from_utf8(size).ok()?.parse().ok());
+                }
+                // global extended headers and other extensions only carry metadata
+                b'g' | b'A'..=b'Z' => (),
+                _ => {
+                    let mut path = until_nul(&header[0..100]).to_vec();
+                    if &header[257..262] == b"ustar" && header[345] != 0 {
+                        let mut prefixed = until_nul(&header[345..500]).to_vec();
+                        prefixed.push(b'/');
+                        prefixed.extend_from_slice(&path);
+                        path = prefixed;
+                    }
+                    let pax_number = |key: &[u8]| pax.get(key).and_then(|value| {
+                        // mtimes can have a fractional part
+                        let value = value.split(|&b| b == b'.').next()?;
+                        core::str::from_utf8(value).ok()?.parse::<u64>().ok()
+                    });
+                    let (file_type, link_kind) = match type_flag {
+                        b'1' => (FileType::Regular, Some(LinkKind::Hard)),
+                        b'2' => (FileType::Symlink, Some(LinkKind::Symbolic)),
+                        b'3' => (FileType::CharDevice, None),
+                        b'4' => (FileType::BlockDevice, None),
+                        b'5' => (FileType::Directory, None),
+                        b'6' => (FileType::Fifo, None),
+                        _ => (FileType::Regular, None),
+                    };
+                    let is_device = matches!(file_type, FileType::CharDevice | FileType::BlockDevice);
+                    let uid = pax_number(b"uid").or_else(|| octal(&header[108..116]))? as u32;
+                    let gid = pax_number(b"gid").or_else(|| octal(&header[116..124]))? as u32;
+                    let mtime = pax_number(b"mtime").or_else(|| octal(&header[136..148]))?.min(u64::from(u32::MAX)) as u32;
+                    return Some(Some(TarEntry {
+                        path: pax.remove(&b"path"[..]).or(long_path).unwrap_or(path),
+                        file_type,
+                        link_kind,
+                        link: pax.remove(&b"linkpath"[..]).or(long_link).unwrap_or_else(|| until_nul(&header[157..257]).to_vec()),
+                        mode: octal(&header[100..108])? as u32,
+                        uid,
+                        gid,
+                        mtime,
+                        rdev: if is_device { (octal(&header[329..337])? as u32, octal(&header[337..345])? as u32) } else { (0, 0) },
+                        // hard links and special files don't have data
+                        data: if link_kind.is_none() && matches!(type_flag, b'0' | b'7' | 0) { data } else { &[] },
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for TarReader<'a> {
+    type Item = Option<TarEntry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let entry = self.next_entry();
+        self.done = !matches!(entry, Some(Some(_)));
+        // ends iteration at the end of the archive, or yields `None` for malformed headers
+        entry.map_or(Some(None), |entry| entry.map(Some))
+    }
+}
+
+fn until_nul(field: &[u8]) -> &[u8] {
+    &field[..field.iter().position(|&b| b == 0).unwrap_or(field.len())]
+}
+
+/// Parses a numeric field, either octal terminated by NUL or space, or base-256 with the high
+/// bit of the first byte set.
+fn octal(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|&b| b & 0x80 != 0) {
+        return field[1..].iter().try_fold(u64::from(field[0] & 0x7f), |n, &b| n.checked_mul(256).map(|n| n | u64::from(b)));
+    }
+    let digits = until_nul(field).trim_ascii();
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(core::str::from_utf8(digits).ok()?, 8).ok()
+}
+
+/// Parses `<length> <key>=<value>\n` records.
+fn parse_pax(mut data: &[u8], records: &mut BTreeMap<Vec<u8>, Vec<u8>>) -> Option<()> {
+    while !data.is_empty() {
+        let space = data.iter().position(|&b| b == b' ')?;
+        let len: usize = core::str::from_utf8(&data[..space]).ok()?.parse().ok()?;
+        let record = data.get(space + 1..len)?.strip_suffix(b"\n")?;
+        let equals = record.iter().position(|&b| b == b'=')?;
+        records.insert(record[..equals].to_vec(), record[equals + 1..].to_vec());
+        data = &data[len..];
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Digest;
+
+    /// Tar archive of (path, type flag, data or link target).
+    fn tar(entries: &[(&str, u8, &[u8])]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        for &(path, type_flag, content) in entries {
+            let (data, link) = match type_flag {
+                b'1' | b'2' => (&[][..], content),
+                _ => (content, &[][..]),
+            };
+            let mut header = [0; 512];
+            header[..path.len()].copy_from_slice(path.as_bytes());
+            let mode = if type_flag == b'5' { "0000755\0" } else { "0000644\0" };
+            header[100..108].copy_from_slice(mode.as_bytes());
+            header[108..116].copy_from_slice(b"0001750\0");
+            header[116..124].copy_from_slice(b"0001750\0");
+            header[124..136].copy_from_slice(alloc::format!("{:011o}\0", data.len()).as_bytes());
+            header[136..148].copy_from_slice(b"14500000000\0");
+            header[148..156].fill(b' ');
+            header[156] = type_flag;
+            header[157..157 + link.len()].copy_from_slice(link);
+            header[257..263].copy_from_slice(b"ustar\0");
+            let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+            header[148..156].copy_from_slice(alloc::format!("{checksum:06o}\0 ").as_bytes());
+            tar.extend_from_slice(&header);
+            tar.extend_from_slice(data);
+            tar.resize(tar.len().next_multiple_of(512), 0);
+        }
+        tar.resize(tar.len() + 1024, 0);
+        tar
+    }
+
+    fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(alloc::format!("initramfs-{}-{name}", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn names(archive: &Archive) -> Vec<&[u8]> {
+        archive.files.iter().map(|file| &file.filename[..]).collect()
+    }
+
+    #[test]
+    fn tar_reader_parses_entries() {
+        let long = "a/".repeat(60) + "file";
+        let pax = alloc::format!("{} path={long}\n", 6 + long.len() + 4);
+        let tar = tar(&[
+            ("etc/", b'5', b""),
+            ("pax", b'x', pax.as_bytes()),
+            ("short", b'0', b"long"),
+            ("etc/link", b'2', b"../target"),
+        ]);
+        let entries: Vec<_> = TarReader::new(&tar).collect::<Option<_>>().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!((&entries[0].path[..], entries[0].file_type, entries[0].mode), (&b"etc/"[..], FileType::Directory, 0o755));
+        assert_eq!((&entries[1].path[..], entries[1].data), (long.as_bytes(), &b"long"[..]));
+        assert_eq!((entries[1].uid, entries[1].gid, entries[1].mtime), (1000, 1000, 0o14500000000));
+        assert_eq!((entries[2].link_kind, &entries[2].link[..]), (Some(LinkKind::Symbolic), &b"../target"[..]));
+
+        let mut corrupt = tar.clone();
+        corrupt[0] ^= 1;
+        assert!(TarReader::new(&corrupt).next().unwrap().is_none());
+    }
+
+    #[test]
+    fn from_oci_applies_layers() {
+        let lower = tar(&[
+            ("etc/", b'5', b""),
+            ("etc/passwd", b'0', b"root:x:0:0::/root:/bin/sh\n"),
+            ("etc/old", b'0', b"old"),
+            ("bin/sh", b'2', b"busybox"),
+        ]);
+        let upper = tar(&[
+            ("etc/.wh.old", b'0', b""),
+            ("etc/hosts", b'1', b"etc/passwd"),
+            ("bin/.wh..wh..opq", b'0', b""),
+        ]);
+        let image = tar(&[
+            ("manifest.json", b'0', br#"[{"Layers": ["lower.tar", "upper.tar"]}]"#),
+            ("lower.tar", b'0', &lower),
+            ("upper.tar", b'0', &upper),
+        ]);
+        let path = temp_file("docker.tar", &image);
+        let archive = Archive::from_oci(&path, &OciOptions::default()).unwrap();
+        assert_eq!(names(&archive), [&b"etc"[..], b"etc/hosts", b"etc/passwd"]);
+        assert_eq!(archive.files[1].data, b"root:x:0:0::/root:/bin/sh\n");
+        assert_eq!(archive.files[2].header.uid, 1000);
+
+        let layers = Initramfs::from_oci_layers(&path, &OciOptions::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(layers.archives.len(), 2);
+        assert_eq!(names(&layers.flatten()), names(&archive));
+    }
+
+    #[test]
+    fn from_oci_verifies_digests() {
+        let layer = tar(&[("init", b'0', b"#!/bin/sh\n")]);
+        let digest = |blob: &[u8]| hex::encode(sha2::Sha256::digest(blob));
+        let manifest = alloc::format!(r#"{{"layers": [{{"digest": "sha256:{}"}}]}}"#, digest(&layer));
+        let index = alloc::format!(r#"{{"manifests": [{{"digest": "sha256:{}"}}]}}"#, digest(manifest.as_bytes()));
+        let (manifest_path, layer_path) = (
+            alloc::format!("blobs/sha256/{}", digest(manifest.as_bytes())),
+            alloc::format!("blobs/sha256/{}", digest(&layer)),
+        );
+        // the layer is stored under its original digest
+        let image = |blob: &[u8]| tar(&[
+            ("index.json", b'0', index.as_bytes()),
+            (&manifest_path, b'0', manifest.as_bytes()),
+            (&layer_path, b'0', blob),
+        ]);
+        let path = temp_file("oci.tar", &image(&layer));
+        assert_eq!(names(&Archive::from_oci(&path, &OciOptions::default()).unwrap()), [&b"init"[..]]);
+        let mut corrupt = layer.clone();
+        corrupt[600] = b'?';
+        std::fs::write(&path, image(&corrupt)).unwrap();
+        let result = Archive::from_oci(&path, &OciOptions::default());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(OciError::DigestMismatch(_))));
+    }
+
+    #[test]
+    fn from_oci_rejects_paths_outside_of_the_image() {
+        let dir = std::env::temp_dir().join(alloc::format!("initramfs-{}-oci-dir", std::process::id()));
+        std::fs::create_dir_all(dir.join("image")).unwrap();
+        std::fs::write(dir.join("layer.tar"), tar(&[("init", b'0', b"#!/bin/sh\n")])).unwrap();
+        std::fs::write(dir.join("image/manifest.json"), br#"[{"Layers": ["./sub/../../layer.tar"]}]"#).unwrap();
+        let escaping = Archive::from_oci(dir.join("image"), &OciOptions::default());
+        std::fs::write(dir.join("image/index.json"), br#"{"manifests": [{"digest": "../../layer:tar"}]}"#).unwrap();
+        let algorithm = Archive::from_oci(dir.join("image"), &OciOptions::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(escaping, Err(OciError::InvalidManifest(_))));
+        assert!(matches!(algorithm, Err(OciError::InvalidManifest(_))));
+    }
+}
@@ -0,0 +1,119 @@
+//! Mapping of archive entries to the distro packages owning them on the host.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use crate::{normalize_path, Archive, File, FileType};
+
+/// Owners of host paths, read from the dpkg and rpm file databases.
+#[derive(Debug, Clone, Default)]
+pub struct PackageDb {
+    /// normalized path -> owning packages
+    owners: BTreeMap<Vec<u8>, Vec<String>>,
+}
+
+/// Result of [`Archive::package_ownership`].
+#[derive(Debug, Clone, Default)]
+pub struct OwnershipReport<'a> {
+    /// package -> entries owned by it
+    pub packages: BTreeMap<String, Vec<&'a File>>,
+    /// entries without an owning package, excluding directories
+    pub unowned: Vec<&'a File>,
+}
+
+impl PackageDb {
+    pub fn new() -> PackageDb {
+        PackageDb::default()
+    }
+
+    /// Reads the databases of every package manager present on the host.
+    pub fn from_host() -> io::Result<PackageDb> {
+        let mut db = PackageDb::new();
+        if Path::new("/var/lib/dpkg/info").is_dir() {
+            db.add_dpkg("/var/lib/dpkg/info")?;
+        }
+        match db.add_rpm() {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            res => res?,
+        }
+        Ok(db)
+    }
+
+    /// Reads the `<package>.list` files of a dpkg info directory, usually `/var/lib/dpkg/info`.
+    pub fn add_dpkg(&mut self, info_dir: impl AsRef<Path>) -> io::Result<()> {
+        for entry in std::fs::read_dir(info_dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "list") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+            // multi-arch packages are named `<package>:<arch>.list`
+            let package = stem.split(':').next().unwrap();
+            for line in std::fs::read(&path)?.split(|&b| b == b'\n') {
+                self.add(line, package);
+            }
+        }
+        Ok(())
+    }
+
+    /// Queries all installed packages with `rpm -qa`.
+    pub fn add_rpm(&mut self) -> io::Result<()> {
+        let output = Command::new("rpm").args(["-qa", "--qf", "[%{NAME}\\t%{FILENAMES}\\n]"]).output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).into_owned()));
+        }
+        for line in output.stdout.split(|&b| b == b'\n') {
+            let Some(tab) = line.iter().position(|&b| b == b'\t') else { continue };
+            self.add(&line[tab + 1..], &String::from_utf8_lossy(&line[..tab]));
+        }
+        Ok(())
+    }
+
+    pub fn add(&mut self, path: &[u8], package: &str) {
+        let path = normalize_path(path);
+        if path.is_empty() {
+            return;
+        }
+        let owners = self.owners.entry(path.to_vec()).or_default();
+        if !owners.iter().any(|owner| owner == package) {
+            owners.push(package.into());
+        }
+    }
+
+    /// Returns the packages owning a path, also trying the `/usr`-merged or unmerged variant of it.
+    pub fn owners(&self, path: &[u8]) -> &[String] {
+        let path = normalize_path(path);
+        let alternative = match path.strip_prefix(b"usr/") {
+            Some(unmerged) => unmerged.to_vec(),
+            None => [b"usr/", path].concat(),
+        };
+        self.owners.get(path)
+            .or_else(|| self.owners.get(&alternative))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+impl Archive {
+    /// Groups all entries except directories and the trailer by their owning packages.
+    ///
+    /// Entries owned by multiple packages are listed for each of them.
+    pub fn package_ownership<'a>(&'a self, db: &PackageDb) -> OwnershipReport<'a> {
+        let mut report = OwnershipReport::default();
+        let files = self.files.iter()
+            .filter(|file| !file.is_trailer() && file.header.file_type() != FileType::Directory);
+        for file in files {
+            let owners = db.owners(&file.filename);
+            if owners.is_empty() {
+                report.unowned.push(file);
+            }
+            for owner in owners {
+                report.packages.entry(owner.clone()).or_default().push(file);
+            }
+        }
+        report
+    }
+}
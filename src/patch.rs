@@ -0,0 +1,110 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{detect_compression, parse_leading_zeroes, Archive, Codec, Error, Initramfs, MaybeRawArchive, ParseOptions};
+
+/// An image opened for surgical editing: on writing, only archives whose entries were modified
+/// are re-encoded, all other segments are copied byte-identically from the original, which keeps
+/// churn minimal for signed or measured images.
+///
+/// Data that doesn't start with a cpio magic, e.g. a compressed archive, can't be parsed and is
+/// kept as a single raw archive up to the end of the image.
+#[derive(Debug, Clone)]
+pub struct Patcher<'a> {
+    original: &'a [u8],
+    initramfs: Initramfs,
+    /// (range in the original including trailing zeros, number of trailing zeros) per archive
+    segments: Vec<(Range<usize>, usize)>,
+}
+
+impl<'a> Patcher<'a> {
+    pub fn parse(original: &'a [u8]) -> Result<Patcher<'a>, Error> {
+        let options = ParseOptions { preserve_layout: true, ..ParseOptions::default() };
+        let mut initramfs = Initramfs::new();
+        let mut segments = Vec::new();
+        let mut index = parse_leading_zeroes(original, 0);
+        if index > 0 {
+            initramfs.add_raw_archive(original[..index].to_vec());
+            segments.push((0..index, 0));
+        }
+        while index < original.len() {
+            if detect_compression(&original[index..]) != Some(Codec::Uncompressed) {
+                initramfs.add_raw_archive(original[index..].to_vec());
+                segments.push((index..original.len(), 0));
+                break;
+            }
+            let (mut archive, end) = Archive::parse_with(original, index, &options)?;
+            let next = parse_leading_zeroes(original, end);
+            archive.padding = Some(next - end);
+            initramfs.add_archive(archive);
+            segments.push((index..next, next - end));
+            index = next;
+        }
+        Ok(Patcher { original, initramfs, segments })
+    }
+
+    pub fn initramfs(&self) -> &Initramfs {
+        &self.initramfs
+    }
+
+    /// Returns the archive at `index` of [`Initramfs::archives`] for editing, `None` for raw archives.
+    pub fn archive_mut(&mut self, index: usize) -> Option<&mut Archive> {
+        match self.initramfs.archives.get_mut(index)? {
+            MaybeRawArchive::Parsed(archive) => Some(archive),
+            MaybeRawArchive::Raw(_) => None,
+        }
+    }
+
+    pub fn archives_mut(&mut self) -> impl Iterator<Item = &mut Archive> {
+        self.initramfs.archives.iter_mut().filter_map(|archive| match archive {
+            MaybeRawArchive::Parsed(archive) => Some(archive),
+            MaybeRawArchive::Raw(_) => None,
+        })
+    }
+
+    /// Indices of the archives that differ from the original and will be re-encoded.
+    pub fn modified_archives(&self) -> Vec<usize> {
+        let mut scratch = Vec::new();
+        (0..self.segments.len()).filter(|&i| {
+            scratch.clear();
+            self.write_segment(i, &mut scratch)
+        }).collect()
+    }
+
+    pub fn write(&self, data: &mut Vec<u8>) {
+        for i in 0..self.segments.len() {
+            self.write_segment(i, data);
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.write(&mut data);
+        data
+    }
+
+    /// Writes a segment, returning whether it had to be re-encoded.
+    ///
+    /// Segments are written at the same offset modulo 4096 as in the original (as long as all
+    /// previous ones were copied, or re-encoded and padded), so that entry alignment is identical.
+    fn write_segment(&self, index: usize, data: &mut Vec<u8>) -> bool {
+        let (range, padding) = &self.segments[index];
+        let original = &self.original[range.clone()];
+        let MaybeRawArchive::Parsed(archive) = &self.initramfs.archives[index] else {
+            data.extend_from_slice(original);
+            return false;
+        };
+        let start = data.len();
+        for file in &archive.files {
+            file.write(data);
+        }
+        if data[start..] == original[..original.len() - padding] {
+            data.extend_from_slice(&original[original.len() - padding..]);
+            return false;
+        }
+        // keep the following segments at their original alignment
+        let padding = (range.end % 4096 + 4096 - data.len() % 4096) % 4096;
+        data.resize(data.len() + padding, 0);
+        true
+    }
+}
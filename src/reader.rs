@@ -0,0 +1,85 @@
+#[cfg(feature = "compression")]
+use alloc::boxed::Box;
+#[cfg(feature = "compression")]
+use alloc::vec::Vec;
+use std::io::{self, BufRead, Read};
+
+use crate::File;
+#[cfg(feature = "compression")]
+use crate::{detect_compression, Codec, Error};
+
+/// [`Read`] and [`BufRead`] over the data of an entry, see [`File::reader`].
+pub struct EntryReader<'a> {
+    inner: Inner<'a>,
+}
+
+enum Inner<'a> {
+    Data(&'a [u8]),
+    /// formats without streaming decoder, decompressed up front
+    #[cfg(feature = "compression")]
+    Decompressed(io::Cursor<Vec<u8>>),
+    #[cfg(feature = "compression")]
+    Decoder(io::BufReader<Box<dyn Read + 'a>>),
+}
+
+impl File {
+    /// Reads the data without copying it.
+    pub fn reader(&self) -> EntryReader<'_> {
+        EntryReader { inner: Inner::Data(&self.data) }
+    }
+
+    /// Like [`reader`](Self::reader), but decompresses data in one of the formats of [`Codec`],
+    /// e.g. of compressed kernel modules (`.ko.gz`, `.ko.xz`, `.ko.zst`). The format is detected
+    /// from the data, anything else is read as-is.
+    ///
+    /// gzip, bzip2 and zstd are decompressed while reading, the other formats up front.
+    #[cfg(feature = "compression")]
+    pub fn decompressing_reader(&self) -> Result<EntryReader<'_>, Error> {
+        let data = &self.data[..];
+        let decoder: Box<dyn Read + '_> = match detect_compression(data) {
+            None | Some(Codec::Uncompressed) => return Ok(self.reader()),
+            Some(Codec::Gzip) => Box::new(flate2::read::GzDecoder::new(data)),
+            Some(Codec::Bzip2) => Box::new(bzip2::read::BzDecoder::new(data)),
+            Some(Codec::Zstd) => Box::new(ruzstd::decoding::StreamingDecoder::new(data).map_err(|_| Error::Decompression(Codec::Zstd))?),
+            Some(_) => {
+                let (_, decompressed) = crate::decompress(data)?;
+                return Ok(EntryReader { inner: Inner::Decompressed(io::Cursor::new(decompressed)) });
+            }
+        };
+        Ok(EntryReader { inner: Inner::Decoder(io::BufReader::new(decoder)) })
+    }
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Data(data) => data.read(buf),
+            #[cfg(feature = "compression")]
+            Inner::Decompressed(cursor) => cursor.read(buf),
+            #[cfg(feature = "compression")]
+            Inner::Decoder(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+impl BufRead for EntryReader<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match &mut self.inner {
+            Inner::Data(data) => Ok(data),
+            #[cfg(feature = "compression")]
+            Inner::Decompressed(cursor) => cursor.fill_buf(),
+            #[cfg(feature = "compression")]
+            Inner::Decoder(decoder) => decoder.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match &mut self.inner {
+            Inner::Data(data) => data.consume(amount),
+            #[cfg(feature = "compression")]
+            Inner::Decompressed(cursor) => cursor.consume(amount),
+            #[cfg(feature = "compression")]
+            Inner::Decoder(decoder) => decoder.consume(amount),
+        }
+    }
+}
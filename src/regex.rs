@@ -0,0 +1,210 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// Backtracking matcher for the extended regular expressions used by SELinux `file_contexts`.
+///
+/// Supported syntax: literals, `.`, `[...]` / `[^...]` with ranges, `\d`, `\w`, `\s`, `\` escaping
+/// other characters, groups `(...)` / `(?:...)` with `|`, the quantifiers `*`, `+`, `?`, `{m}`,
+/// `{m,}`, `{m,n}` and the anchors `^` and `$`. Matches always cover the whole input.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Regex {
+    alternatives: Vec<Vec<Node>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Node {
+    Byte(u8),
+    Any,
+    /// inclusive ranges, negated
+    Class(Vec<(u8, u8)>, bool),
+    Group(Vec<Vec<Node>>),
+    /// node, min, max
+    Repeat(Box<Node>, u32, Option<u32>),
+    Start,
+    End,
+}
+
+impl Regex {
+    /// Returns `None` if the pattern is malformed or uses unsupported syntax.
+    pub(crate) fn new(pattern: &str) -> Option<Regex> {
+        let mut parser = Parser { pattern: pattern.as_bytes(), pos: 0 };
+        let alternatives = parser.alternatives()?;
+        // an unmatched `)` ends the top level early
+        (parser.pos == parser.pattern.len()).then_some(Regex { alternatives })
+    }
+
+    pub(crate) fn is_match(&self, input: &[u8]) -> bool {
+        self.alternatives.iter().any(|sequence| match_sequence(sequence, input, 0, &mut |end| end == input.len()))
+    }
+}
+
+struct Parser<'a> {
+    pattern: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.pattern.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    /// Parses `|`-separated sequences up to a `)` or the end of the pattern.
+    fn alternatives(&mut self) -> Option<Vec<Vec<Node>>> {
+        let mut alternatives = alloc::vec![self.sequence()?];
+        while self.peek() == Some(b'|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Some(alternatives)
+    }
+
+    fn sequence(&mut self) -> Option<Vec<Node>> {
+        let mut sequence = Vec::new();
+        while let Some(b) = self.peek().filter(|&b| b != b'|' && b != b')') {
+            let node = match b {
+                b'*' | b'+' | b'?' | b'{' => {
+                    let (min, max) = self.quantifier()?;
+                    match sequence.pop()? {
+                        Node::Start | Node::End | Node::Repeat(..) => return None,
+                        node => Node::Repeat(Box::new(node), min, max),
+                    }
+                }
+                _ => self.atom()?,
+            };
+            sequence.push(node);
+        }
+        Some(sequence)
+    }
+
+    fn quantifier(&mut self) -> Option<(u32, Option<u32>)> {
+        match self.next()? {
+            b'*' => Some((0, None)),
+            b'+' => Some((1, None)),
+            b'?' => Some((0, Some(1))),
+            _ => {
+                let min = self.number()?;
+                let max = match self.next()? {
+                    b'}' => return Some((min, Some(min))),
+                    b',' if self.peek() == Some(b'}') => None,
+                    b',' => Some(self.number()?).filter(|&max| max >= min),
+                    _ => return None,
+                };
+                (self.next()? == b'}').then_some((min, max))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        core::str::from_utf8(&self.pattern[start..self.pos]).ok()?.parse().ok()
+    }
+
+    fn atom(&mut self) -> Option<Node> {
+        Some(match self.next()? {
+            b'.' => Node::Any,
+            b'^' => Node::Start,
+            b'$' => Node::End,
+            b'(' => {
+                if self.pattern[self.pos..].starts_with(b"?:") {
+                    self.pos += 2;
+                }
+                let alternatives = self.alternatives()?;
+                if self.next()? != b')' {
+                    return None;
+                }
+                Node::Group(alternatives)
+            }
+            b'[' => self.class()?,
+            b'\\' => self.escape()?,
+            b => Node::Byte(b),
+        })
+    }
+
+    fn escape(&mut self) -> Option<Node> {
+        Some(match self.next()? {
+            b'd' => Node::Class(alloc::vec![(b'0', b'9')], false),
+            b'w' => Node::Class(alloc::vec![(b'a', b'z'), (b'A', b'Z'), (b'0', b'9'), (b'_', b'_')], false),
+            b's' => Node::Class(alloc::vec![(b' ', b' '), (b'\t', b'\r')], false),
+            b if b.is_ascii_alphanumeric() => return None,
+            b => Node::Byte(b),
+        })
+    }
+
+    /// Parses a bracket expression after the opening `[`.
+    fn class(&mut self) -> Option<Node> {
+        let negated = self.peek() == Some(b'^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let b = match self.next()? {
+                // a `]` directly after the opening bracket is part of the class
+                b']' if !first => break,
+                b'\\' => self.next()?,
+                b'[' if self.peek() == Some(b':') => return None,
+                b => b,
+            };
+            first = false;
+            let is_range = self.peek() == Some(b'-') && self.pattern.get(self.pos + 1).is_some_and(|&b| b != b']');
+            if is_range {
+                self.pos += 1;
+                let end = match self.next()? {
+                    b'\\' => self.next()?,
+                    end => end,
+                };
+                ranges.push((b, end));
+            } else {
+                ranges.push((b, b));
+            }
+        }
+        Some(Node::Class(ranges, negated))
+    }
+}
+
+/// Matches `sequence` at `pos`, calling `k` with every possible end until it returns true.
+fn match_sequence(sequence: &[Node], input: &[u8], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+    let Some((node, rest)) = sequence.split_first() else { return k(pos) };
+    match node {
+        Node::Repeat(node, min, max) => match_repeat(node, *min, *max, 0, input, pos, &mut |end| match_sequence(rest, input, end, k)),
+        node => match_node(node, input, pos, &mut |end| match_sequence(rest, input, end, k)),
+    }
+}
+
+/// Greedily matches further repetitions of `node` after `count` ones.
+fn match_repeat(node: &Node, min: u32, max: Option<u32>, count: u32, input: &[u8], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+    if max.is_none_or(|max| count < max) {
+        // empty repetitions beyond the minimum can't lead to new matches, but would loop forever
+        let more = match_node(node, input, pos, &mut |end| {
+            (end != pos || count < min) && match_repeat(node, min, max, count + 1, input, end, k)
+        });
+        if more {
+            return true;
+        }
+    }
+    count >= min && k(pos)
+}
+
+fn match_node(node: &Node, input: &[u8], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+    match node {
+        Node::Byte(b) => input.get(pos) == Some(b) && k(pos + 1),
+        Node::Any => pos < input.len() && k(pos + 1),
+        Node::Class(ranges, negated) => {
+            input.get(pos).is_some_and(|&b| ranges.iter().any(|&(start, end)| (start..=end).contains(&b)) != *negated) && k(pos + 1)
+        }
+        Node::Group(alternatives) => alternatives.iter().any(|sequence| match_sequence(sequence, input, pos, k)),
+        Node::Repeat(node, min, max) => match_repeat(node, *min, *max, 0, input, pos, k),
+        Node::Start => pos == 0 && k(pos),
+        Node::End => pos == input.len() && k(pos),
+    }
+}
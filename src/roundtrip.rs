@@ -0,0 +1,189 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{File, Initramfs, MaybeRawArchive};
+
+const HEADER_FIELDS: [&str; 13] = [
+    "ino", "mode", "uid", "gid", "nlink", "mtime", "filesize", "maj", "min", "rmaj", "rmin", "namesize", "chksum",
+];
+
+/// Result of [`Initramfs::roundtrip_report`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RoundtripReport {
+    pub original_len: usize,
+    pub written_len: usize,
+    /// first difference between the original and the re-serialized bytes, if any
+    pub divergence: Option<Divergence>,
+}
+
+impl RoundtripReport {
+    pub fn is_equal(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Explanation of the first byte at which a re-serialized initramfs differs from its original.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Divergence {
+    /// The original has more zero padding at its end. (offset, number of bytes)
+    ExtraTrailingPadding(usize, usize),
+    /// The original has non-zero data after everything that got written. (offset, number of bytes)
+    TrailingData(usize, usize),
+    /// The written output has padding beyond the end of the original, e.g. because archives are
+    /// padded to 4096 bytes. (offset, number of bytes)
+    MissingTrailingPadding(usize, usize),
+    /// A header field has the same value, but with different hex letter case. (offset, field)
+    HexCase(usize, &'static str),
+    /// The original uses a different amount of padding between entries or archives. (offset)
+    Alignment(usize),
+    /// A different entry appears at this position, which exists elsewhere in the output. (offset, original filename)
+    Reordered(usize, Vec<u8>),
+    /// A header field has a different value. (offset, field)
+    Header(usize, &'static str),
+    /// Filename or data differ. (offset)
+    Content(usize),
+}
+
+impl Divergence {
+    pub fn offset(&self) -> usize {
+        match *self {
+            Divergence::ExtraTrailingPadding(offset, _)
+            | Divergence::TrailingData(offset, _)
+            | Divergence::MissingTrailingPadding(offset, _)
+            | Divergence::HexCase(offset, _)
+            | Divergence::Alignment(offset)
+            | Divergence::Reordered(offset, _)
+            | Divergence::Header(offset, _)
+            | Divergence::Content(offset) => offset,
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::ExtraTrailingPadding(offset, len) => write!(f, "original has {len} additional bytes of trailing padding at {offset:#x}"),
+            Divergence::TrailingData(offset, len) => write!(f, "original has {len} additional bytes of trailing data at {offset:#x}"),
+            Divergence::MissingTrailingPadding(offset, len) => write!(f, "output has {len} additional bytes of trailing padding at {offset:#x}"),
+            Divergence::HexCase(offset, field) => write!(f, "different hex case of header field {field} at {offset:#x}"),
+            Divergence::Alignment(offset) => write!(f, "different alignment padding at {offset:#x}"),
+            Divergence::Reordered(offset, filename) => write!(f, "entries reordered, original has {} at {offset:#x}", alloc::string::String::from_utf8_lossy(filename)),
+            Divergence::Header(offset, field) => write!(f, "different value of header field {field} at {offset:#x}"),
+            Divergence::Content(offset) => write!(f, "different content at {offset:#x}"),
+        }
+    }
+}
+
+enum Region<'a> {
+    /// (entry, header start, field index, `None` for the magic)
+    Header(&'a File, usize, Option<usize>),
+    Filename(&'a File, usize),
+    Data,
+    Padding,
+}
+
+impl Initramfs {
+    /// Re-serializes the initramfs and compares the result against `original`, which it was parsed from,
+    /// explaining the first divergence.
+    pub fn roundtrip_report(&self, original: impl AsRef<[u8]>) -> RoundtripReport {
+        let original = original.as_ref();
+        let written = self.to_bytes();
+        let offset = original.iter().zip(&written).take_while(|(a, b)| a == b).count();
+        let divergence = if original.len() == written.len() && offset == original.len() {
+            None
+        } else if offset == written.len() {
+            let len = original.len() - offset;
+            Some(if original[offset..].iter().all(|&b| b == 0) {
+                Divergence::ExtraTrailingPadding(offset, len)
+            } else {
+                Divergence::TrailingData(offset, len)
+            })
+        } else if offset == original.len() {
+            Some(Divergence::MissingTrailingPadding(offset, written.len() - offset))
+        } else {
+            Some(self.classify(original, &written, offset))
+        };
+        RoundtripReport { original_len: original.len(), written_len: written.len(), divergence }
+    }
+
+    fn classify(&self, original: &[u8], written: &[u8], offset: usize) -> Divergence {
+        let reordered = |file: &File, header: usize| {
+            let (original_file, _) = File::parse(original, header).ok()?;
+            let exists = self.files().any(|file| file.filename == original_file.filename);
+            (original_file.filename != file.filename && exists).then_some(original_file.filename)
+        };
+        match self.region(offset) {
+            Region::Header(_, header, _) if original[offset] == 0 && offset == header => Divergence::Alignment(offset),
+            Region::Header(file, header, field) => {
+                if let Some(filename) = reordered(file, header) {
+                    return Divergence::Reordered(offset, filename);
+                }
+                let Some(field) = field else { return Divergence::Header(offset, "magic") };
+                let range = header + 6 + field * 8..header + 14 + field * 8;
+                match original.get(range.clone()) {
+                    Some(value) if value.eq_ignore_ascii_case(&written[range]) => Divergence::HexCase(offset, HEADER_FIELDS[field]),
+                    _ => Divergence::Header(offset, HEADER_FIELDS[field]),
+                }
+            }
+            Region::Filename(file, header) => match reordered(file, header) {
+                Some(filename) => Divergence::Reordered(offset, filename),
+                None => Divergence::Content(offset),
+            },
+            Region::Data => Divergence::Content(offset),
+            Region::Padding => Divergence::Alignment(offset),
+        }
+    }
+
+    /// Finds the part of the output of [`Initramfs::write`] containing `offset`.
+    fn region(&self, offset: usize) -> Region<'_> {
+        let mut pos = 0;
+        for archive in &self.archives {
+            match archive {
+                MaybeRawArchive::Parsed(archive) => {
+                    for file in &archive.files {
+                        let header = pos;
+                        let filename_end = header + 110 + file.filename.len() + 1;
+                        let data = filename_end.next_multiple_of(4);
+                        let end = pos + file.encoded_len();
+                        pos = end;
+                        if offset >= end {
+                            continue;
+                        }
+                        return if offset < header + 6 {
+                            Region::Header(file, header, None)
+                        } else if offset < header + 110 {
+                            Region::Header(file, header, Some((offset - header - 6) / 8))
+                        } else if offset < filename_end {
+                            Region::Filename(file, header)
+                        } else if offset >= data && offset < data + file.data.len() {
+                            Region::Data
+                        } else {
+                            Region::Padding
+                        };
+                    }
+                    match archive.padding {
+                        Some(padding) => {
+                            pos += padding;
+                            if offset < pos {
+                                return Region::Padding;
+                            }
+                            continue;
+                        }
+                        None => pos = pos.next_multiple_of(4096),
+                    }
+                }
+                MaybeRawArchive::Raw(raw) => {
+                    pos += raw.len();
+                    if offset < pos {
+                        return Region::Data;
+                    }
+                }
+            }
+            pos = pos.next_multiple_of(4);
+            if offset < pos {
+                return Region::Padding;
+            }
+        }
+        Region::Padding
+    }
+}
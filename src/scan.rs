@@ -0,0 +1,63 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{parse_leading_zeroes, Archive, Initramfs, MaybeRawArchive};
+
+/// An initramfs found inside a larger image, see [`find_embedded_initramfs`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EmbeddedInitramfs {
+    /// location within the scanned image, excluding trailing padding
+    pub range: Range<usize>,
+    pub initramfs: Initramfs,
+}
+
+/// Scans an image for uncompressed cpio archives, e.g. the initramfs built into an uncompressed
+/// `vmlinux` or decompressed `bzImage` via `CONFIG_INITRAMFS_SOURCE`.
+///
+/// Only runs of complete archives ending with a trailer are returned, so stray occurrences of the
+/// cpio magic (like the one in the kernel's own cpio parser) are skipped.
+/// Directly following archives (only separated by zeroes) are combined into one initramfs.
+/// Compressed built-in initramfs images can't be found this way.
+pub fn find_embedded_initramfs(image: impl AsRef<[u8]>) -> Vec<EmbeddedInitramfs> {
+    let image = image.as_ref();
+    let mut found = Vec::new();
+    let mut start = 0;
+    while let Some(offset) = find_magic(&image[start..]) {
+        let candidate = start + offset;
+        match parse_archives(&image[candidate..]) {
+            Some((initramfs, len)) => {
+                found.push(EmbeddedInitramfs { range: candidate..candidate + len, initramfs });
+                start = candidate + len;
+            }
+            None => start = candidate + 1,
+        }
+    }
+    found
+}
+
+fn find_magic(data: &[u8]) -> Option<usize> {
+    data.windows(6).position(|window| window == b"070701" || window == b"070702")
+}
+
+/// Parses consecutive archives at the start of `data`, each of which must end with a trailer.
+fn parse_archives(data: &[u8]) -> Option<(Initramfs, usize)> {
+    let mut archives = Vec::new();
+    let mut end = 0;
+    loop {
+        let index = parse_leading_zeroes(data, end);
+        if !data[index..].starts_with(b"07070") {
+            break;
+        }
+        match Archive::parse(data, index) {
+            Ok((archive, idx)) if archive.files.last().is_some_and(|file| file.is_trailer()) => {
+                archives.push(MaybeRawArchive::Parsed(archive));
+                end = idx;
+            }
+            _ => break,
+        }
+    }
+    if archives.is_empty() {
+        return None;
+    }
+    Some((Initramfs { archives }, end))
+}
@@ -0,0 +1,194 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::{Archive, FileType};
+
+/// Kind of secret found by [`Archive::scan_secrets`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SecretDetector {
+    /// PEM or OpenSSH private key
+    PrivateKey,
+    /// `password = ...`-style assignment with a literal value in a text file
+    Password,
+    /// crypt(3) hash in a `shadow`-style `user:hash:...` line
+    PasswordHash,
+    /// AWS access key IDs and secret keys, GCP service account keys, Azure storage account keys
+    CloudCredentials,
+}
+
+impl SecretDetector {
+    pub const ALL: [SecretDetector; 4] = [
+        SecretDetector::PrivateKey,
+        SecretDetector::Password,
+        SecretDetector::PasswordHash,
+        SecretDetector::CloudCredentials,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SecretDetector::PrivateKey => "private-key",
+            SecretDetector::Password => "password",
+            SecretDetector::PasswordHash => "password-hash",
+            SecretDetector::CloudCredentials => "cloud-credentials",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SecretDetector> {
+        SecretDetector::ALL.into_iter().find(|detector| detector.name() == name)
+    }
+
+    /// Offsets of all matches in `data`.
+    fn find(self, data: &[u8]) -> Vec<usize> {
+        match self {
+            SecretDetector::PrivateKey => find_all(data, b"-----BEGIN ")
+                .filter(|&start| {
+                    let label = &data[start + 11..];
+                    let label = &label[..label.len().min(64)];
+                    find_all(label, b"-----").next().is_some_and(|end| label[..end].ends_with(b"PRIVATE KEY"))
+                })
+                .collect(),
+            // binaries contain lots of strings like `password=%s`
+            SecretDetector::Password if data.contains(&0) => Vec::new(),
+            SecretDetector::Password => lines(data).filter(|&(_, line)| is_password_assignment(line)).map(|(start, _)| start).collect(),
+            SecretDetector::PasswordHash => lines(data).filter(|&(_, line)| is_shadow_line(line)).map(|(start, _)| start).collect(),
+            SecretDetector::CloudCredentials => {
+                let aws_key_ids = find_all(data, b"AKIA").filter(|&start| {
+                    data.get(start + 4..start + 20).is_some_and(|id| id.iter().all(|&b| b.is_ascii_uppercase() || b.is_ascii_digit()))
+                });
+                let mut offsets: Vec<_> = aws_key_ids
+                    .chain(find_all(data, b"aws_secret_access_key"))
+                    .chain(find_all(data, b"\"private_key_id\""))
+                    .chain(find_all(data, b"AccountKey="))
+                    .collect();
+                offsets.sort_unstable();
+                offsets
+            }
+        }
+    }
+}
+
+impl Display for SecretDetector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Result of [`Archive::scan_secrets`]. The secret itself isn't included, so matches can be
+/// reported safely.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SecretMatch {
+    /// index of the entry in [`Archive::files`]
+    pub index: usize,
+    pub detector: SecretDetector,
+    /// offset of the match in the entry's data
+    pub offset: usize,
+}
+
+/// What [`Archive::redact_secrets`] does with entries containing secrets.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Redaction {
+    /// removes the entries (and their `METADATA!!!` entries)
+    Remove,
+    /// keeps the entries, replacing their data with a note naming the detectors
+    Replace,
+}
+
+impl Archive {
+    /// Scans the data of regular files for secrets with the given detectors, returning the
+    /// matches sorted by entry and offset.
+    pub fn scan_secrets(&self, detectors: &[SecretDetector]) -> Vec<SecretMatch> {
+        let mut matches = Vec::new();
+        for (index, file) in self.files.iter().enumerate() {
+            if file.header.file_type() != FileType::Regular || file.is_metadata() {
+                continue;
+            }
+            let start = matches.len();
+            for &detector in detectors {
+                matches.extend(detector.find(&file.data).into_iter().map(|offset| SecretMatch { index, detector, offset }));
+            }
+            matches[start..].sort_by_key(|m| m.offset);
+        }
+        matches
+    }
+
+    /// Removes or replaces all entries in which [`scan_secrets`](Self::scan_secrets) finds
+    /// something, returning the matches (with indices from before the redaction).
+    pub fn redact_secrets(&mut self, detectors: &[SecretDetector], redaction: Redaction) -> Vec<SecretMatch> {
+        let matches = self.scan_secrets(detectors);
+        let mut found: BTreeMap<usize, Vec<SecretDetector>> = BTreeMap::new();
+        for m in &matches {
+            let detectors = found.entry(m.index).or_default();
+            if !detectors.contains(&m.detector) {
+                detectors.push(m.detector);
+            }
+        }
+        match redaction {
+            Redaction::Remove => {
+                let mut index = 0;
+                let mut keep = true;
+                self.files.retain(|file| {
+                    if !file.is_metadata() {
+                        keep = !found.contains_key(&index);
+                    }
+                    index += 1;
+                    keep
+                });
+            }
+            Redaction::Replace => {
+                for (&index, detectors) in &found {
+                    let names: Vec<_> = detectors.iter().map(|detector| detector.name()).collect();
+                    let note = format!("redacted by initramfs: {}\n", names.join(", "));
+                    self.files[index].set_data(String::into_bytes(note));
+                }
+            }
+        }
+        matches
+    }
+}
+
+fn find_all<'a>(haystack: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    haystack.windows(needle.len()).enumerate().filter(move |(_, window)| *window == needle).map(|(i, _)| i)
+}
+
+/// Lines with the offsets they start at.
+fn lines(data: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    data.split(|&b| b == b'\n').scan(0, |start, line| {
+        let item = (*start, line);
+        *start += line.len() + 1;
+        Some(item)
+    })
+}
+
+const PASSWORD_KEYS: [&[u8]; 6] = [b"password", b"passwd", b"secret", b"token", b"api_key", b"apikey"];
+
+/// `key = value`, `key: value` or `"key": "value"` with a key ending in one of
+/// [`PASSWORD_KEYS`] and a non-empty value that isn't a variable reference.
+fn is_password_assignment(line: &[u8]) -> bool {
+    let Some(separator) = line.iter().position(|&b| b == b'=' || b == b':') else { return false };
+    let key = trim_quotes(line[..separator].trim_ascii()).to_ascii_lowercase();
+    let value = line[separator + 1..].trim_ascii();
+    let value = trim_quotes(value.strip_suffix(b",").unwrap_or(value));
+    PASSWORD_KEYS.iter().any(|suffix| key.ends_with(suffix))
+        && !value.is_empty()
+        && !value.starts_with(b"$")
+        && !value.starts_with(b"%")
+}
+
+fn trim_quotes(s: &[u8]) -> &[u8] {
+    let start = s.iter().position(|&b| b != b'"' && b != b'\'').unwrap_or(s.len());
+    let end = s.iter().rposition(|&b| b != b'"' && b != b'\'').map_or(start, |end| end + 1);
+    &s[start..end]
+}
+
+/// `user:$id$...:` with a crypt(3) hash in the second field.
+fn is_shadow_line(line: &[u8]) -> bool {
+    let mut fields = line.split(|&b| b == b':');
+    let (Some(user), Some(hash)) = (fields.next(), fields.next()) else { return false };
+    !user.is_empty()
+        && user.iter().all(|&b| b.is_ascii_alphanumeric() || b"_.-".contains(&b))
+        && hash.len() > 20
+        && [&b"$1$"[..], b"$2a$", b"$2b$", b"$2y$", b"$5$", b"$6$", b"$y$", b"$gy$"].iter().any(|prefix| hash.starts_with(prefix))
+}
@@ -0,0 +1,175 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::regex::Regex;
+use crate::xattrs::with_xattr;
+use crate::{normalize_path, Archive, File, FileType, Initramfs, MaybeRawArchive};
+
+/// SELinux labels by path, parsed from a `file_contexts` specification,
+/// see [`Archive::apply_file_contexts`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct FileContexts {
+    specs: Vec<ContextSpec>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ContextSpec {
+    regex: Regex,
+    /// whether the pattern is a plain path without regex syntax
+    exact: bool,
+    file_type: Option<FileType>,
+    /// `None` for `<<none>>`
+    context: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FileContextsError {
+    /// (line number) line doesn't consist of a pattern, an optional file type and a context
+    InvalidLine(usize),
+    /// (line number) unsupported or malformed regular expression
+    InvalidRegex(usize),
+    /// (line number) file type other than `--`, `-d`, `-l`, `-c`, `-b`, `-p` or `-s`
+    InvalidFileType(usize),
+}
+
+impl Display for FileContextsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FileContextsError::InvalidLine(line) => write!(f, "invalid specification in line {line}, expected `<regex> [<file type>] <context>`"),
+            FileContextsError::InvalidRegex(line) => write!(f, "invalid or unsupported regular expression in line {line}"),
+            FileContextsError::InvalidFileType(line) => write!(f, "invalid file type in line {line}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FileContextsError {}
+
+impl FileContexts {
+    /// Parses a `file_contexts` file with one `<regex> [<file type>] <context>` specification per
+    /// line, as used by `setfiles`. Empty lines and lines starting with `#` are ignored.
+    ///
+    /// Regular expressions are matched against the whole absolute path, see
+    /// [`lookup`](Self::lookup) for precedence.
+    pub fn parse(specs: &str) -> Result<FileContexts, FileContextsError> {
+        let mut contexts = FileContexts::default();
+        for (i, line) in specs.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let (pattern, file_type, context) = match fields[..] {
+                [pattern, context] => (pattern, None, context),
+                [pattern, file_type, context] => {
+                    let file_type = match file_type {
+                        "--" => FileType::Regular,
+                        "-d" => FileType::Directory,
+                        "-l" => FileType::Symlink,
+                        "-c" => FileType::CharDevice,
+                        "-b" => FileType::BlockDevice,
+                        "-p" => FileType::Fifo,
+                        "-s" => FileType::Socket,
+                        _ => return Err(FileContextsError::InvalidFileType(i + 1)),
+                    };
+                    (pattern, Some(file_type), context)
+                }
+                _ => return Err(FileContextsError::InvalidLine(i + 1)),
+            };
+            contexts.specs.push(ContextSpec {
+                regex: Regex::new(pattern).ok_or(FileContextsError::InvalidRegex(i + 1))?,
+                exact: !pattern.contains(['.', '^', '$', '?', '*', '+', '|', '[', '(', '{', '\\']),
+                file_type,
+                context: (context != "<<none>>").then(|| context.into()),
+            });
+        }
+        Ok(contexts)
+    }
+
+    /// Label of the entry at `path` (absolute or relative to the root), `None` if no
+    /// specification matches or the matching one is `<<none>>`.
+    ///
+    /// Like libselinux, the last matching specification wins, with plain paths taking
+    /// precedence over regular expressions.
+    pub fn lookup(&self, path: &[u8], file_type: FileType) -> Option<&str> {
+        let mut absolute = Vec::with_capacity(path.len() + 1);
+        absolute.push(b'/');
+        absolute.extend_from_slice(normalize_path(path));
+        let matches = |spec: &&ContextSpec| spec.file_type.is_none_or(|t| t == file_type) && spec.regex.is_match(&absolute);
+        let exact = self.specs.iter().rev().filter(|spec| spec.exact).find(matches);
+        let spec = exact.or_else(|| self.specs.iter().rev().filter(|spec| !spec.exact).find(matches))?;
+        spec.context.as_deref()
+    }
+}
+
+impl Archive {
+    /// Sets `security.selinux` of every entry labeled by `contexts` (see [`FileContexts::lookup`]),
+    /// returning the number of labeled entries. Other entries keep their previous label.
+    pub fn apply_file_contexts(&mut self, contexts: &FileContexts) -> usize {
+        let mut labeled = 0;
+        let mut next_ino = self.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+        // rebuilt in one pass instead of `set_xattr` per entry, which would be quadratic
+        let mut files = core::mem::take(&mut self.files).into_iter().peekable();
+        while let Some(file) = files.next() {
+            let mut metadata = Vec::new();
+            while let Some(entry) = files.next_if(File::is_metadata) {
+                metadata.push(entry);
+            }
+            let context = if file.is_trailer() || file.is_metadata() {
+                None
+            } else {
+                contexts.lookup(&file.filename, file.header.file_type())
+            };
+            self.files.push(file);
+            match context {
+                Some(context) => {
+                    // like setfiles, including the NUL terminator
+                    let mut label = context.as_bytes().to_vec();
+                    label.push(0);
+                    let mut entry = with_xattr(&metadata, b"security.selinux", &label);
+                    entry.header.ino = next_ino;
+                    next_ino += 1;
+                    self.files.push(entry);
+                    labeled += 1;
+                }
+                None => self.files.extend(metadata),
+            }
+        }
+        labeled
+    }
+}
+
+impl Initramfs {
+    /// Labels the entries of all parsed archives, see [`Archive::apply_file_contexts`].
+    pub fn apply_file_contexts(&mut self, contexts: &FileContexts) -> usize {
+        self.archives.iter_mut()
+            .filter_map(|archive| match archive {
+                MaybeRawArchive::Parsed(archive) => Some(archive.apply_file_contexts(contexts)),
+                MaybeRawArchive::Raw(_) => None,
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_file_contexts_keeps_other_xattrs() {
+        let contexts = FileContexts::parse("/bin(/.*)? system_u:object_r:bin_t:s0\n").unwrap();
+        let mut archive = Archive::new();
+        archive.add_file(File::new(String::from("bin/sh"), b"sh".to_vec()));
+        archive.add_file(File::new_xattrs(&[(b"user.test".to_vec(), b"1".to_vec())]));
+        archive.add_file(File::new(String::from("etc"), Vec::new()));
+        archive.add_file(File::new(String::from("bin/ls"), b"ls".to_vec()));
+        assert_eq!(archive.apply_file_contexts(&contexts), 2);
+        let label = &b"system_u:object_r:bin_t:s0\0"[..];
+        assert_eq!(archive.xattrs(0), [(&b"security.selinux"[..], label), (b"user.test", b"1")]);
+        assert!(archive.xattrs(2).is_empty());
+        assert_eq!(archive.files[3].filename, b"bin/ls");
+        assert_eq!(archive.xattrs(3), [(&b"security.selinux"[..], label)]);
+        assert_eq!(archive.files.len(), 5);
+    }
+}
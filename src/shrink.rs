@@ -0,0 +1,253 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{glob_match, Archive, FileType};
+
+/// Well-known size reduction, see [`Archive::shrink`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ShrinkRule {
+    /// translations below `usr/share/locale` and `usr/lib/locale`
+    Locales,
+    /// man pages, info pages and `usr/share/doc`
+    Documentation,
+    /// `*.a` static libraries
+    StaticLibraries,
+    /// `__pycache__` directories and `*.pyc` files
+    PythonCache,
+    /// regular files with identical content and metadata, turned into hard links
+    DuplicateFiles,
+}
+
+impl ShrinkRule {
+    pub const ALL: [ShrinkRule; 5] = [
+        ShrinkRule::Locales,
+        ShrinkRule::Documentation,
+        ShrinkRule::StaticLibraries,
+        ShrinkRule::PythonCache,
+        ShrinkRule::DuplicateFiles,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShrinkRule::Locales => "locales",
+            ShrinkRule::Documentation => "docs",
+            ShrinkRule::StaticLibraries => "static-libs",
+            ShrinkRule::PythonCache => "pycache",
+            ShrinkRule::DuplicateFiles => "dedup",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ShrinkRule> {
+        ShrinkRule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+
+    /// Glob patterns of the entries removed by this rule.
+    fn patterns(self) -> &'static [&'static str] {
+        match self {
+            ShrinkRule::Locales => &["usr/share/locale/**", "usr/lib/locale/**"],
+            ShrinkRule::Documentation => &["usr/share/man/**", "usr/share/info/**", "usr/share/doc/**"],
+            ShrinkRule::StaticLibraries => &["**/*.a"],
+            ShrinkRule::PythonCache => &["**/__pycache__", "**/__pycache__/**", "**/*.pyc"],
+            ShrinkRule::DuplicateFiles => &[],
+        }
+    }
+}
+
+/// Savings of one rule, returned by [`Archive::shrink`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ShrinkReport {
+    pub rule: ShrinkRule,
+    /// number of removed or hard-linked entries
+    pub entries: usize,
+    /// reduction of the written archive size, excluding the final padding
+    pub bytes_saved: usize,
+}
+
+impl Archive {
+    /// Applies the given rules in order, returning the savings of every rule.
+    ///
+    /// Duplicate files keep their data in the first entry; the others become hard links to it
+    /// with empty data, which the kernel resolves on extraction.
+    pub fn shrink(&mut self, rules: &[ShrinkRule]) -> Vec<ShrinkReport> {
+        rules.iter().map(|&rule| {
+            let size_before = self.encoded_len();
+            let entries = match rule {
+                ShrinkRule::DuplicateFiles => self.link_duplicates(),
+                _ => {
+                    // removed entries take their `METADATA!!!` entries with them
+                    let mut removed = 0;
+                    let mut keep = true;
+                    self.files.retain(|file| {
+                        if !file.is_metadata() {
+                            keep = file.is_trailer()
+                                || !rule.patterns().iter().any(|pattern| glob_match(pattern.as_bytes(), &file.filename));
+                            removed += usize::from(!keep);
+                        }
+                        keep
+                    });
+                    removed
+                }
+            };
+            ShrinkReport { rule, entries, bytes_saved: size_before - self.encoded_len() }
+        }).collect()
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.files.iter().map(|file| file.encoded_len()).sum()
+    }
+
+    /// Turns non-empty regular files with identical data, mode and owner into hard links of the
+    /// first one. Returns the number of entries that became links.
+    fn link_duplicates(&mut self) -> usize {
+        let mut groups: BTreeMap<(&[u8], u32, u32, u32), Vec<usize>> = BTreeMap::new();
+        for (i, file) in self.files.iter().enumerate() {
+            if file.header.file_type() == FileType::Regular && file.header.nlink <= 1 && !file.data.is_empty() && !file.is_metadata() {
+                groups.entry((&file.data, file.header.mode, file.header.uid, file.header.gid)).or_default().push(i);
+            }
+        }
+        let groups: Vec<Vec<usize>> = groups.into_values().filter(|group| group.len() > 1).collect();
+        let mut linked = 0;
+        for group in groups {
+            let first = self.files[group[0]].header.clone();
+            for &i in &group {
+                let file = &mut self.files[i];
+                file.header.ino = first.ino;
+                file.header.maj = first.maj;
+                file.header.min = first.min;
+                file.header.mtime = first.mtime;
+                file.header.nlink = group.len() as u32;
+                if i != group[0] {
+                    file.set_data(Vec::new());
+                    linked += 1;
+                }
+            }
+        }
+        linked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::File;
+    use alloc::string::ToString;
+
+    fn names(archive: &Archive) -> Vec<&[u8]> {
+        archive.files.iter().map(|file| &file.filename[..]).collect()
+    }
+
+    #[test]
+    fn removed_entries_take_their_xattrs() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("usr/lib/libc.a".to_string(), b"archive".to_vec()));
+        archive.add_file(File::new_xattrs(&[(b"user.a".to_vec(), b"1".to_vec())]));
+        archive.add_file(File::new("usr/lib/libc.so".to_string(), b"library".to_vec()));
+        archive.add_trailer();
+        let report = archive.shrink(&[ShrinkRule::StaticLibraries]);
+        assert_eq!(report[0].entries, 1);
+        assert_eq!(names(&archive), [&b"usr/lib/libc.so"[..], b"TRAILER!!!"]);
+        assert!(archive.xattrs(0).is_empty());
+    }
+
+    #[test]
+    fn duplicates_keep_their_xattrs() {
+        let xattrs = [(b"security.selinux".to_vec(), b"system_u:object_r:bin_t:s0\0".to_vec())];
+        let mut archive = Archive::new();
+        archive.add_file(File::new("bin/a".to_string(), b"binary".to_vec()));
+        archive.add_file(File::new_xattrs(&xattrs));
+        archive.add_file(File::new("bin/b".to_string(), b"binary".to_vec()));
+        archive.add_file(File::new_xattrs(&xattrs));
+        archive.add_trailer();
+        assert_eq!(archive.shrink(&[ShrinkRule::DuplicateFiles])[0].entries, 1);
+        assert_eq!(archive.files[0].data, b"binary");
+        assert!(archive.files[2].data.is_empty());
+        assert_eq!(archive.files[0].header.ino, archive.files[2].header.ino);
+        for i in [0, 2] {
+            assert_eq!(archive.xattrs(i), [(&xattrs[0].0[..], &xattrs[0].1[..])]);
+            assert_eq!(archive.files[i + 1].header.nlink, 0);
+        }
+    }
+
+    fn fixture() -> Archive {
+        let mut archive = Archive::new();
+        for path in [
+            "usr/bin/ls",
+            "usr/share/locale/de/LC_MESSAGES/ls.mo",
+            "usr/lib/locale/C.utf8/LC_CTYPE",
+            "usr/share/man/man1/ls.1.gz",
+            "usr/share/info/coreutils.info",
+            "usr/share/doc/coreutils/README",
+            "usr/lib/libz.a",
+            "usr/lib/libz.so",
+            "usr/lib/python3/__pycache__/",
+            "usr/lib/python3/__pycache__/os.cpython-312.pyc",
+            "usr/lib/python3/legacy.pyc",
+            "usr/lib/python3/os.py",
+        ] {
+            let data = if path.ends_with('/') { Vec::new() } else { path.as_bytes().to_vec() };
+            archive.add_file(File::new(path.to_string(), data));
+        }
+        archive.add_trailer();
+        archive
+    }
+
+    #[test]
+    fn rules_remove_their_entries() {
+        let cases: [(ShrinkRule, &[&str]); 4] = [
+            (ShrinkRule::Locales, &["usr/share/locale/de/LC_MESSAGES/ls.mo", "usr/lib/locale/C.utf8/LC_CTYPE"]),
+            (ShrinkRule::Documentation, &["usr/share/man/man1/ls.1.gz", "usr/share/info/coreutils.info", "usr/share/doc/coreutils/README"]),
+            (ShrinkRule::StaticLibraries, &["usr/lib/libz.a"]),
+            (ShrinkRule::PythonCache, &[
+                "usr/lib/python3/__pycache__/",
+                "usr/lib/python3/__pycache__/os.cpython-312.pyc",
+                "usr/lib/python3/legacy.pyc",
+            ]),
+        ];
+        for (rule, removed) in cases {
+            let original = fixture();
+            let mut archive = original.clone();
+            let before = archive.encoded_len();
+            let report = archive.shrink(&[rule]);
+            assert_eq!(report, [ShrinkReport { rule, entries: removed.len(), bytes_saved: before - archive.encoded_len() }]);
+            assert!(report[0].bytes_saved > 0);
+            let expected: Vec<_> = names(&original).into_iter()
+                .filter(|name| !removed.iter().any(|removed| removed.as_bytes() == *name))
+                .collect();
+            assert_eq!(names(&archive), expected, "{}", rule.name());
+        }
+    }
+
+    #[test]
+    fn duplicates_become_hard_links() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("bin/a".to_string(), b"binary".to_vec()));
+        archive.add_file(File::new("bin/b".to_string(), b"binary".to_vec()));
+        let mut other_mode = File::new("bin/c".to_string(), b"binary".to_vec());
+        other_mode.header.set_permissions(0o755);
+        archive.add_file(other_mode);
+        archive.add_file(File::new("empty".to_string(), Vec::new()));
+        archive.add_file(File::new("empty2".to_string(), Vec::new()));
+        archive.add_trailer();
+        let inos: Vec<_> = archive.files.iter().map(|file| file.header.ino).collect();
+        let report = archive.shrink(&[ShrinkRule::DuplicateFiles]);
+        assert_eq!(report[0].entries, 1);
+        assert_eq!(report[0].bytes_saved, 8);
+        assert_eq!(archive.files[0].header.ino, archive.files[1].header.ino);
+        assert_eq!((archive.files[0].header.nlink, archive.files[1].header.nlink), (2, 2));
+        assert!(archive.files[1].data.is_empty());
+        assert_eq!(archive.files[2].data, b"binary");
+        // other modes, empty files and the trailer aren't linked
+        for (file, &ino) in archive.files.iter().zip(&inos).skip(2) {
+            assert_eq!((file.header.ino, file.header.nlink), (ino, 0));
+        }
+        assert!(archive.files[5].is_trailer());
+    }
+
+    #[test]
+    fn rule_names_round_trip() {
+        for rule in ShrinkRule::ALL {
+            assert_eq!(ShrinkRule::from_name(rule.name()), Some(rule));
+        }
+        assert_eq!(ShrinkRule::from_name("unknown"), None);
+    }
+}
@@ -0,0 +1,108 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use crate::mode::DEFAULT_DIR_MODE;
+use crate::walk::path_components;
+use crate::{normalize_path, Archive, File, FileType};
+
+/// Canonical entry orders for [`Archive::sort`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum SortOrder {
+    /// by normalized path bytes, like `find | LC_ALL=C sort`
+    Lexicographic,
+    /// every directory directly followed by its contents, like [`Archive::walk`]
+    #[default]
+    DepthFirst,
+    /// by depth, with directories before other entries on the same level
+    BreadthFirst,
+}
+
+impl SortOrder {
+    pub fn compare(self, a: &File, b: &File) -> Ordering {
+        match self {
+            SortOrder::Lexicographic => normalize_path(&a.filename).cmp(normalize_path(&b.filename)),
+            SortOrder::DepthFirst => path_components(&a.filename).cmp(&path_components(&b.filename)),
+            SortOrder::BreadthFirst => {
+                let (a_components, b_components) = (path_components(&a.filename), path_components(&b.filename));
+                let is_dir = |file: &File| file.header.file_type() == FileType::Directory;
+                a_components.len().cmp(&b_components.len())
+                    .then(is_dir(b).cmp(&is_dir(a)))
+                    .then(a_components.cmp(&b_components))
+            }
+        }
+    }
+}
+
+impl Archive {
+    /// Sorts all entries before the trailer. The sort is stable, so entries with the same
+    /// path keep overriding each other in the same order. `METADATA!!!` entries stay directly
+    /// after the entry they belong to.
+    pub fn sort(&mut self, order: SortOrder) {
+        let end = self.end_of_entries();
+        let mut groups: Vec<Vec<File>> = Vec::new();
+        for file in self.files.drain(..end) {
+            match groups.last_mut() {
+                Some(group) if file.is_metadata() => group.push(file),
+                _ => groups.push(vec![file]),
+            }
+        }
+        groups.sort_by(|a, b| order.compare(&a[0], &b[0]));
+        self.files.splice(0..0, groups.into_iter().flatten());
+    }
+
+    /// Inserts an entry at its position in [`SortOrder::default`] order, after entries with the
+    /// same path, so an archive sorted with [`Archive::sort`] stays sorted.
+    ///
+    /// Missing parent directories are inserted as well with mode `0o755`.
+    /// Inode numbers are assigned like [`Archive::append`].
+    pub fn insert_sorted(&mut self, file: File) {
+        let path = normalize_path(&file.filename);
+        let parents: Vec<Vec<u8>> = path.iter().enumerate()
+            .filter(|&(_, &b)| b == b'/')
+            .map(|(i, _)| path[..i].to_vec())
+            .filter(|parent| !self.files.iter().any(|file| normalize_path(&file.filename) == &parent[..]))
+            .collect();
+        for parent in parents {
+            let mut dir = File::new(String::new(), Vec::new());
+            dir.header.mode = DEFAULT_DIR_MODE;
+            dir.set_filename(parent);
+            self.insert_sorted_entry(dir);
+        }
+        self.insert_sorted_entry(file);
+    }
+
+    fn insert_sorted_entry(&mut self, mut file: File) {
+        file.header.ino = self.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+        let end = self.end_of_entries();
+        // skipping `METADATA!!!` entries, so they stay with the entry before them
+        let index = (0..end)
+            .find(|&i| !self.files[i].is_metadata() && SortOrder::default().compare(&self.files[i], &file) == Ordering::Greater)
+            .unwrap_or(end);
+        self.files.insert(index, file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_keeps_metadata_with_entry() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new(String::from("b"), b"b".to_vec()));
+        archive.add_file(File::new(String::from("a"), b"a".to_vec()));
+        archive.set_xattr(0, b"user.name", b"b");
+        archive.set_xattr(2, b"user.name", b"a");
+        archive.add_trailer();
+        archive.sort(SortOrder::Lexicographic);
+        let names: Vec<_> = archive.files.iter().map(|file| &file.filename[..]).collect();
+        assert_eq!(names, [&b"a"[..], b"METADATA!!!", b"b", b"METADATA!!!", b"TRAILER!!!"]);
+        assert_eq!(archive.xattrs(0), [(&b"user.name"[..], &b"a"[..])]);
+        assert_eq!(archive.xattrs(2), [(&b"user.name"[..], &b"b"[..])]);
+
+        archive.insert_sorted(File::new(String::from("aa"), Vec::new()));
+        assert_eq!(archive.files[2].filename, b"aa");
+    }
+}
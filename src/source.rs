@@ -0,0 +1,223 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use crate::{Archive, CpioHeaderMagic, Error, File};
+
+/// Data of an entry that is only read when the entry is written, see [`File::set_source`] and
+/// [`ArchiveBuilder::add_entry`](crate::ArchiveBuilder::add_entry).
+#[derive(Clone)]
+pub enum DataSource<'a> {
+    Bytes(Vec<u8>),
+    /// host file, only opened when its entry is written
+    Path(PathBuf),
+    /// called every time the entry is written, e.g. to open a file from another source or
+    /// decompress data on the fly
+    Reader(ReaderFactory<'a>),
+}
+
+pub(crate) type ReaderFactory<'a> = Arc<dyn Fn() -> io::Result<Box<dyn Read + 'a>> + Send + Sync + 'a>;
+
+impl<'a> DataSource<'a> {
+    pub fn reader<R: Read + 'a>(factory: impl Fn() -> io::Result<R> + Send + Sync + 'a) -> DataSource<'a> {
+        DataSource::Reader(Arc::new(move || factory().map(|reader| Box::new(reader) as Box<dyn Read + 'a>)))
+    }
+
+    pub(crate) fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(match self {
+            DataSource::Bytes(data) => Box::new(&data[..]),
+            DataSource::Path(path) => Box::new(io::BufReader::new(std::fs::File::open(path)?)),
+            DataSource::Reader(factory) => factory()?,
+        })
+    }
+
+    /// Reads exactly `len` bytes, failing if the source is shorter.
+    fn read(&self, len: u32) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(len as usize);
+        self.open()?.take(len.into()).read_to_end(&mut data)?;
+        if data.len() != len as usize {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "source returned less data than announced"));
+        }
+        Ok(data)
+    }
+}
+
+impl DataSource<'static> {
+    /// The same source with a shorter lifetime, e.g. to add the source of a [`File`] to an
+    /// [`ArchiveBuilder`](crate::ArchiveBuilder).
+    pub(crate) fn scoped<'a>(self) -> DataSource<'a> {
+        match self {
+            DataSource::Bytes(data) => DataSource::Bytes(data),
+            DataSource::Path(path) => DataSource::Path(path),
+            DataSource::Reader(factory) => DataSource::Reader(Arc::new(move || factory().map(|reader| reader as Box<dyn Read + 'a>))),
+        }
+    }
+}
+
+impl Debug for DataSource<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DataSource::Bytes(data) => f.debug_tuple("Bytes").field(&data.len()).finish(),
+            DataSource::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            DataSource::Reader(_) => f.write_str("Reader(..)"),
+        }
+    }
+}
+
+/// Reader sources are only equal to clones of themselves.
+impl PartialEq for DataSource<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DataSource::Bytes(a), DataSource::Bytes(b)) => a == b,
+            (DataSource::Path(a), DataSource::Path(b)) => a == b,
+            (DataSource::Reader(a), DataSource::Reader(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for DataSource<'_> {}
+
+impl File {
+    /// Replaces the data with `len` bytes read from `source` only when the entry is written, so
+    /// an archive can describe more data than fits into memory (see [`Archive::write_to`]).
+    ///
+    /// Until then [`data`](File::data) is empty. As the checksum isn't known before the data is
+    /// read, the entry is switched to the `070701` format. [`DataSource::Bytes`] are stored in
+    /// `data` directly, ignoring `len`.
+    pub fn set_source(&mut self, source: DataSource<'static>, len: u32) {
+        self.raw_header = None;
+        self.header.magic = CpioHeaderMagic::WithoutChecksum;
+        self.header.chksum = 0;
+        match source {
+            DataSource::Bytes(data) => {
+                self.source = None;
+                self.set_data(data);
+            }
+            source => {
+                self.data = Vec::new();
+                self.header.filesize = len;
+                self.source = Some(source);
+            }
+        }
+    }
+
+    /// The entry with its lazy data read into [`data`](File::data).
+    pub(crate) fn resolved(&self) -> Result<Option<File>, Error> {
+        let Some(source) = &self.source else { return Ok(None) };
+        let data = source.read(self.header.filesize).map_err(|e| Error::Io(e.kind()))?;
+        let mut file = File { source: None, data, ..self.clone() };
+        if file.header.magic == CpioHeaderMagic::WithChecksum {
+            file.header.chksum = file.checksum();
+        }
+        Ok(Some(file))
+    }
+}
+
+impl Archive {
+    /// Writes the archive like [`write`](Archive::write) into `out`, returning the number of
+    /// written bytes.
+    ///
+    /// Lazy data (see [`File::set_source`]) of `070701` entries is streamed, so only one entry's
+    /// data is read at a time.
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<u64> {
+        let mut written = 0;
+        let mut buf = Vec::new();
+        for file in &self.files {
+            written += write_padding(&mut out, written, 4)?;
+            // entries are encoded starting at an aligned position, so their padding is the same
+            // as in the output
+            buf.clear();
+            match &file.source {
+                Some(source) if file.header.magic == CpioHeaderMagic::WithoutChecksum => {
+                    file.write_in_memory(&mut buf);
+                    out.write_all(&buf)?;
+                    let len = u64::from(file.header.filesize);
+                    let copied = io::copy(&mut source.open()?.take(len), &mut out)?;
+                    if copied != len {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "source returned less data than announced"));
+                    }
+                    written += buf.len() as u64 + len;
+                    continue;
+                }
+                _ => file.try_write(&mut buf).map_err(|e| match e {
+                    Error::Io(kind) => io::Error::from(kind),
+                    e => io::Error::other(e),
+                })?,
+            }
+            out.write_all(&buf)?;
+            written += buf.len() as u64;
+        }
+        match self.padding {
+            Some(padding) => {
+                out.write_all(&alloc::vec![0; padding])?;
+                written += padding as u64;
+            }
+            None => written += write_padding(&mut out, written, 4096)?,
+        }
+        Ok(written)
+    }
+}
+
+pub(crate) fn write_padding(out: &mut impl Write, written: u64, align_to: u64) -> io::Result<u64> {
+    let padding = written.next_multiple_of(align_to) - written;
+    out.write_all(&[0; 4096][..padding as usize])?;
+    Ok(padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn archive(source: DataSource<'static>, len: u32) -> Archive {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("small".to_string(), b"in memory".to_vec()));
+        let mut file = File::new("large".to_string(), Vec::new());
+        file.set_source(source, len);
+        archive.add_file(file);
+        archive.add_trailer();
+        archive
+    }
+
+    #[test]
+    fn lazy_data_is_read_when_writing() {
+        let lazy = archive(DataSource::reader(|| Ok(io::repeat(7))), 10_000);
+        assert!(lazy.files[1].data.is_empty());
+        let eager = archive(DataSource::Bytes(alloc::vec![7; 10_000]), 0);
+        assert_eq!(lazy.files[1].encoded_len(), eager.files[1].encoded_len());
+        let mut expected = Vec::new();
+        eager.write(&mut expected);
+
+        let mut data = Vec::new();
+        lazy.try_write(&mut data).unwrap();
+        assert_eq!(data, expected);
+        let mut streamed = Vec::new();
+        assert_eq!(lazy.write_to(&mut streamed).unwrap(), expected.len() as u64);
+        assert_eq!(streamed, expected);
+
+        let mut checksummed = lazy.clone();
+        checksummed.convert_checksums(CpioHeaderMagic::WithChecksum);
+        let mut expected = eager.clone();
+        expected.convert_checksums(CpioHeaderMagic::WithChecksum);
+        let mut streamed = Vec::new();
+        checksummed.write_to(&mut streamed).unwrap();
+        let mut data = Vec::new();
+        expected.write(&mut data);
+        assert_eq!(streamed, data);
+    }
+
+    #[test]
+    fn unreadable_sources_fail() {
+        let missing = archive(DataSource::Path(PathBuf::from("/nonexistent/initramfs-test")), 4);
+        assert_eq!(missing.try_write(&mut Vec::new()), Err(Error::Io(io::ErrorKind::NotFound)));
+        assert_eq!(missing.write_to(io::sink()).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        let short = archive(DataSource::reader(|| Ok(&b"abc"[..])), 4);
+        assert_eq!(short.try_write(&mut Vec::new()), Err(Error::Io(io::ErrorKind::UnexpectedEof)));
+        assert_eq!(short.write_to(io::sink()).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,148 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::{normalize_path, Archive, File, FileType};
+
+impl Archive {
+    /// Splits the archive into multiple archives whose written size (see [`Archive::write`]) stays
+    /// within `max_size` where possible, each ending with a trailer.
+    ///
+    /// Directory entries are repeated in every archive containing something below them, so each
+    /// archive can be extracted on its own. Entries larger than `max_size` get an archive of their own.
+    /// `METADATA!!!` entries stay in the archive of the entry they belong to, and hard links to the
+    /// same inode in one archive, even if that exceeds `max_size`.
+    pub fn split(&self, max_size: usize) -> Vec<Archive> {
+        let trailer = File::new("TRAILER!!!".to_string(), Vec::new());
+        let written_size = |entries_size: usize| (entries_size + trailer.encoded_len()).next_multiple_of(4096);
+        // entries with their `METADATA!!!` entries, which must stay in the same archive
+        let mut groups = Vec::new();
+        let mut i = 0;
+        while i < self.files.len() {
+            let end = i + 1 + self.files[i + 1..].iter().take_while(|file| file.is_metadata()).count();
+            if !self.files[i].is_trailer() {
+                groups.push(&self.files[i..end]);
+            }
+            i = end;
+        }
+        let dirs: BTreeMap<&[u8], &[File]> = groups.iter()
+            .filter(|group| group[0].header.file_type() == FileType::Directory)
+            .map(|group| (normalize_path(&group[0].filename), *group))
+            .collect();
+        // the kernel forgets hard links at each trailer, and only one of them stores the data
+        let link_key = |file: &File| {
+            (file.header.file_type() != FileType::Directory && file.header.nlink > 1)
+                .then_some((file.header.maj, file.header.min, file.header.ino))
+        };
+        let mut unplaced_links: BTreeMap<_, usize> = BTreeMap::new();
+        for group in &groups {
+            if let Some(key) = link_key(&group[0]) {
+                *unplaced_links.entry(key).or_default() += 1;
+            }
+        }
+        // hard links with some but not all entries in the current archive
+        let mut open_links = BTreeSet::new();
+
+        let mut archives = Vec::new();
+        let mut current = Archive::new();
+        let mut current_size = 0;
+        let mut current_dirs = BTreeSet::new();
+        for group in groups {
+            let file = &group[0];
+            let path = normalize_path(&file.filename);
+            let missing_parents = |current_dirs: &BTreeSet<&[u8]>| -> Vec<&[File]> {
+                path.iter().enumerate()
+                    .filter(|&(_, &b)| b == b'/')
+                    .filter_map(|(i, _)| (!current_dirs.contains(&path[..i])).then(|| dirs.get(&path[..i])).flatten())
+                    .copied()
+                    .collect()
+            };
+            let mut parents = missing_parents(&current_dirs);
+            let size = |parents: &[&[File]]| parents.iter().chain([&group]).flat_map(|group| group.iter()).map(File::encoded_len).sum::<usize>();
+            if !current.files.is_empty() && open_links.is_empty() && written_size(current_size + size(&parents)) > max_size {
+                current.files.push(trailer.clone());
+                archives.push(core::mem::take(&mut current));
+                current_size = 0;
+                current_dirs.clear();
+                parents = missing_parents(&current_dirs);
+            }
+            current_size += size(&parents);
+            for parent in parents {
+                current_dirs.insert(normalize_path(&parent[0].filename));
+                current.files.extend_from_slice(parent);
+            }
+            if file.header.file_type() == FileType::Directory {
+                current_dirs.insert(path);
+            }
+            if let Some(key) = link_key(file) {
+                let unplaced = unplaced_links.get_mut(&key).unwrap();
+                *unplaced -= 1;
+                if *unplaced == 0 {
+                    open_links.remove(&key);
+                } else {
+                    open_links.insert(key);
+                }
+            }
+            current.files.extend_from_slice(group);
+        }
+        if !current.files.is_empty() || archives.is_empty() {
+            current.files.push(trailer);
+            archives.push(current);
+        }
+        archives
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(archive: &Archive) -> Vec<&[u8]> {
+        archive.files.iter().map(|file| &file.filename[..]).collect()
+    }
+
+    #[test]
+    fn split_repeats_parent_directories() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("lib/".to_string(), Vec::new()));
+        archive.add_file(File::new("lib/a".to_string(), alloc::vec![1; 3000]));
+        archive.add_file(File::new("lib/b".to_string(), alloc::vec![2; 3000]));
+        archive.add_trailer();
+        let archives = archive.split(4096);
+        assert_eq!(archives.len(), 2);
+        assert_eq!(names(&archives[0]), [&b"lib/"[..], b"lib/a", b"TRAILER!!!"]);
+        assert_eq!(names(&archives[1]), [&b"lib/"[..], b"lib/b", b"TRAILER!!!"]);
+    }
+
+    #[test]
+    fn split_keeps_metadata_with_entry() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("a".to_string(), alloc::vec![1; 3900]));
+        archive.add_file(File::new_xattrs(&[(b"user.test".to_vec(), alloc::vec![0; 200])]));
+        archive.add_file(File::new("b".to_string(), Vec::new()));
+        archive.add_trailer();
+        let archives = archive.split(4096);
+        assert_eq!(archives.len(), 2);
+        assert_eq!(names(&archives[0]), [&b"a"[..], b"METADATA!!!", b"TRAILER!!!"]);
+        assert_eq!(names(&archives[1]), [&b"b"[..], b"TRAILER!!!"]);
+    }
+
+    #[test]
+    fn split_keeps_hard_links_together() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("a".to_string(), alloc::vec![1; 3000]));
+        archive.add_file(File::new("b".to_string(), alloc::vec![2; 2000]));
+        archive.add_file(File::new("c".to_string(), Vec::new()));
+        archive.add_file(File::new("d".to_string(), Vec::new()));
+        archive.add_trailer();
+        // `c` is a hard link to `a`, which stores the data
+        for i in [0, 2] {
+            archive.files[i].header.ino = 7;
+            archive.files[i].header.nlink = 2;
+        }
+        let archives = archive.split(4096);
+        assert_eq!(archives.len(), 2);
+        assert_eq!(names(&archives[0]), [&b"a"[..], b"b", b"c", b"TRAILER!!!"]);
+        assert_eq!(names(&archives[1]), [&b"d"[..], b"TRAILER!!!"]);
+    }
+}
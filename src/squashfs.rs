@@ -0,0 +1,602 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::mode::{DEFAULT_DIR_MODE, PERMISSION_BITS};
+use crate::{Error, File, FileType, Initramfs};
+
+/// Options for [`Initramfs::to_squashfs`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SquashfsOptions {
+    /// size of data blocks, must be a power of two between 4 KiB and 1 MiB
+    pub block_size: u32,
+    /// Compresses data and metadata blocks with gzip (zlib) where that saves space. Requires
+    /// the `compression` feature, otherwise everything is stored uncompressed.
+    pub compress: bool,
+}
+
+impl Default for SquashfsOptions {
+    fn default() -> SquashfsOptions {
+        SquashfsOptions { block_size: 128 * 1024, compress: true }
+    }
+}
+
+const METADATA_BLOCK_SIZE: usize = 8192;
+const NO_FRAGMENT: u32 = 0xffff_ffff;
+const NO_TABLE: u64 = u64::MAX;
+
+const FLAG_UNCOMPRESSED_INODES: u16 = 0x1;
+const FLAG_UNCOMPRESSED_DATA: u16 = 0x2;
+const FLAG_UNCOMPRESSED_FRAGMENTS: u16 = 0x8;
+const FLAG_NO_FRAGMENTS: u16 = 0x10;
+const FLAG_NO_XATTRS: u16 = 0x200;
+const FLAG_UNCOMPRESSED_IDS: u16 = 0x800;
+
+struct Node<'a> {
+    /// `None` for directories only implied by their contents
+    file: Option<&'a File>,
+    children: BTreeMap<&'a [u8], usize>,
+    inode_number: u32,
+}
+
+impl Initramfs {
+    /// Converts the effective tree (see [`Initramfs::effective_view`]) into a squashfs 4.0
+    /// filesystem image without fragments or xattrs.
+    ///
+    /// Hard links share an inode. Directories only implied by the paths of their contents get
+    /// mode `0755` and root ownership, entries below non-directories are skipped. Fails with
+    /// [`Error::InvalidBlockSize`] if the block size isn't supported.
+    pub fn to_squashfs(&self, options: &SquashfsOptions) -> Result<Vec<u8>, Error> {
+        if !options.block_size.is_power_of_two() || !(4096..=1 << 20).contains(&options.block_size) {
+            return Err(Error::InvalidBlockSize(options.block_size));
+        }
+        let view = self.effective_view();
+        let mut nodes = alloc::vec![Node { file: None, children: BTreeMap::new(), inode_number: 0 }];
+        // sorted by path, so parents come before their children
+        for (path, file) in view.iter() {
+            let components: Vec<_> = path.split(|&b| b == b'/').filter(|c| !c.is_empty() && *c != b".").collect();
+            if components.contains(&&b".."[..]) {
+                log::warn!("skipping {} containing `..`", String::from_utf8_lossy(path));
+                continue;
+            }
+            let mut node = 0;
+            for (i, &name) in components.iter().enumerate() {
+                let is_dir = nodes[node].file.is_none_or(|file| file.header.file_type() == FileType::Directory);
+                if !is_dir {
+                    log::warn!("skipping {} below a non-directory", String::from_utf8_lossy(path));
+                    break;
+                }
+                let last = i + 1 == components.len();
+                node = match nodes[node].children.get(name) {
+                    Some(&child) => {
+                        if last {
+                            nodes[child].file = Some(file);
+                        }
+                        child
+                    }
+                    None => {
+                        nodes.push(Node { file: last.then_some(file), children: BTreeMap::new(), inode_number: 0 });
+                        let child = nodes.len() - 1;
+                        nodes[node].children.insert(name, child);
+                        child
+                    }
+                };
+            }
+        }
+
+        // inode numbers in breadth-first order, shared by hard links
+        let mut links: BTreeMap<(u32, u32, u32), u32> = BTreeMap::new();
+        let mut link_counts: BTreeMap<u32, u32> = BTreeMap::new();
+        let mut next_inode = 1;
+        let mut queue = alloc::collections::VecDeque::from([0]);
+        while let Some(node) = queue.pop_front() {
+            let inode_number = match nodes[node].file {
+                Some(file) if file.header.file_type() == FileType::Regular && file.header.nlink > 1 => {
+                    *links.entry((file.header.maj, file.header.min, file.header.ino)).or_insert_with(|| {
+                        next_inode += 1;
+                        next_inode - 1
+                    })
+                }
+                _ => {
+                    next_inode += 1;
+                    next_inode - 1
+                }
+            };
+            *link_counts.entry(inode_number).or_default() += 1;
+            nodes[node].inode_number = inode_number;
+            queue.extend(nodes[node].children.values().copied());
+        }
+        // hard links store their data in only one of the entries
+        let mut link_data: BTreeMap<u32, &[u8]> = BTreeMap::new();
+        for node in &nodes {
+            if let Some(file) = node.file {
+                let data = link_data.entry(node.inode_number).or_default();
+                if file.data.len() > data.len() {
+                    *data = &file.data;
+                }
+            }
+        }
+
+        let mut writer = Writer {
+            options,
+            data: Vec::new(),
+            inodes: MetadataWriter::new(options.compress),
+            directories: MetadataWriter::new(options.compress),
+            ids: Vec::new(),
+            written: BTreeMap::new(),
+            link_counts,
+            link_data,
+            inode_count: next_inode - 1,
+            mtime: view.iter().map(|(_, file)| file.header.mtime).max().unwrap_or(0),
+        };
+        let root = writer.write_node(&nodes, 0, next_inode);
+
+        // superblock | data | inode table | directory table | id table
+        let inode_table_start = 96 + writer.data.len() as u64;
+        let inodes = writer.inodes.finish();
+        let directory_table_start = inode_table_start + inodes.len() as u64;
+        let directories = writer.directories.finish();
+        let mut id_metadata = MetadataWriter::new(options.compress);
+        for id in &writer.ids {
+            id_metadata.write(&id.to_le_bytes());
+        }
+        let id_blocks_start = directory_table_start + directories.len() as u64;
+        let id_blocks = id_metadata.finish();
+        let id_block_starts = id_metadata.block_starts;
+        let id_table_start = id_blocks_start + id_blocks.len() as u64;
+
+        let mut flags = FLAG_NO_FRAGMENTS | FLAG_NO_XATTRS;
+        if !options.compress || cfg!(not(feature = "compression")) {
+            flags |= FLAG_UNCOMPRESSED_INODES | FLAG_UNCOMPRESSED_DATA | FLAG_UNCOMPRESSED_FRAGMENTS | FLAG_UNCOMPRESSED_IDS;
+        }
+        let bytes_used = id_table_start + 8 * id_block_starts.len() as u64;
+        let mut image = Vec::with_capacity(bytes_used.next_multiple_of(4096) as usize);
+        image.extend_from_slice(&0x7371_7368u32.to_le_bytes());
+        image.extend_from_slice(&writer.inode_count.to_le_bytes());
+        image.extend_from_slice(&writer.mtime.to_le_bytes());
+        image.extend_from_slice(&options.block_size.to_le_bytes());
+        // fragment entries
+        image.extend_from_slice(&0u32.to_le_bytes());
+        // gzip
+        image.extend_from_slice(&1u16.to_le_bytes());
+        image.extend_from_slice(&(options.block_size.trailing_zeros() as u16).to_le_bytes());
+        image.extend_from_slice(&flags.to_le_bytes());
+        image.extend_from_slice(&(writer.ids.len() as u16).to_le_bytes());
+        image.extend_from_slice(&4u16.to_le_bytes());
+        image.extend_from_slice(&0u16.to_le_bytes());
+        image.extend_from_slice(&root.to_le_bytes());
+        image.extend_from_slice(&bytes_used.to_le_bytes());
+        image.extend_from_slice(&id_table_start.to_le_bytes());
+        // xattr id table
+        image.extend_from_slice(&NO_TABLE.to_le_bytes());
+        image.extend_from_slice(&inode_table_start.to_le_bytes());
+        image.extend_from_slice(&directory_table_start.to_le_bytes());
+        // fragment table, pointing to the (empty) end of the directory table like mksquashfs
+        image.extend_from_slice(&id_blocks_start.to_le_bytes());
+        // export table
+        image.extend_from_slice(&NO_TABLE.to_le_bytes());
+        image.extend_from_slice(&writer.data);
+        image.extend_from_slice(&inodes);
+        image.extend_from_slice(&directories);
+        image.extend_from_slice(&id_blocks);
+        for start in id_block_starts {
+            image.extend_from_slice(&(id_blocks_start + start as u64).to_le_bytes());
+        }
+        image.resize(image.len().next_multiple_of(4096), 0);
+        Ok(image)
+    }
+}
+
+struct Writer<'o, 'a> {
+    options: &'o SquashfsOptions,
+    /// data blocks, written directly after the superblock
+    data: Vec<u8>,
+    inodes: MetadataWriter,
+    directories: MetadataWriter,
+    /// uids and gids referenced by index
+    ids: Vec<u32>,
+    /// inode number -> inode reference of already written inodes
+    written: BTreeMap<u32, u64>,
+    link_counts: BTreeMap<u32, u32>,
+    link_data: BTreeMap<u32, &'a [u8]>,
+    inode_count: u32,
+    mtime: u32,
+}
+
+impl Writer<'_, '_> {
+    /// Writes the inode of `node` (and everything below it) once, returning its inode reference.
+    fn write_node(&mut self, nodes: &[Node<'_>], node: usize, parent_inode: u32) -> u64 {
+        let inode_number = nodes[node].inode_number;
+        if let Some(&reference) = self.written.get(&inode_number) {
+            return reference;
+        }
+        let file = nodes[node].file;
+        let file_type = file.map_or(FileType::Directory, |file| file.header.file_type());
+        let mut entries = Vec::new();
+        if file_type == FileType::Directory {
+            for (&name, &child) in &nodes[node].children {
+                let reference = self.write_node(nodes, child, inode_number);
+                let child_type = nodes[child].file.map_or(FileType::Directory, |file| file.header.file_type());
+                entries.push((name, reference, nodes[child].inode_number, basic_type(child_type)));
+            }
+        }
+
+        let (mode, uid, gid, mtime) = match file {
+            Some(file) => (file.header.permissions(), file.header.uid, file.header.gid, file.header.mtime),
+            None => (DEFAULT_DIR_MODE & PERMISSION_BITS, 0, 0, self.mtime),
+        };
+        let reference = self.inodes.reference();
+        let mut inode = Vec::new();
+        let header = |inode: &mut Vec<u8>, inode_type: u16, ids: &mut Vec<u32>| {
+            inode.extend_from_slice(&inode_type.to_le_bytes());
+            inode.extend_from_slice(&(mode as u16).to_le_bytes());
+            inode.extend_from_slice(&id_index(ids, uid).to_le_bytes());
+            inode.extend_from_slice(&id_index(ids, gid).to_le_bytes());
+            inode.extend_from_slice(&mtime.to_le_bytes());
+            inode.extend_from_slice(&inode_number.to_le_bytes());
+        };
+        let link_count = self.link_counts[&inode_number];
+        match file_type {
+            FileType::Directory => {
+                let (start_block, offset, listing_size) = self.write_directory(&entries);
+                let subdirectories = entries.iter().filter(|entry| entry.3 == 1).count() as u32;
+                // `.` and `..`, which aren't stored
+                let file_size = listing_size + 3;
+                if let Ok(file_size) = u16::try_from(file_size) {
+                    header(&mut inode, 1, &mut self.ids);
+                    inode.extend_from_slice(&start_block.to_le_bytes());
+                    inode.extend_from_slice(&(2 + subdirectories).to_le_bytes());
+                    inode.extend_from_slice(&file_size.to_le_bytes());
+                    inode.extend_from_slice(&offset.to_le_bytes());
+                    inode.extend_from_slice(&parent_inode.to_le_bytes());
+                } else {
+                    header(&mut inode, 8, &mut self.ids);
+                    inode.extend_from_slice(&(2 + subdirectories).to_le_bytes());
+                    inode.extend_from_slice(&file_size.to_le_bytes());
+                    inode.extend_from_slice(&start_block.to_le_bytes());
+                    inode.extend_from_slice(&parent_inode.to_le_bytes());
+                    // directory index entries
+                    inode.extend_from_slice(&0u16.to_le_bytes());
+                    inode.extend_from_slice(&offset.to_le_bytes());
+                    inode.extend_from_slice(&NO_FRAGMENT.to_le_bytes());
+                }
+            }
+            FileType::Regular => {
+                let data = self.link_data[&inode_number];
+                let start = 96 + self.data.len() as u64;
+                let block_sizes: Vec<u32> = data.chunks(self.options.block_size as usize).map(|block| self.write_block(block)).collect();
+                match u32::try_from(start) {
+                    Ok(start) if link_count == 1 => {
+                        header(&mut inode, 2, &mut self.ids);
+                        inode.extend_from_slice(&start.to_le_bytes());
+                        inode.extend_from_slice(&NO_FRAGMENT.to_le_bytes());
+                        inode.extend_from_slice(&0u32.to_le_bytes());
+                        inode.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    }
+                    _ => {
+                        header(&mut inode, 9, &mut self.ids);
+                        inode.extend_from_slice(&start.to_le_bytes());
+                        inode.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                        // sparse bytes
+                        inode.extend_from_slice(&0u64.to_le_bytes());
+                        inode.extend_from_slice(&link_count.to_le_bytes());
+                        inode.extend_from_slice(&NO_FRAGMENT.to_le_bytes());
+                        inode.extend_from_slice(&0u32.to_le_bytes());
+                        inode.extend_from_slice(&NO_FRAGMENT.to_le_bytes());
+                    }
+                }
+                for size in block_sizes {
+                    inode.extend_from_slice(&size.to_le_bytes());
+                }
+            }
+            FileType::Symlink => {
+                let target = &file.unwrap().data;
+                header(&mut inode, 3, &mut self.ids);
+                inode.extend_from_slice(&link_count.to_le_bytes());
+                inode.extend_from_slice(&(target.len() as u32).to_le_bytes());
+                inode.extend_from_slice(target);
+            }
+            FileType::BlockDevice | FileType::CharDevice => {
+                let header_fields = &file.unwrap().header;
+                let (major, minor) = (header_fields.rmaj, header_fields.rmin);
+                header(&mut inode, basic_type(file_type), &mut self.ids);
+                inode.extend_from_slice(&link_count.to_le_bytes());
+                inode.extend_from_slice(&((major << 8) | (minor & 0xff) | ((minor & !0xff) << 12)).to_le_bytes());
+            }
+            // unknown types can't be represented, store them as fifos
+            FileType::Fifo | FileType::Socket | FileType::Unknown => {
+                header(&mut inode, basic_type(file_type), &mut self.ids);
+                inode.extend_from_slice(&link_count.to_le_bytes());
+            }
+        }
+        self.inodes.write(&inode);
+        self.written.insert(inode_number, reference);
+        reference
+    }
+
+    /// Writes a data block, returning its size word.
+    fn write_block(&mut self, block: &[u8]) -> u32 {
+        match compress(block, self.options.compress) {
+            Some(compressed) => {
+                self.data.extend_from_slice(&compressed);
+                compressed.len() as u32
+            }
+            None => {
+                self.data.extend_from_slice(block);
+                block.len() as u32 | 1 << 24
+            }
+        }
+    }
+
+    /// Writes a directory listing of (name, inode reference, inode number, type) sorted by name,
+    /// returning its start block, offset and size.
+    fn write_directory(&mut self, entries: &[(&[u8], u64, u32, u16)]) -> (u32, u16, u32) {
+        let (start_block, offset) = ((self.directories.reference() >> 16) as u32, self.directories.reference() as u16);
+        let mut size = 0;
+        let mut i = 0;
+        while i < entries.len() {
+            // entries of one header share the metadata block of their inodes and are limited to 256
+            let (_, first_reference, base, _) = entries[i];
+            let run = entries[i..].iter()
+                .take(256)
+                .take_while(|&&(_, reference, inode_number, _)| {
+                    reference >> 16 == first_reference >> 16 && i16::try_from(inode_number as i64 - base as i64).is_ok()
+                })
+                .count();
+            let mut listing = Vec::new();
+            listing.extend_from_slice(&(run as u32 - 1).to_le_bytes());
+            listing.extend_from_slice(&((first_reference >> 16) as u32).to_le_bytes());
+            listing.extend_from_slice(&base.to_le_bytes());
+            for &(name, reference, inode_number, entry_type) in &entries[i..i + run] {
+                listing.extend_from_slice(&(reference as u16).to_le_bytes());
+                listing.extend_from_slice(&((inode_number as i64 - base as i64) as i16).to_le_bytes());
+                listing.extend_from_slice(&entry_type.to_le_bytes());
+                listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+                listing.extend_from_slice(name);
+            }
+            self.directories.write(&listing);
+            size += listing.len() as u32;
+            i += run;
+        }
+        (start_block, offset, size)
+    }
+}
+
+fn basic_type(file_type: FileType) -> u16 {
+    match file_type {
+        FileType::Directory => 1,
+        FileType::Regular => 2,
+        FileType::Symlink => 3,
+        FileType::BlockDevice => 4,
+        FileType::CharDevice => 5,
+        FileType::Fifo | FileType::Unknown => 6,
+        FileType::Socket => 7,
+    }
+}
+
+fn id_index(ids: &mut Vec<u32>, id: u32) -> u16 {
+    let index = ids.iter().position(|&other| other == id).unwrap_or_else(|| {
+        ids.push(id);
+        ids.len() - 1
+    });
+    index as u16
+}
+
+/// zlib-compressed `data` if enabled and smaller.
+fn compress(data: &[u8], enabled: bool) -> Option<Vec<u8>> {
+    #[cfg(feature = "compression")]
+    if enabled {
+        use std::io::Write;
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(data).ok()?;
+        return encoder.finish().ok().filter(|compressed| compressed.len() < data.len());
+    }
+    let _ = (data, enabled);
+    None
+}
+
+/// Table of metadata blocks of up to 8 KiB, each preceded by its (compressed) size.
+struct MetadataWriter {
+    out: Vec<u8>,
+    block: Vec<u8>,
+    compress: bool,
+    /// offsets of the written blocks in `out`
+    block_starts: Vec<usize>,
+}
+
+impl MetadataWriter {
+    fn new(compress: bool) -> MetadataWriter {
+        MetadataWriter { out: Vec::new(), block: Vec::new(), compress, block_starts: Vec::new() }
+    }
+
+    /// Reference to the next written byte: block offset in the table << 16 | offset in the block
+    fn reference(&self) -> u64 {
+        (self.out.len() as u64) << 16 | self.block.len() as u64
+    }
+
+    fn write(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let n = bytes.len().min(METADATA_BLOCK_SIZE - self.block.len());
+            self.block.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.block.len() == METADATA_BLOCK_SIZE {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        self.block_starts.push(self.out.len());
+        match compress(&self.block, self.compress) {
+            Some(compressed) => {
+                self.out.extend_from_slice(&(compressed.len() as u16).to_le_bytes());
+                self.out.extend_from_slice(&compressed);
+            }
+            None => {
+                self.out.extend_from_slice(&(self.block.len() as u16 | 0x8000).to_le_bytes());
+                self.out.extend_from_slice(&self.block);
+            }
+        }
+        self.block.clear();
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        if !self.block.is_empty() {
+            self.flush();
+        }
+        core::mem::take(&mut self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Archive, MaybeRawArchive};
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// Uncompressed metadata table with the position of each block in its contents.
+    struct Table {
+        contents: Vec<u8>,
+        blocks: BTreeMap<u64, usize>,
+    }
+
+    impl Table {
+        fn read(image: &[u8], start: u64, end: u64) -> Table {
+            let mut table = Table { contents: Vec::new(), blocks: BTreeMap::new() };
+            let mut offset = start;
+            while offset < end {
+                let header = u16_at(image, offset as usize);
+                assert_ne!(header & 0x8000, 0, "compressed metadata block");
+                let size = (header & 0x7fff) as usize;
+                table.blocks.insert(offset - start, table.contents.len());
+                let data = offset as usize + 2;
+                table.contents.extend_from_slice(&image[data..data + size]);
+                offset += 2 + size as u64;
+            }
+            table
+        }
+
+        fn at(&self, block: u64, offset: u16) -> &[u8] {
+            &self.contents[self.blocks[&block] + offset as usize..]
+        }
+    }
+
+    /// Minimal reader of uncompressed images: path -> (inode type, inode number, content)
+    fn read_image(image: &[u8]) -> BTreeMap<Vec<u8>, (u16, u32, Vec<u8>)> {
+        assert_eq!(u32_at(image, 0), 0x7371_7368);
+        let root = u64_at(image, 32);
+        let (inode_table, directory_table) = (u64_at(image, 64), u64_at(image, 72));
+        let inodes = Table::read(image, inode_table, directory_table);
+        let directories = Table::read(image, directory_table, u64_at(image, 80));
+        let mut entries = BTreeMap::new();
+        let mut queue = alloc::vec![(Vec::new(), root)];
+        while let Some((path, reference)) = queue.pop() {
+            let inode = inodes.at(reference >> 16, reference as u16);
+            let (inode_type, inode_number) = (u16_at(inode, 0), u32_at(inode, 12));
+            let content = match inode_type {
+                1 => {
+                    let (block, size, offset) = (u32_at(inode, 16), u16_at(inode, 24), u16_at(inode, 26));
+                    let listing = &directories.at(block as u64, offset)[..size as usize - 3];
+                    let mut i = 0;
+                    while i < listing.len() {
+                        let (count, start) = (u32_at(listing, i) + 1, u32_at(listing, i + 4));
+                        i += 12;
+                        for _ in 0..count {
+                            let name_len = u16_at(listing, i + 6) as usize + 1;
+                            let name = &listing[i + 8..i + 8 + name_len];
+                            let child = if path.is_empty() { name.to_vec() } else { [&path[..], b"/", name].concat() };
+                            queue.push((child, (start as u64) << 16 | u16_at(listing, i) as u64));
+                            i += 8 + name_len;
+                        }
+                    }
+                    Vec::new()
+                }
+                2 | 9 => {
+                    let (start, size, blocks) = match inode_type {
+                        2 => (u32_at(inode, 16) as usize, u32_at(inode, 28) as usize, 32),
+                        _ => (u64_at(inode, 16) as usize, u64_at(inode, 24) as usize, 56),
+                    };
+                    let mut data = Vec::new();
+                    let mut offset = start;
+                    for i in 0..size.div_ceil(4096) {
+                        let word = u32_at(inode, blocks + 4 * i);
+                        assert_ne!(word & 1 << 24, 0, "compressed data block");
+                        let block_size = (word & 0xff_ffff) as usize;
+                        data.extend_from_slice(&image[offset..offset + block_size]);
+                        offset += block_size;
+                    }
+                    assert_eq!(data.len(), size);
+                    data
+                }
+                3 => inode[24..24 + u32_at(inode, 20) as usize].to_vec(),
+                _ => Vec::new(),
+            };
+            entries.insert(path, (inode_type, inode_number, content));
+        }
+        entries
+    }
+
+    #[test]
+    fn to_squashfs_rejects_invalid_block_sizes() {
+        let initramfs = Initramfs::new();
+        for block_size in [0, 2048, 3 * 4096, 2 << 20] {
+            let options = SquashfsOptions { block_size, ..SquashfsOptions::default() };
+            assert_eq!(initramfs.to_squashfs(&options), Err(Error::InvalidBlockSize(block_size)));
+        }
+    }
+
+    #[test]
+    fn to_squashfs_round_trip() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new(String::from("bin/"), Vec::new()));
+        let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        archive.add_file(File::new(String::from("bin/busybox"), data.clone()));
+        archive.add_file(File::new_symlink(String::from("bin/sh"), b"busybox".to_vec()));
+        // directory implied by its contents
+        archive.add_file(File::new(String::from("etc/hostname"), b"initramfs\n".to_vec()));
+        let mut link = File::new(String::from("etc/hosts"), b"127.0.0.1 localhost\n".to_vec());
+        link.header.nlink = 2;
+        let mut other_link = link.clone();
+        other_link.set_filename(b"etc/hosts.bak".to_vec());
+        other_link.data.clear();
+        other_link.header.filesize = 0;
+        archive.add_file(link);
+        archive.add_file(other_link);
+        // `add_file` assigns fresh inode numbers
+        let ino = archive.files[4].header.ino;
+        archive.files[5].header.ino = ino;
+        let mut initramfs = Initramfs::new();
+        initramfs.archives.push(MaybeRawArchive::Parsed(archive));
+
+        let image = initramfs.to_squashfs(&SquashfsOptions { block_size: 4096, compress: false }).unwrap();
+        assert_eq!(image.len() % 4096, 0);
+        // a single block of ids, then padding
+        let bytes_used = u64_at(&image, 40) as usize;
+        assert_eq!(bytes_used, u64_at(&image, 48) as usize + 8);
+        assert!(image[bytes_used..].iter().all(|&b| b == 0));
+        let entries = read_image(&image);
+        let paths: Vec<_> = entries.keys().map(|path| &path[..]).collect();
+        assert_eq!(paths, [
+            &b""[..], b"bin", b"bin/busybox", b"bin/sh", b"etc", b"etc/hostname", b"etc/hosts", b"etc/hosts.bak",
+        ]);
+        assert_eq!(u32_at(&image, 4), 7);
+        assert_eq!(entries[&b"bin/busybox"[..]].2, data);
+        assert_eq!(entries[&b"bin/sh"[..]], (3, entries[&b"bin/sh"[..]].1, b"busybox".to_vec()));
+        assert_eq!(entries[&b"etc"[..]].0, 1);
+        assert_eq!(entries[&b"etc/hostname"[..]].2, b"initramfs\n");
+        let (hosts, hosts_bak) = (&entries[&b"etc/hosts"[..]], &entries[&b"etc/hosts.bak"[..]]);
+        assert_eq!(hosts, hosts_bak);
+        assert_eq!((hosts.0, &hosts.2[..]), (9, &b"127.0.0.1 localhost\n"[..]));
+    }
+}
@@ -0,0 +1,286 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use crate::{detect_compression, normalize_path, parse_leading_zeroes, Archive, Codec, EntryLocation, File, FileType, Initramfs, MaybeRawArchive};
+
+/// Summary of an archive's contents, see [`Archive::stats`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ArchiveStats {
+    pub regular_files: usize,
+    pub directories: usize,
+    pub symlinks: usize,
+    pub char_devices: usize,
+    pub block_devices: usize,
+    pub fifos: usize,
+    pub sockets: usize,
+    /// entries with unknown file type, excluding the trailer
+    pub unknown: usize,
+    /// sum of all file data
+    pub data_bytes: usize,
+    /// sum of all filenames including their NUL terminators
+    pub namespace_bytes: usize,
+    /// number of distinct inodes shared by multiple entries
+    pub hardlink_groups: usize,
+    /// alignment bytes inserted between headers, filenames and data, and at the end of the archive
+    pub padding_bytes: usize,
+}
+
+impl ArchiveStats {
+    pub fn entries(&self) -> usize {
+        self.regular_files + self.directories + self.symlinks + self.char_devices
+            + self.block_devices + self.fifos + self.sockets + self.unknown
+    }
+}
+
+/// Cumulative usage of a directory, see [`Archive::du`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DirUsage {
+    /// normalized directory path, empty for the root
+    pub path: Vec<u8>,
+    /// sum of the data of all entries below this directory
+    pub bytes: usize,
+    /// number of entries below this directory
+    pub entries: usize,
+}
+
+/// Sizes of one segment of an image, see [`Initramfs::segment_report`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SegmentReport {
+    /// index in [`Initramfs::archives`]
+    pub index: usize,
+    /// `None` for raw segments in an unknown format
+    pub codec: Option<Codec>,
+    /// size of the segment in the image, including padding
+    pub compressed_size: usize,
+    /// `None` if the segment can't be decompressed, e.g. without the `compression` feature
+    pub decompressed_size: Option<usize>,
+    /// entries excluding the trailer, `None` if the segment can't be parsed
+    pub entries: Option<usize>,
+}
+
+impl SegmentReport {
+    /// Decompressed size divided by compressed size.
+    pub fn ratio(&self) -> Option<f64> {
+        self.decompressed_size.filter(|_| self.compressed_size > 0).map(|size| size as f64 / self.compressed_size as f64)
+    }
+}
+
+/// Regular files storing identical data, see [`Archive::duplicate_content`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DuplicateGroup {
+    /// indices of the entries in [`Archive::files`], in archive order
+    pub indices: Vec<usize>,
+    /// size of the data of each entry
+    pub size: usize,
+    /// whether all entries have the same mode and owner, so
+    /// [`ShrinkRule::DuplicateFiles`](crate::ShrinkRule::DuplicateFiles) turns them into hard links
+    pub linkable: bool,
+}
+
+impl DuplicateGroup {
+    /// Data bytes saved by storing the content only once.
+    pub fn reclaimable_bytes(&self) -> usize {
+        self.size * (self.indices.len() - 1)
+    }
+}
+
+impl Archive {
+    pub fn stats(&self) -> ArchiveStats {
+        let mut stats = ArchiveStats::default();
+        let mut hardlinks = BTreeSet::new();
+        let mut encoded_len = 0;
+        for file in &self.files {
+            let unpadded_len = 110 + file.filename.len() + 1 + file.data.len();
+            encoded_len += file.encoded_len();
+            stats.padding_bytes += file.encoded_len() - unpadded_len;
+            stats.data_bytes += file.data.len();
+            stats.namespace_bytes += file.filename.len() + 1;
+            if file.is_trailer() {
+                continue;
+            }
+            match file.header.file_type() {
+                FileType::Regular => stats.regular_files += 1,
+                FileType::Directory => stats.directories += 1,
+                FileType::Symlink => stats.symlinks += 1,
+                FileType::CharDevice => stats.char_devices += 1,
+                FileType::BlockDevice => stats.block_devices += 1,
+                FileType::Fifo => stats.fifos += 1,
+                FileType::Socket => stats.sockets += 1,
+                FileType::Unknown => stats.unknown += 1,
+            }
+            if file.header.file_type() != FileType::Directory && file.header.nlink > 1 {
+                hardlinks.insert((file.header.maj, file.header.min, file.header.ino));
+            }
+        }
+        // `Archive::write` pads the archive to 4096 bytes
+        stats.padding_bytes += 4096 * encoded_len.div_ceil(4096) - encoded_len;
+        stats.hardlink_groups = hardlinks.len();
+        stats
+    }
+
+    /// Computes cumulative sizes for every directory, like `du -s` on each directory of the
+    /// extracted tree.
+    ///
+    /// Directories without an entry of their own (implicit parents) are included as well.
+    /// The result is sorted by path.
+    pub fn du(&self) -> Vec<DirUsage> {
+        du(self.files.iter().filter(|file| !file.is_trailer()))
+    }
+
+    /// Returns the `n` entries with the most file data, largest first.
+    pub fn largest(&self, n: usize) -> Vec<&File> {
+        self.largest_by(n, |file| file.data.len())
+    }
+
+    /// Returns the `n` entries taking up the most space in the written archive
+    /// (header, filename, data and padding), largest first.
+    pub fn largest_encoded(&self, n: usize) -> Vec<&File> {
+        self.largest_by(n, File::encoded_len)
+    }
+
+    fn largest_by(&self, n: usize, size: impl Fn(&File) -> usize) -> Vec<&File> {
+        let mut files: Vec<_> = self.files.iter().filter(|file| !file.is_trailer()).collect();
+        files.sort_by_key(|&file| core::cmp::Reverse(size(file)));
+        files.truncate(n);
+        files
+    }
+
+    /// Finds non-empty regular files storing the same data, sorted by reclaimable bytes, largest
+    /// first.
+    ///
+    /// Hard links don't count, as only one entry of them carries the data.
+    pub fn duplicate_content(&self) -> Vec<DuplicateGroup> {
+        let mut groups: BTreeMap<&[u8], Vec<usize>> = BTreeMap::new();
+        for (i, file) in self.files.iter().enumerate() {
+            if file.header.file_type() == FileType::Regular && !file.is_metadata() && !file.data.is_empty() {
+                groups.entry(&file.data).or_default().push(i);
+            }
+        }
+        let mut groups: Vec<_> = groups.into_iter()
+            .filter(|(_, indices)| indices.len() > 1)
+            .map(|(data, indices)| {
+                let first = &self.files[indices[0]].header;
+                let linkable = indices.iter().all(|&i| {
+                    let header = &self.files[i].header;
+                    (header.mode, header.uid, header.gid, header.nlink <= 1) == (first.mode, first.uid, first.gid, true)
+                });
+                DuplicateGroup { indices, size: data.len(), linkable }
+            })
+            .collect();
+        groups.sort_by_key(|group| core::cmp::Reverse(group.reclaimable_bytes()));
+        groups
+    }
+
+    /// Returns every normalized path that occurs more than once, with the indices of all entries
+    /// using it, sorted by path.
+    ///
+    /// Paths only used by directories are not reported, as directories are merged instead of
+    /// overriding each other on extraction.
+    pub fn duplicate_paths(&self) -> Vec<(&[u8], Vec<usize>)> {
+        let mut paths: BTreeMap<&[u8], Vec<usize>> = BTreeMap::new();
+        for (i, file) in self.files.iter().enumerate() {
+            if !file.is_trailer() && !file.is_metadata() {
+                paths.entry(normalize_path(&file.filename)).or_default().push(i);
+            }
+        }
+        paths.into_iter()
+            .filter(|(_, indices)| indices.len() > 1)
+            .filter(|(_, indices)| indices.iter().any(|&i| self.files[i].header.file_type() != FileType::Directory))
+            .collect()
+    }
+}
+
+impl Initramfs {
+    /// Reports the codec and sizes of every segment, i.e. parsed (uncompressed) archives and
+    /// raw segments like compressed ones.
+    pub fn segment_report(&self) -> Vec<SegmentReport> {
+        self.archives.iter().enumerate().map(|(index, archive)| match archive {
+            MaybeRawArchive::Parsed(archive) => {
+                let mut data = Vec::new();
+                archive.write(&mut data);
+                SegmentReport {
+                    index,
+                    codec: Some(Codec::Uncompressed),
+                    compressed_size: data.len(),
+                    decompressed_size: Some(data.len()),
+                    entries: Some(archive.files.iter().filter(|file| !file.is_trailer()).count()),
+                }
+            }
+            MaybeRawArchive::Raw(raw) => {
+                let data = &raw[parse_leading_zeroes(raw, 0)..];
+                // zero padding, which counts as uncompressed
+                let codec = if data.is_empty() { Some(Codec::Uncompressed) } else { detect_compression(data) };
+                let decompressed = match codec {
+                    Some(Codec::Uncompressed) => Some(data.to_vec()),
+                    #[cfg(feature = "compression")]
+                    Some(_) => crate::decompress(data).ok().map(|(_, decompressed)| decompressed),
+                    _ => None,
+                };
+                SegmentReport {
+                    index,
+                    codec,
+                    compressed_size: raw.len(),
+                    decompressed_size: decompressed.as_ref().map(|decompressed| decompressed.len() + raw.len() - data.len()),
+                    entries: decompressed.and_then(|decompressed| if decompressed.is_empty() {
+                        Some(0)
+                    } else {
+                        Archive::parse(&decompressed, 0).ok().map(|(archive, _)| archive.files.iter().filter(|file| !file.is_trailer()).count())
+                    }),
+                }
+            }
+        }).collect()
+    }
+
+    /// Like [`Archive::du`], but for the effective tree of all archives (see
+    /// [`Initramfs::effective_view`]).
+    pub fn du(&self) -> Vec<DirUsage> {
+        du(self.effective_view().iter().map(|(_, file)| file))
+    }
+
+    /// Like [`Archive::duplicate_paths`], but across all parsed archives, including paths
+    /// from earlier archives shadowed by later ones.
+    pub fn duplicate_paths(&self) -> Vec<(&[u8], Vec<EntryLocation>)> {
+        let mut paths: BTreeMap<&[u8], Vec<EntryLocation>> = BTreeMap::new();
+        for (a, archive) in self.archives.iter().enumerate() {
+            let MaybeRawArchive::Parsed(archive) = archive else { continue };
+            for (i, file) in archive.files.iter().enumerate() {
+                if !file.is_trailer() && !file.is_metadata() {
+                    paths.entry(normalize_path(&file.filename)).or_default().push((a, i));
+                }
+            }
+        }
+        let is_dir = |&(a, i): &EntryLocation| match &self.archives[a] {
+            MaybeRawArchive::Parsed(archive) => archive.files[i].header.file_type() == FileType::Directory,
+            MaybeRawArchive::Raw(_) => unreachable!(),
+        };
+        paths.into_iter()
+            .filter(|(_, occurrences)| occurrences.len() > 1)
+            .filter(|(_, occurrences)| !occurrences.iter().all(is_dir))
+            .collect()
+    }
+}
+
+/// Computes the cumulative directory sizes of entries, see [`Archive::du`].
+fn du<'a>(files: impl Iterator<Item = &'a File>) -> Vec<DirUsage> {
+    let mut dirs: BTreeMap<&[u8], DirUsage> = BTreeMap::new();
+    dirs.insert(b"", DirUsage::default());
+    for file in files {
+        let path = normalize_path(&file.filename);
+        if path.is_empty() {
+            continue;
+        }
+        if file.header.file_type() == FileType::Directory {
+            dirs.entry(path).or_insert_with(|| DirUsage { path: path.to_vec(), ..DirUsage::default() });
+        }
+        let parents = path.iter().enumerate()
+            .filter(|&(_, &b)| b == b'/')
+            .map(|(i, _)| &path[..i]);
+        for parent in core::iter::once(&b""[..]).chain(parents) {
+            let usage = dirs.entry(parent)
+                .or_insert_with(|| DirUsage { path: parent.to_vec(), ..DirUsage::default() });
+            usage.bytes += file.data.len();
+            usage.entries += 1;
+        }
+    }
+    dirs.into_values().collect()
+}
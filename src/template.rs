@@ -0,0 +1,57 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::mode::{DEFAULT_DIR_MODE, S_IFDIR, S_IFREG, S_ISVTX};
+use crate::{Archive, File};
+
+/// Busybox applets symlinked in [`Archive::rescue_template`].
+pub const RESCUE_APPLETS: &[&str] = &[
+    "sh", "ash", "cat", "chmod", "chown", "chroot", "cp", "cttyhack", "dd", "df", "dmesg", "echo", "fdisk",
+    "find", "free", "fsck", "grep", "gunzip", "gzip", "head", "hexdump", "ip", "kill", "less", "ln", "losetup",
+    "ls", "lsmod", "mdev", "mkdir", "mknod", "modprobe", "more", "mount", "mv", "ping", "poweroff", "ps",
+    "reboot", "rm", "rmdir", "sed", "setsid", "sleep", "switch_root", "sync", "tail", "tar", "top", "touch",
+    "umount", "uname", "vi", "wget",
+];
+
+const RESCUE_INIT: &str = "\
+#!/bin/sh
+mount -t proc proc /proc
+mount -t sysfs sysfs /sys
+mount -t devtmpfs devtmpfs /dev
+echo 'rescue shell, exit to power off'
+setsid cttyhack sh
+poweroff -f
+";
+
+impl Archive {
+    /// Creates a minimal bootable rescue image from a statically linked busybox binary:
+    /// `bin/busybox` with symlinks for the [`RESCUE_APPLETS`], the usual top-level directories,
+    /// [standard devices](Archive::add_standard_devices) and an `init` script mounting the
+    /// pseudo filesystems and starting a shell on the console. The trailer is added.
+    pub fn rescue_template(busybox: Vec<u8>) -> Archive {
+        let mut archive = Archive::new();
+        for dir in ["bin/", "dev/", "etc/", "mnt/", "proc/", "root/", "run/", "sys/", "tmp/"] {
+            let mut file = File::new(dir.to_string(), Vec::new());
+            file.header.nlink = 2;
+            file.header.mode = match dir {
+                "root/" => S_IFDIR | 0o700,
+                "tmp/" => S_IFDIR | S_ISVTX | 0o777,
+                _ => DEFAULT_DIR_MODE,
+            };
+            archive.add_file(file);
+        }
+        let mut file = File::new("bin/busybox".to_string(), busybox);
+        file.header.mode = S_IFREG | 0o755;
+        archive.add_file(file);
+        for applet in RESCUE_APPLETS {
+            archive.add_file(File::new_symlink(format!("bin/{applet}"), b"busybox".to_vec()));
+        }
+        archive.add_standard_devices();
+        let mut init = File::new("init".to_string(), RESCUE_INIT.as_bytes().to_vec());
+        init.header.mode = S_IFREG | 0o755;
+        archive.add_file(init);
+        archive.add_trailer();
+        archive
+    }
+}
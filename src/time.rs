@@ -0,0 +1,82 @@
+use core::fmt::{Display, Formatter};
+
+use crate::CpioHeader;
+#[cfg(feature = "std")]
+use crate::Error;
+
+/// Calendar representation (UTC) of an mtime.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DateTime {
+    pub year: u32,
+    /// 1-12
+    pub month: u32,
+    /// 1-31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    /// Converts seconds since the unix epoch to a UTC date and time.
+    pub fn from_epoch(epoch: u32) -> DateTime {
+        // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = epoch / 86400;
+        let seconds = epoch % 86400;
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as u32;
+        DateTime {
+            year,
+            month,
+            day,
+            hour: seconds / 3600,
+            minute: seconds / 60 % 60,
+            second: seconds % 60,
+        }
+    }
+}
+
+/// Formats as `YYYY-MM-DD HH:MM:SS`.
+impl Display for DateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+impl CpioHeader {
+    pub fn mtime_date_time(&self) -> DateTime {
+        DateTime::from_epoch(self.mtime)
+    }
+}
+
+#[cfg(feature = "std")]
+impl CpioHeader {
+    pub fn mtime_system_time(&self) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(self.mtime.into())
+    }
+
+    /// Sets the mtime, truncating sub-second precision.
+    ///
+    /// Fails for times before 1970 or after 2106, which can't be represented by the format.
+    pub fn set_mtime_system_time(&mut self, time: std::time::SystemTime) -> Result<(), Error> {
+        let secs = match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(before_epoch) => -(before_epoch.duration().as_secs() as i64),
+        };
+        self.mtime = u32::try_from(secs).map_err(|_| Error::MtimeOutOfRange(secs))?;
+        Ok(())
+    }
+
+    /// Sets the mtime to the modification time of a host file.
+    pub fn set_mtime_from_metadata(&mut self, metadata: &std::fs::Metadata) -> std::io::Result<()> {
+        self.set_mtime_system_time(metadata.modified()?)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
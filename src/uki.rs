@@ -0,0 +1,113 @@
+//! Extraction of the initramfs from Unified Kernel Images (UKI), which embed it as `.initrd`
+//! section of a PE/EFI executable.
+
+use core::fmt::{Display, Formatter};
+
+use crate::{Error, Initramfs};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum UkiError {
+    /// missing `MZ` or `PE\0\0` signature
+    NotPe,
+    /// headers or section data exceed the file
+    Truncated,
+    NoInitrdSection,
+    Parse(Error),
+}
+impl Display for UkiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UkiError::NotPe => write!(f, "not a PE executable"),
+            UkiError::Truncated => write!(f, "truncated PE executable"),
+            UkiError::NoInitrdSection => write!(f, "PE executable has no .initrd section"),
+            UkiError::Parse(e) => write!(f, "invalid .initrd section: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UkiError {}
+
+/// Returns the contents of the `.initrd` section of a UKI.
+pub fn initrd_section(pe: &[u8]) -> Result<&[u8], UkiError> {
+    section(pe, b".initrd")?.ok_or(UkiError::NoInitrdSection)
+}
+
+/// Returns the contents of the PE section with the given name (at most 8 bytes).
+pub fn section<'a>(pe: &'a [u8], name: &[u8]) -> Result<Option<&'a [u8]>, UkiError> {
+    // offsets read from the file can overflow `usize` on 32-bit targets
+    let bytes_at = |offset: usize, len: usize| offset.checked_add(len)
+        .and_then(|end| pe.get(offset..end))
+        .ok_or(UkiError::Truncated);
+    let u16_at = |offset: usize| bytes_at(offset, 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()) as usize);
+    let u32_at = |offset: usize| bytes_at(offset, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+
+    if !pe.starts_with(b"MZ") {
+        return Err(UkiError::NotPe);
+    }
+    let pe_header = u32_at(0x3c)?;
+    if bytes_at(pe_header, 4).ok() != Some(b"PE\0\0") {
+        return Err(UkiError::NotPe);
+    }
+    let coff_header = pe_header + 4;
+    let number_of_sections = u16_at(coff_header + 2)?;
+    let size_of_optional_header = u16_at(coff_header + 16)?;
+    let section_table = coff_header + 20 + size_of_optional_header;
+    for i in 0..number_of_sections {
+        let header = section_table + i * 40;
+        let section_name = bytes_at(header, 8)?;
+        let section_name = section_name.split(|&b| b == 0).next().unwrap();
+        if section_name != name {
+            continue;
+        }
+        let virtual_size = u32_at(header + 8)?;
+        let size_of_raw_data = u32_at(header + 16)?;
+        let pointer_to_raw_data = u32_at(header + 20)?;
+        // the raw data is padded to the file alignment, the virtual size is the actual size
+        let len = match virtual_size {
+            0 => size_of_raw_data,
+            size => size.min(size_of_raw_data),
+        };
+        return bytes_at(pointer_to_raw_data, len).map(Some);
+    }
+    Ok(None)
+}
+
+impl Initramfs {
+    /// Parses the `.initrd` section of a UKI.
+    pub fn from_uki(pe: impl AsRef<[u8]>) -> Result<Initramfs, UkiError> {
+        let pe = pe.as_ref();
+        Initramfs::parse(initrd_section(pe)?).map_err(UkiError::Parse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// PE image with a single section header at 0x58, without optional header.
+    fn pe(name: &[u8; 8], pointer_to_raw_data: u32, size_of_raw_data: u32) -> Vec<u8> {
+        let mut pe = alloc::vec![0; 0x80];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        pe[0x46..0x48].copy_from_slice(&1u16.to_le_bytes());
+        pe[0x58..0x60].copy_from_slice(name);
+        pe[0x68..0x6c].copy_from_slice(&size_of_raw_data.to_le_bytes());
+        pe[0x6c..0x70].copy_from_slice(&pointer_to_raw_data.to_le_bytes());
+        pe
+    }
+
+    #[test]
+    fn section_offsets_are_checked() {
+        let mut image = pe(b".initrd\0", 0x80, 4);
+        image.extend_from_slice(b"data");
+        assert_eq!(initrd_section(&image), Ok(&b"data"[..]));
+        assert_eq!(section(&image, b".linux"), Ok(None));
+        assert_eq!(initrd_section(&image[..0x60]), Err(UkiError::Truncated));
+        assert_eq!(initrd_section(&pe(b".initrd\0", u32::MAX, u32::MAX)), Err(UkiError::Truncated));
+        image[0x3c..0x40].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(initrd_section(&image), Err(UkiError::NotPe));
+    }
+}
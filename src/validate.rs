@@ -0,0 +1,163 @@
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::elf::parse_dynamic;
+use crate::init::shebang_interpreter;
+use crate::vfs::EffectiveView;
+use crate::walk::path_components;
+use crate::{Archive, File, FileType, Initramfs};
+
+/// Filename properties that break shell-based tooling, see [`File::filename_issues`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum FilenameIssue {
+    /// filename is empty, i.e. the header's `namesize` is 1
+    Empty,
+    Newline,
+    /// (first control character other than newline)
+    ControlCharacter(u8),
+    InvalidUtf8,
+    /// filename ends with `/`, but the entry isn't a directory
+    TrailingSlash,
+}
+
+impl Display for FilenameIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FilenameIssue::Empty => write!(f, "empty filename"),
+            FilenameIssue::Newline => write!(f, "filename contains a newline"),
+            FilenameIssue::ControlCharacter(c) => write!(f, "filename contains control character {c:#04x}"),
+            FilenameIssue::InvalidUtf8 => write!(f, "filename is not valid UTF-8"),
+            FilenameIssue::TrailingSlash => write!(f, "filename of non-directory ends with /"),
+        }
+    }
+}
+
+/// Executable script whose interpreter doesn't exist, see [`Initramfs::missing_interpreters`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MissingInterpreter<'a> {
+    /// normalized path of the script
+    pub script: &'a [u8],
+    /// interpreter as written in the shebang line
+    pub interpreter: &'a [u8],
+}
+
+/// Shared library or dynamic loader of an ELF file that can't be found,
+/// see [`Initramfs::missing_libraries`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MissingLibrary<'a> {
+    /// normalized path of the executable or library
+    pub binary: &'a [u8],
+    /// `DT_NEEDED` name or `PT_INTERP` path
+    pub library: &'a [u8],
+}
+
+impl File {
+    pub fn filename_issues(&self) -> Vec<FilenameIssue> {
+        let mut issues = Vec::new();
+        if self.filename.is_empty() {
+            issues.push(FilenameIssue::Empty);
+        }
+        if self.filename.contains(&b'\n') {
+            issues.push(FilenameIssue::Newline);
+        }
+        if let Some(&c) = self.filename.iter().find(|&&c| c != b'\n' && (c < 0x20 || c == 0x7f)) {
+            issues.push(FilenameIssue::ControlCharacter(c));
+        }
+        if core::str::from_utf8(&self.filename).is_err() {
+            issues.push(FilenameIssue::InvalidUtf8);
+        }
+        if self.filename.ends_with(b"/") && self.header.file_type() != FileType::Directory {
+            issues.push(FilenameIssue::TrailingSlash);
+        }
+        issues
+    }
+}
+
+impl Archive {
+    /// Checks the filenames of all entries except the trailer, returning the index of every
+    /// entry with issues together with each of its issues.
+    pub fn validate_filenames(&self) -> Vec<(usize, FilenameIssue)> {
+        self.files.iter().enumerate()
+            .filter(|(_, file)| !file.is_trailer())
+            .flat_map(|(i, file)| file.filename_issues().into_iter().map(move |issue| (i, issue)))
+            .collect()
+    }
+}
+
+impl Initramfs {
+    /// Checks that the `#!` interpreters of all executable scripts (after symlinks, e.g. busybox
+    /// applet links) resolve to regular files in the effective tree, which would otherwise fail
+    /// with "not found" at boot.
+    pub fn missing_interpreters(&self) -> Vec<MissingInterpreter<'_>> {
+        let view = self.effective_view();
+        view.iter()
+            .filter(|(_, file)| file.header.is_executable())
+            .filter_map(|(script, file)| Some(MissingInterpreter { script, interpreter: shebang_interpreter(&file.data)? }))
+            .filter(|missing| view.follow(missing.interpreter).is_none_or(|(_, file)| file.header.file_type() != FileType::Regular))
+            .collect()
+    }
+
+    /// Checks that the dynamic loader and all `DT_NEEDED` libraries of every ELF file in the
+    /// effective tree can be found like `ld.so` would, i.e. in its `DT_RPATH` / `DT_RUNPATH`,
+    /// the directories of `/etc/ld.so.conf` and the default and multiarch library directories.
+    ///
+    /// Libraries loaded with `dlopen` can't be detected.
+    pub fn missing_libraries(&self) -> Vec<MissingLibrary<'_>> {
+        let view = self.effective_view();
+        let default_dirs = library_dirs(&view);
+        let exists = |path: &[u8]| view.follow(path).is_some_and(|(_, file)| file.header.file_type() == FileType::Regular);
+        let mut missing = Vec::new();
+        for (binary, file) in view.iter().filter(|(_, file)| file.header.file_type() == FileType::Regular) {
+            let Some(dynamic) = parse_dynamic(&file.data) else { continue };
+            if let Some(interpreter) = dynamic.interpreter.filter(|interpreter| !exists(interpreter)) {
+                missing.push(MissingLibrary { binary, library: interpreter });
+            }
+            let origin = binary.iter().rposition(|&b| b == b'/').map_or(&b""[..], |i| &binary[..i]);
+            let dirs: Vec<Vec<u8>> = dynamic.search_paths.iter()
+                .map(|dir| replace(&replace(dir, b"${ORIGIN}", origin), b"$ORIGIN", origin))
+                .chain(default_dirs.iter().cloned())
+                .collect();
+            for library in dynamic.needed {
+                let found = match library.contains(&b'/') {
+                    true => exists(library),
+                    false => dirs.iter().any(|dir| exists(&[dir.as_slice(), library].join(&b'/'))),
+                };
+                if !found {
+                    missing.push(MissingLibrary { binary, library });
+                }
+            }
+        }
+        missing
+    }
+}
+
+/// Directories searched by the dynamic loader without `DT_RPATH` / `DT_RUNPATH`.
+fn library_dirs(view: &EffectiveView<'_>) -> Vec<Vec<u8>> {
+    let mut dirs: Vec<Vec<u8>> = view.iter()
+        .filter(|(path, _)| path.starts_with(b"etc/ld.so.conf.d/") && path.ends_with(b".conf") || *path == b"etc/ld.so.conf")
+        .flat_map(|(_, file)| file.data.split(|&b| b == b'\n'))
+        .map(|line| line.trim_ascii())
+        .filter(|line| line.starts_with(b"/"))
+        .map(|line| line.to_vec())
+        .collect();
+    dirs.extend([&b"lib"[..], b"lib64", b"usr/lib", b"usr/lib64"].map(<[u8]>::to_vec));
+    // multiarch directories like `lib/x86_64-linux-gnu` are compiled into Debian's loader
+    dirs.extend(view.iter()
+        .filter(|(_, file)| file.header.file_type() == FileType::Directory)
+        .map(|(path, _)| path_components(path))
+        .filter(|components| matches!(components.as_slice(), [b"lib", arch] | [b"usr", b"lib", arch] if arch.windows(7).any(|w| w == b"-linux-")))
+        .map(|components| components.join(&b'/')));
+    dirs
+}
+
+fn replace(haystack: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut rest = haystack;
+    while let Some(pos) = rest.windows(from.len()).position(|w| w == from) {
+        result.extend_from_slice(&rest[..pos]);
+        result.extend_from_slice(to);
+        rest = &rest[pos + from.len()..];
+    }
+    result.extend_from_slice(rest);
+    result
+}
@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::{checksum, CpioHeader, CpioHeaderMagic, Error, RawCpioHeader};
+
+/// Longest filename including its NUL terminator accepted by the kernel's unpacker.
+const PATH_MAX: u32 = 4096;
+
+/// Result of [`verify_stream`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct StreamSummary {
+    pub archives: usize,
+    /// number of entries excluding trailers
+    pub entries: usize,
+    /// sum of all file data
+    pub data_bytes: u64,
+    /// total length of the input including padding
+    pub bytes: u64,
+}
+
+/// Checks headers, alignment, filenames and checksums of all archives read from `reader`
+/// like [`Initramfs::parse`](crate::Initramfs::parse), without keeping file data in memory.
+///
+/// Errors contain offsets relative to the start of the stream. Filenames longer than `PATH_MAX`
+/// fail with [`Error::FilenameTooLong`], so memory use is bounded even for untrusted input.
+pub fn verify_stream(reader: impl Read) -> Result<StreamSummary, Error> {
+    let mut stream = Stream { reader: BufReader::new(reader), offset: 0 };
+    let mut summary = StreamSummary::default();
+    let mut in_archive = false;
+    loop {
+        if !in_archive {
+            if !stream.skip_zeros()? {
+                break;
+            }
+            summary.archives += 1;
+            in_archive = true;
+        }
+        stream.skip_align()?;
+        let mut header = Vec::with_capacity(110);
+        stream.take(110, |chunk| header.extend_from_slice(chunk))?;
+        let header = CpioHeader::parse(&RawCpioHeader::new(header[..].try_into().unwrap()))?;
+
+        // the kernel rejects longer names, so an untrusted header can't make us buffer gigabytes
+        if header.namesize > PATH_MAX {
+            return Err(Error::FilenameTooLong(header.namesize));
+        }
+        let mut filename = Vec::new();
+        stream.take(header.namesize as u64, |chunk| filename.extend_from_slice(chunk))?;
+        let filename_len = filename.iter().position(|&b| b == 0).ok_or(Error::UnexpectedEof)?;
+        if filename_len + 1 != header.namesize as usize {
+            return Err(Error::InvalidFilenameLength(filename_len as u32 + 1, header.namesize));
+        }
+        filename.truncate(filename_len);
+        stream.skip_align()?;
+
+        let mut sum = 0u32;
+        stream.take(header.filesize as u64, |chunk| sum = sum.wrapping_add(checksum(chunk)))?;
+        match header.magic {
+            CpioHeaderMagic::WithoutChecksum if header.chksum != 0 => return Err(Error::InvalidChecksumNotZero(header.chksum)),
+            CpioHeaderMagic::WithChecksum if header.chksum != sum => return Err(Error::InvalidChecksum(header.chksum, sum)),
+            _ => (),
+        }
+        if filename == b"TRAILER!!!" {
+            in_archive = false;
+        } else {
+            summary.entries += 1;
+            summary.data_bytes += header.filesize as u64;
+        }
+    }
+    summary.bytes = stream.offset as u64;
+    Ok(summary)
+}
+
+struct Stream<R> {
+    reader: BufReader<R>,
+    offset: usize,
+}
+
+impl<R: Read> Stream<R> {
+    fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        while let Err(e) = self.reader.fill_buf() {
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(Error::Io(e.kind()));
+            }
+        }
+        Ok(self.reader.buffer())
+    }
+
+    fn consume_buffered(&mut self, amount: usize) {
+        self.reader.consume(amount);
+        self.offset += amount;
+    }
+
+    /// Skips zero padding between archives, returning whether another archive follows.
+    fn skip_zeros(&mut self) -> Result<bool, Error> {
+        loop {
+            let buf = self.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(false);
+            }
+            let zeros = buf.iter().position(|&b| b != 0).unwrap_or(buf.len());
+            let more = zeros < buf.len();
+            self.consume_buffered(zeros);
+            if more {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Skips alignment to 4 bytes, which has to be zero if present.
+    fn skip_align(&mut self) -> Result<(), Error> {
+        while !self.offset.is_multiple_of(4) {
+            let Some(&b) = self.fill_buf()?.first() else { return Ok(()) };
+            if b != 0 {
+                return Err(Error::InvalidAlign(self.offset, b));
+            }
+            self.consume_buffered(1);
+        }
+        Ok(())
+    }
+
+    /// Passes the next `len` bytes to `f` in chunks.
+    fn take(&mut self, mut len: u64, mut f: impl FnMut(&[u8])) -> Result<(), Error> {
+        while len > 0 {
+            let available = self.fill_buf()?;
+            if available.is_empty() {
+                return Err(Error::UnexpectedEof);
+            }
+            let n = available.len().min(usize::try_from(len).unwrap_or(usize::MAX));
+            f(&available[..n]);
+            self.consume_buffered(n);
+            len -= n as u64;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::{Archive, File, Initramfs};
+
+    fn image() -> Vec<u8> {
+        let mut archive = Archive::new();
+        archive.add_file(File::new(String::from("init"), b"#!/bin/sh\n".to_vec()));
+        archive.add_file(File::new(String::from("etc/"), Vec::new()));
+        archive.add_trailer();
+        archive.convert_checksums(CpioHeaderMagic::WithChecksum);
+        let mut initramfs = Initramfs::new();
+        initramfs.add_archive(archive.clone());
+        initramfs.add_archive(archive);
+        initramfs.to_bytes()
+    }
+
+    #[test]
+    fn verify_stream_matches_parse() {
+        let data = image();
+        let summary = verify_stream(&data[..]).unwrap();
+        assert_eq!(summary, StreamSummary { archives: 2, entries: 4, data_bytes: 20, bytes: data.len() as u64 });
+        Initramfs::parse(&data).unwrap();
+    }
+
+    #[test]
+    fn verify_stream_rejects_corruption() {
+        let mut data = image();
+        let offset = data.windows(10).position(|window| window == b"#!/bin/sh\n").unwrap();
+        data[offset] = b'?';
+        assert!(matches!(verify_stream(&data[..]), Err(Error::InvalidChecksum(..))));
+        assert_eq!(verify_stream(&image()[..100]), Err(Error::UnexpectedEof));
+    }
+
+    #[test]
+    fn verify_stream_limits_filename_length() {
+        let mut data = image();
+        // namesize field of the first header
+        data[94..102].copy_from_slice(b"FFFFFFFF");
+        assert_eq!(verify_stream(&data[..]), Err(Error::FilenameTooLong(u32::MAX)));
+    }
+}
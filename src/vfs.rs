@@ -0,0 +1,289 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::ops::Bound;
+
+use crate::mode::DEFAULT_DIR_MODE;
+use crate::walk::path_components;
+use crate::{normalize_path, Archive, CpioHeader, CpioHeaderMagic, File, FileType, Initramfs};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FsError {
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    /// symlink loop or more symlinks than the hop limit
+    TooManyLinks,
+}
+impl Display for FsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FsError::NotFound => write!(f, "no such file or directory"),
+            FsError::NotADirectory => write!(f, "not a directory"),
+            FsError::IsADirectory => write!(f, "is a directory"),
+            FsError::TooManyLinks => write!(f, "too many levels of symbolic links"),
+        }
+    }
+}
+
+/// Minimal read-only filesystem interface over parsed images, for plugging an initramfs into
+/// path resolution code of bootloaders, hypervisors and similar.
+///
+/// Paths are relative to the root of the image, leading `/` and `./` are ignored.
+/// Symlinks are not followed.
+pub trait ReadOnlyFs {
+    /// Finds the entry of a path. Directories only implied by their contents don't have an entry.
+    fn lookup(&self, path: &str) -> Option<&File>;
+
+    /// Returns the names of all direct children of a directory, sorted.
+    fn readdir(&self, path: &str) -> Result<Vec<&[u8]>, FsError>;
+
+    /// Returns the contents of a regular file or the target of a symlink.
+    fn read(&self, path: &str) -> Result<&[u8], FsError> {
+        match self.lookup(path) {
+            Some(file) if file.header.file_type() == FileType::Directory => Err(FsError::IsADirectory),
+            Some(file) => Ok(&file.data),
+            None if self.readdir(path).is_ok() => Err(FsError::IsADirectory),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    /// Returns the header of an entry.
+    fn metadata(&self, path: &str) -> Result<&CpioHeader, FsError> {
+        self.lookup(path).map(|file| &file.header).ok_or(FsError::NotFound)
+    }
+}
+
+impl ReadOnlyFs for Archive {
+    fn lookup(&self, path: &str) -> Option<&File> {
+        Archive::lookup(self, path)
+    }
+
+    fn readdir(&self, path: &str) -> Result<Vec<&[u8]>, FsError> {
+        let dir = normalize_path(path.as_bytes());
+        if let Some(file) = Archive::lookup(self, path) {
+            if file.header.file_type() != FileType::Directory {
+                return Err(FsError::NotADirectory);
+            }
+        }
+        let children: BTreeSet<_> = self.files.iter()
+            .filter(|file| !file.is_trailer())
+            .filter_map(|file| child_name(dir, normalize_path(&file.filename)))
+            .collect();
+        if children.is_empty() && !dir.is_empty() && Archive::lookup(self, path).is_none() {
+            return Err(FsError::NotFound);
+        }
+        Ok(children.into_iter().collect())
+    }
+}
+
+/// Merged view of all parsed archives of an [`Initramfs`] as the kernel would extract them,
+/// see [`Initramfs::effective_view`].
+#[derive(Debug, Clone)]
+pub struct EffectiveView<'a> {
+    entries: BTreeMap<&'a [u8], &'a File>,
+}
+
+impl<'a> EffectiveView<'a> {
+    /// Iterates over all effective entries sorted by their normalized path.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a [u8], &'a File)> + '_ {
+        self.entries.iter().map(|(&path, &file)| (path, file))
+    }
+
+    /// Finds the entry of a path given as bytes, e.g. a non-UTF-8 filename.
+    pub fn get(&self, path: &[u8]) -> Option<&'a File> {
+        self.entries.get(normalize_path(path)).copied()
+    }
+
+    /// Like [`Archive::resolve`] on the merged tree.
+    pub fn resolve(&self, path: &[u8], max_hops: usize) -> Result<&'a File, FsError> {
+        resolve(|path| self.get(path), |dir| self.has_children(dir), path, max_hops).map(|(_, file)| file)
+    }
+
+    /// Follows symlinks in all path components until reaching another type of entry, returning
+    /// its normalized path and entry. Returns `None` for dangling or looping symlinks.
+    pub(crate) fn follow(&self, path: &[u8]) -> Option<(Vec<u8>, &'a File)> {
+        resolve(|path| self.get(path), |dir| self.has_children(dir), path, MAX_SYMLINKS).ok()
+    }
+
+    /// Whether there are entries below the normalized `dir`.
+    fn has_children(&self, dir: &[u8]) -> bool {
+        self.entries.range::<[u8], _>((Bound::Excluded(dir), Bound::Unbounded))
+            .take_while(|(&path, _)| path.starts_with(dir))
+            .any(|(&path, _)| child_name(dir, path).is_some())
+    }
+}
+
+/// Default hop limit for [`Archive::resolve`], like the kernel's `MAXSYMLINKS`.
+pub const MAX_SYMLINKS: usize = 40;
+
+/// Result of resolving the root or a directory only implied by entries below it.
+static IMPLIED_DIR: File = File {
+    header: CpioHeader {
+        magic: CpioHeaderMagic::WithoutChecksum,
+        ino: 0,
+        mode: DEFAULT_DIR_MODE,
+        uid: 0,
+        gid: 0,
+        nlink: 2,
+        mtime: 0,
+        filesize: 0,
+        maj: 0,
+        min: 0,
+        rmaj: 0,
+        rmin: 0,
+        namesize: 1,
+        chksum: 0,
+    },
+    filename: Vec::new(),
+    data: Vec::new(),
+    raw_header: None,
+    #[cfg(feature = "std")]
+    source: None,
+};
+
+impl Archive {
+    /// Finds the entry of a path like the kernel's path lookup would, following symlinks in all
+    /// components including the last one. `..` is resolved after following symlinks and stops
+    /// at the root.
+    ///
+    /// Directories only implied by entries below them (and the root) count as existing; resolving
+    /// to one returns an entry with an empty filename, mode `0755` and root ownership. Fails with
+    /// [`FsError::TooManyLinks`] after following more than `max_hops` symlinks (see
+    /// [`MAX_SYMLINKS`]) or on a symlink loop.
+    pub fn resolve(&self, path: &str, max_hops: usize) -> Result<&File, FsError> {
+        let lookup = |path: &[u8]| Archive::lookup(self, core::str::from_utf8(path).ok()?);
+        let has_children = |dir: &[u8]| {
+            self.files.iter().any(|file| !file.is_trailer() && !file.is_metadata() && child_name(dir, normalize_path(&file.filename)).is_some())
+        };
+        resolve(lookup, has_children, path.as_bytes(), max_hops).map(|(_, file)| file)
+    }
+}
+
+/// Resolves `path` with `lookup` finding entries by normalized path and `has_children` telling
+/// whether there are entries below a path, returning the resolved path with the entry.
+fn resolve<'a>(
+    lookup: impl Fn(&[u8]) -> Option<&'a File>, has_children: impl Fn(&[u8]) -> bool, path: &[u8], max_hops: usize,
+) -> Result<(Vec<u8>, &'a File), FsError> {
+    let mut remaining: Vec<Vec<u8>> = path_components(path).into_iter().rev().map(<[u8]>::to_vec).collect();
+    let mut resolved: Vec<Vec<u8>> = Vec::new();
+    // (symlink, components after it) that were already followed
+    let mut seen = BTreeSet::new();
+    let mut hops = 0;
+    while let Some(component) = remaining.pop() {
+        if component == b".." {
+            resolved.pop();
+            continue;
+        }
+        let parent = resolved.join(&b'/');
+        if let Some(file) = (!parent.is_empty()).then(|| lookup(&parent)).flatten() {
+            if file.header.file_type() != FileType::Directory {
+                return Err(FsError::NotADirectory);
+            }
+        }
+        resolved.push(component);
+        let Some(file) = lookup(&resolved.join(&b'/')) else { continue };
+        if file.header.file_type() != FileType::Symlink {
+            continue;
+        }
+        hops += 1;
+        if hops > max_hops || !seen.insert((resolved.clone(), remaining.clone())) {
+            return Err(FsError::TooManyLinks);
+        }
+        resolved.pop();
+        if file.data.starts_with(b"/") {
+            resolved.clear();
+        }
+        remaining.extend(path_components(&file.data).into_iter().rev().map(<[u8]>::to_vec));
+    }
+    let path = resolved.join(&b'/');
+    match lookup(&path) {
+        Some(file) => Ok((path, file)),
+        None if path.is_empty() || has_children(&path) => Ok((path, &IMPLIED_DIR)),
+        None => Err(FsError::NotFound),
+    }
+}
+
+impl ReadOnlyFs for EffectiveView<'_> {
+    fn lookup(&self, path: &str) -> Option<&File> {
+        self.entries.get(normalize_path(path.as_bytes())).copied()
+    }
+
+    fn readdir(&self, path: &str) -> Result<Vec<&[u8]>, FsError> {
+        let dir = normalize_path(path.as_bytes());
+        let own = self.entries.get(dir);
+        if let Some(file) = own {
+            if file.header.file_type() != FileType::Directory {
+                return Err(FsError::NotADirectory);
+            }
+        }
+        let children: BTreeSet<_> = self.entries.range::<[u8], _>((Bound::Included(dir), Bound::Unbounded))
+            .skip_while(|(&path, _)| path == dir)
+            .take_while(|(&path, _)| dir.is_empty() || path.starts_with(dir))
+            .filter_map(|(&path, _)| child_name(dir, path))
+            .collect();
+        if children.is_empty() && !dir.is_empty() && own.is_none() {
+            return Err(FsError::NotFound);
+        }
+        Ok(children.into_iter().collect())
+    }
+}
+
+impl<'a> EffectiveView<'a> {
+    /// Merges entries in extraction order, with later entries overriding earlier ones.
+    /// `METADATA!!!` entries are attributes of the preceding entry, not part of the tree.
+    pub(crate) fn new(files: impl IntoIterator<Item = &'a File>) -> EffectiveView<'a> {
+        let mut entries = BTreeMap::new();
+        for file in files.into_iter().filter(|file| !file.is_trailer() && !file.is_metadata()) {
+            let path = normalize_path(&file.filename);
+            if !path.is_empty() {
+                entries.insert(path, file);
+            }
+        }
+        EffectiveView { entries }
+    }
+}
+
+impl Initramfs {
+    /// Merges all parsed archives, with later entries overriding earlier ones like on extraction.
+    pub fn effective_view(&self) -> EffectiveView<'_> {
+        EffectiveView::new(self.files())
+    }
+}
+
+/// Returns the first component of `path` below `dir`, if `path` is inside `dir`.
+fn child_name<'a>(dir: &[u8], path: &'a [u8]) -> Option<&'a [u8]> {
+    let rest = if dir.is_empty() {
+        path
+    } else {
+        path.strip_prefix(dir)?.strip_prefix(b"/")?
+    };
+    rest.split(|&b| b == b'/').next().filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn resolve_implied_directories() {
+        let mut archive = Archive::new();
+        archive.add_file(File::new("implied/dir/file".to_string(), b"data".to_vec()));
+        archive.add_file(File::new_symlink("link".to_string(), b"implied/dir".to_vec()));
+        archive.add_trailer();
+
+        for path in ["implied/dir", "implied/", "link", ""] {
+            let dir = archive.resolve(path, MAX_SYMLINKS).unwrap();
+            assert_eq!(dir.header.file_type(), FileType::Directory);
+            assert_eq!(dir.header.permissions(), 0o755);
+        }
+        assert_eq!(archive.resolve("link/file", MAX_SYMLINKS).unwrap().data, b"data");
+        assert_eq!(archive.resolve("implied/di", MAX_SYMLINKS), Err(FsError::NotFound));
+        assert_eq!(archive.resolve("implied/dir/file/x", MAX_SYMLINKS), Err(FsError::NotADirectory));
+
+        let view = EffectiveView::new(&archive.files);
+        assert_eq!(view.resolve(b"link", MAX_SYMLINKS).unwrap().header.file_type(), FileType::Directory);
+        assert_eq!(view.resolve(b"implied/di", MAX_SYMLINKS), Err(FsError::NotFound));
+    }
+}
@@ -0,0 +1,67 @@
+use alloc::vec::Vec;
+
+use crate::{normalize_path, Archive, File};
+
+/// Entry yielded by [`Walk`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WalkEntry<'a> {
+    /// number of path components, `0` for the root directory `.`
+    pub depth: usize,
+    pub components: Vec<&'a [u8]>,
+    pub file: &'a File,
+}
+
+/// Depth-first iterator over the logical tree of an archive, see [`Archive::walk`].
+#[derive(Debug, Clone)]
+pub struct Walk<'a> {
+    entries: Vec<(Vec<&'a [u8]>, &'a File)>,
+    index: usize,
+    /// components of the most recently yielded entry
+    last: Option<Vec<&'a [u8]>>,
+}
+
+impl<'a> Walk<'a> {
+    /// Skips all entries below the most recently yielded entry.
+    pub fn skip_subtree(&mut self) {
+        let Some(last) = self.last.as_ref() else { return };
+        while let Some((components, _)) = self.entries.get(self.index) {
+            if components.len() <= last.len() || !components.starts_with(last) {
+                break;
+            }
+            self.index += 1;
+        }
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = WalkEntry<'a>;
+
+    fn next(&mut self) -> Option<WalkEntry<'a>> {
+        let (components, file) = self.entries.get(self.index)?.clone();
+        self.index += 1;
+        self.last = Some(components.clone());
+        Some(WalkEntry { depth: components.len(), components, file })
+    }
+}
+
+impl Archive {
+    /// Iterates over all entries (except the trailer) in depth-first order, with every directory
+    /// directly followed by its contents, regardless of the order in the archive.
+    ///
+    /// Entries with the same path are yielded in archive order.
+    /// Parent directories without an entry of their own are not yielded.
+    pub fn walk(&self) -> Walk<'_> {
+        let mut entries: Vec<_> = self.files.iter()
+            .filter(|file| !file.is_trailer())
+            .map(|file| (path_components(&file.filename), file))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Walk { entries, index: 0, last: None }
+    }
+}
+
+pub(crate) fn path_components(path: &[u8]) -> Vec<&[u8]> {
+    normalize_path(path).split(|&b| b == b'/')
+        .filter(|component| !component.is_empty() && *component != b".")
+        .collect()
+}
@@ -0,0 +1,45 @@
+//! [`wasm_bindgen`](mod@wasm_bindgen) wrappers for inspecting an initramfs from JavaScript.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::Initramfs;
+
+/// Parsed initramfs, exported to JavaScript as `Initramfs`.
+#[wasm_bindgen(js_name = Initramfs)]
+pub struct WasmInitramfs(Initramfs);
+
+/// Entry listed by [`WasmInitramfs::entries`].
+#[wasm_bindgen(getter_with_clone)]
+pub struct WasmEntry {
+    /// filename, lossily converted to UTF-8
+    pub filename: String,
+    pub mode: u32,
+    pub size: u32,
+    pub mtime: u32,
+}
+
+#[wasm_bindgen(js_class = Initramfs)]
+impl WasmInitramfs {
+    /// Parses an initramfs from a `Uint8Array`.
+    pub fn parse(data: &[u8]) -> Result<WasmInitramfs, JsError> {
+        Initramfs::parse(data).map(WasmInitramfs).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Lists all entries excluding trailers.
+    pub fn entries(&self) -> Vec<WasmEntry> {
+        self.0.files_without_trailers().map(|file| WasmEntry {
+            filename: String::from_utf8_lossy(&file.filename).into_owned(),
+            mode: file.header.mode,
+            size: file.header.filesize,
+            mtime: file.header.mtime,
+        }).collect()
+    }
+
+    /// Returns the data of the entry the kernel would extract for `path`, if any.
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        self.0.lookup(path).map(|(_, file)| file.data.clone())
+    }
+}
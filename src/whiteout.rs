@@ -0,0 +1,237 @@
+// format: https://github.com/opencontainers/image-spec/blob/main/layer.md#whiteouts
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Bound;
+
+use crate::{normalize_path, Archive, File, FileType, Initramfs, MaybeRawArchive};
+
+const PREFIX: &[u8] = b".wh.";
+const OPAQUE: &[u8] = b".wh..wh..opq";
+
+/// Deletion marker of an overlay layer, see [`File::whiteout`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Whiteout {
+    /// (normalized path) removes the path and everything below it from lower layers
+    Path(Vec<u8>),
+    /// (normalized directory, empty for the root) removes everything below the directory from
+    /// lower layers, but keeps the directory itself
+    Opaque(Vec<u8>),
+}
+
+impl Whiteout {
+    /// Whether the whiteout removes `path` of a lower layer.
+    pub fn hides(&self, path: &[u8]) -> bool {
+        let path = normalize_path(path);
+        match self {
+            Whiteout::Path(removed) => path == removed || is_below(path, removed),
+            Whiteout::Opaque(dir) => is_below(path, dir),
+        }
+    }
+}
+
+fn is_below(path: &[u8], dir: &[u8]) -> bool {
+    if dir.is_empty() {
+        return !path.is_empty();
+    }
+    path.strip_prefix(dir).is_some_and(|rest| rest.starts_with(b"/"))
+}
+
+/// Joins `name` to the normalized `dir`.
+fn join(dir: &[u8], name: &[u8]) -> Vec<u8> {
+    let mut path = dir.to_vec();
+    if !path.is_empty() {
+        path.push(b'/');
+    }
+    path.extend_from_slice(name);
+    path
+}
+
+impl File {
+    /// Creates a marker removing `path` and everything below it from lower layers when
+    /// flattening (see [`Initramfs::flatten`]), stored like in OCI layers as an empty regular
+    /// file `.wh.<name>` next to it.
+    pub fn new_whiteout(path: String) -> File {
+        let path = normalize_path(path.as_bytes());
+        let (dir, name) = match path.iter().rposition(|&b| b == b'/') {
+            Some(slash) => (&path[..slash], &path[slash + 1..]),
+            None => (&b""[..], path),
+        };
+        let mut file = File::new(String::new(), Vec::new());
+        file.set_filename(join(dir, &[PREFIX, name].concat()));
+        file
+    }
+
+    /// Creates a marker removing everything below `dir` from lower layers when flattening,
+    /// stored as an empty regular file `.wh..wh..opq` in it. The directory itself is kept.
+    pub fn new_opaque(dir: String) -> File {
+        let mut file = File::new(String::new(), Vec::new());
+        file.set_filename(join(normalize_path(dir.as_bytes()), OPAQUE));
+        file
+    }
+
+    /// Parses a whiteout marker, a regular file named `.wh.<name>` or `.wh..wh..opq`.
+    pub fn whiteout(&self) -> Option<Whiteout> {
+        if self.header.file_type() != FileType::Regular || self.is_trailer() || self.is_metadata() {
+            return None;
+        }
+        let path = normalize_path(&self.filename);
+        let (dir, name) = match path.iter().rposition(|&b| b == b'/') {
+            Some(slash) => (&path[..slash], &path[slash + 1..]),
+            None => (&b""[..], path),
+        };
+        if name == OPAQUE {
+            Some(Whiteout::Opaque(dir.to_vec()))
+        } else {
+            name.strip_prefix(PREFIX).filter(|name| !name.is_empty()).map(|name| Whiteout::Path(join(dir, name)))
+        }
+    }
+}
+
+/// Flattened tree of normalized paths to each entry followed by its `METADATA!!!` entries.
+pub(crate) type Tree = BTreeMap<Vec<u8>, Vec<File>>;
+
+/// Applies a layer to the tree: first its whiteouts to the lower layers, then its entries.
+///
+/// Hard links become separate files, with the data of the entry of their inode storing it.
+pub(crate) fn apply_layer(tree: &mut Tree, files: &[File]) {
+    for whiteout in files.iter().filter_map(File::whiteout) {
+        match whiteout {
+            Whiteout::Path(path) => {
+                tree.remove(&path);
+                remove_children(tree, &path);
+            }
+            Whiteout::Opaque(dir) => remove_children(tree, &dir),
+        }
+    }
+    let mut link_data = BTreeMap::new();
+    for file in files {
+        if file.header.file_type() == FileType::Regular && file.header.nlink > 1 && !file.data.is_empty() {
+            link_data.insert((file.header.maj, file.header.min, file.header.ino), &file.data);
+        }
+    }
+    let mut i = 0;
+    while i < files.len() {
+        let file = &files[i];
+        let metadata = files[i + 1..].iter().take_while(|file| file.is_metadata()).count();
+        let group = &files[i..i + 1 + metadata];
+        i += 1 + metadata;
+        let path = normalize_path(&file.filename);
+        if path.is_empty() || file.is_trailer() || file.is_metadata() || file.whiteout().is_some() {
+            continue;
+        }
+        let mut entry = group.to_vec();
+        let file = &mut entry[0];
+        file.set_filename(path.to_vec());
+        if file.header.file_type() != FileType::Directory && file.header.nlink > 1 {
+            if let Some(data) = link_data.get(&(file.header.maj, file.header.min, file.header.ino)) {
+                file.data = data.to_vec();
+                file.header.filesize = data.len() as u32;
+            }
+            file.header.nlink = 1;
+        }
+        let is_dir = file.header.file_type() == FileType::Directory;
+        let replaces_dir = tree.get(path).is_some_and(|old| old[0].header.file_type() == FileType::Directory);
+        // a directory keeps the contents of lower layers unless replaced by another type
+        if replaces_dir && !is_dir {
+            remove_children(tree, path);
+        }
+        tree.insert(path.to_vec(), entry);
+    }
+}
+
+/// Removes all entries below `dir`, everything for the root.
+fn remove_children(tree: &mut Tree, dir: &[u8]) {
+    if dir.is_empty() {
+        tree.clear();
+        return;
+    }
+    let mut prefix = dir.to_vec();
+    prefix.push(b'/');
+    let children: Vec<_> = tree.range::<[u8], _>((Bound::Included(&prefix[..]), Bound::Unbounded))
+        .map(|(path, _)| path)
+        .take_while(|path| path.starts_with(&prefix))
+        .cloned()
+        .collect();
+    for child in children {
+        tree.remove(&child);
+    }
+}
+
+/// Creates an archive of the tree sorted by path, with fresh inode numbers and without trailer.
+pub(crate) fn tree_to_archive(tree: Tree) -> Archive {
+    let mut archive = Archive::new();
+    for file in tree.into_values().flatten() {
+        archive.add_file(file);
+    }
+    archive
+}
+
+impl Initramfs {
+    /// Merges the parsed archives like the layers of an overlay or container image: each
+    /// archive's whiteouts (see [`File::whiteout`]) remove paths of the archives before it and
+    /// are dropped, then its entries override earlier ones.
+    ///
+    /// Like [`Archive::from_dir`], hard links are stored as separate files, entries are sorted by
+    /// path and no trailer is added.
+    pub fn flatten(&self) -> Archive {
+        let mut tree = Tree::new();
+        for archive in &self.archives {
+            if let MaybeRawArchive::Parsed(archive) = archive {
+                apply_layer(&mut tree, &archive.files);
+            }
+        }
+        tree_to_archive(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn whiteout_markers() {
+        assert_eq!(File::new_whiteout("/etc/old".to_string()).filename, b"etc/.wh.old");
+        assert_eq!(File::new_whiteout("file".to_string()).whiteout(), Some(Whiteout::Path(b"file".to_vec())));
+        assert_eq!(File::new_opaque("./usr/lib/".to_string()).whiteout(), Some(Whiteout::Opaque(b"usr/lib".to_vec())));
+        assert_eq!(File::new_opaque(String::new()).whiteout(), Some(Whiteout::Opaque(Vec::new())));
+        assert_eq!(File::new("etc/.wh.".to_string(), Vec::new()).whiteout(), None);
+        assert_eq!(File::new_symlink("etc/.wh.old".to_string(), b"x".to_vec()).whiteout(), None);
+
+        let whiteout = Whiteout::Path(b"etc/old".to_vec());
+        assert!(whiteout.hides(b"./etc/old") && whiteout.hides(b"etc/old/file"));
+        assert!(!whiteout.hides(b"etc/older") && !whiteout.hides(b"etc"));
+        let opaque = Whiteout::Opaque(b"etc".to_vec());
+        assert!(opaque.hides(b"etc/passwd") && !opaque.hides(b"etc"));
+        assert!(Whiteout::Opaque(Vec::new()).hides(b"etc") && !Whiteout::Opaque(Vec::new()).hides(b"."));
+    }
+
+    #[test]
+    fn flatten_applies_whiteouts() {
+        let mut lower = Archive::new();
+        lower.add_file(File::new("etc/".to_string(), Vec::new()));
+        lower.add_file(File::new("etc/passwd".to_string(), b"lower".to_vec()));
+        lower.add_file(File::new_xattrs(&[(b"user.a".to_vec(), b"1".to_vec())]));
+        lower.add_file(File::new("etc/old/".to_string(), Vec::new()));
+        lower.add_file(File::new("etc/old/file".to_string(), Vec::new()));
+        lower.add_file(File::new("lib/".to_string(), Vec::new()));
+        lower.add_file(File::new("lib/libc.so".to_string(), Vec::new()));
+        lower.add_trailer();
+        let mut upper = Archive::new();
+        upper.add_file(File::new_whiteout("etc/old".to_string()));
+        upper.add_file(File::new_opaque("lib".to_string()));
+        upper.add_file(File::new("lib/ld.so".to_string(), Vec::new()));
+        upper.add_file(File::new("etc/hosts".to_string(), Vec::new()));
+        upper.add_trailer();
+        let mut initramfs = Initramfs::new();
+        initramfs.archives.push(MaybeRawArchive::Parsed(lower));
+        initramfs.archives.push(MaybeRawArchive::Parsed(upper));
+
+        let flattened = initramfs.flatten();
+        let names: Vec<_> = flattened.files.iter().map(|file| &file.filename[..]).collect();
+        assert_eq!(names, [&b"etc"[..], b"etc/hosts", b"etc/passwd", b"METADATA!!!", b"lib", b"lib/ld.so"]);
+        assert_eq!(flattened.xattrs(2), [(&b"user.a"[..], &b"1"[..])]);
+    }
+}
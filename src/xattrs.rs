@@ -0,0 +1,123 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::mode::S_IFREG;
+use crate::{normalize_path, Archive, File, Initramfs, MaybeRawArchive};
+
+/// (name, value) of an extended attribute
+pub type Xattr<'a> = (&'a [u8], &'a [u8]);
+
+/// `c_type` of metadata records containing an extended attribute.
+const TYPE_XATTR: u8 = 1;
+/// xattrs storing POSIX ACLs
+#[cfg(feature = "xattrs")]
+pub(crate) const ACL_XATTRS: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+/// `c_size` (8 hex digits), `c_version` and `c_type`
+const RECORD_HEADER_LEN: usize = 10;
+
+impl File {
+    /// Creates a `METADATA!!!` entry storing extended attributes (name, value) of the preceding
+    /// entry, as proposed for the kernel's initramfs unpacker to restore e.g. `security.selinux`
+    /// and `security.capability`.
+    ///
+    /// The data consists of one record per xattr: its total size as 8 hex digits, version 1,
+    /// type 1 (xattr) and `name\0value`.
+    ///
+    /// Stock kernels don't support this and extract the entry as a regular file `/METADATA!!!`.
+    pub fn new_xattrs(xattrs: &[(Vec<u8>, Vec<u8>)]) -> File {
+        let mut data = Vec::new();
+        for (name, value) in xattrs {
+            let size = RECORD_HEADER_LEN + name.len() + 1 + value.len();
+            data.extend_from_slice(alloc::format!("{size:08x}").as_bytes());
+            data.extend_from_slice(&[1, TYPE_XATTR]);
+            data.extend_from_slice(name);
+            data.push(0);
+            data.extend_from_slice(value);
+        }
+        let mut file = File::new(String::from("METADATA!!!"), data);
+        file.header.mode = S_IFREG;
+        file
+    }
+
+    /// Whether this is a `METADATA!!!` entry, see [`File::new_xattrs`].
+    pub fn is_metadata(&self) -> bool {
+        self.filename == b"METADATA!!!"
+    }
+
+    /// Decodes the xattrs (name, value) of a `METADATA!!!` entry, `None` if the records are
+    /// malformed. Records of unknown types are skipped.
+    pub fn parse_xattrs(&self) -> Option<Vec<Xattr<'_>>> {
+        let mut xattrs = Vec::new();
+        let mut rest = &self.data[..];
+        while !rest.is_empty() {
+            let size = rest.get(..8)
+                .and_then(|size| core::str::from_utf8(size).ok())
+                .and_then(|size| usize::from_str_radix(size, 16).ok())?;
+            let record = rest.get(..size).filter(|record| record.len() >= RECORD_HEADER_LEN)?;
+            rest = &rest[size..];
+            if record[8] != 1 {
+                return None;
+            }
+            if record[9] != TYPE_XATTR {
+                continue;
+            }
+            let content = &record[RECORD_HEADER_LEN..];
+            let name_len = content.iter().position(|&b| b == 0)?;
+            xattrs.push((&content[..name_len], &content[name_len + 1..]));
+        }
+        Some(xattrs)
+    }
+}
+
+impl Archive {
+    /// Extended attributes of the entry at `index`, stored in the `METADATA!!!` entries
+    /// directly following it.
+    pub fn xattrs(&self, index: usize) -> Vec<Xattr<'_>> {
+        self.files.iter()
+            .skip(index + 1)
+            .take_while(|file| file.is_metadata())
+            .filter_map(File::parse_xattrs)
+            .flatten()
+            .collect()
+    }
+
+    /// Sets an extended attribute of the entry at `index`, replacing the `METADATA!!!` entries
+    /// following it with a single one. Records other than xattrs are dropped.
+    pub fn set_xattr(&mut self, index: usize, name: &[u8], value: &[u8]) {
+        let end = index + 1 + self.files[index + 1..].iter().take_while(|file| file.is_metadata()).count();
+        let mut metadata = with_xattr(&self.files[index + 1..end], name, value);
+        metadata.header.ino = self.files.iter().map(|file| file.header.ino).max().map_or(0, |ino| ino + 1);
+        self.files.splice(index + 1..end, [metadata]);
+    }
+}
+
+/// Single `METADATA!!!` entry with the xattrs of `metadata` and `name` set to `value`.
+pub(crate) fn with_xattr(metadata: &[File], name: &[u8], value: &[u8]) -> File {
+    let mut xattrs: Vec<_> = metadata.iter()
+        .filter_map(File::parse_xattrs)
+        .flatten()
+        .filter(|&(other, _)| other != name)
+        .map(|(name, value)| (name.to_vec(), value.to_vec()))
+        .collect();
+    xattrs.push((name.to_vec(), value.to_vec()));
+    xattrs.sort();
+    File::new_xattrs(&xattrs)
+}
+
+impl Initramfs {
+    /// Extended attributes of the entries of the effective tree (see [`Initramfs::effective_view`])
+    /// by normalized path.
+    pub fn effective_xattrs(&self) -> BTreeMap<&[u8], Vec<Xattr<'_>>> {
+        let mut xattrs = BTreeMap::new();
+        for archive in &self.archives {
+            let MaybeRawArchive::Parsed(archive) = archive else { continue };
+            for (i, file) in archive.files.iter().enumerate() {
+                if !file.is_trailer() && !file.is_metadata() {
+                    xattrs.insert(normalize_path(&file.filename), archive.xattrs(i));
+                }
+            }
+        }
+        xattrs
+    }
+}